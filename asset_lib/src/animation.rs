@@ -0,0 +1,275 @@
+use super::{Asset, AssetError, AssetFile, AssetType, Result};
+
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+const ANIMATION_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LoopMode {
+  #[default]
+  Clamp,
+  Loop,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+  Step,
+  Linear,
+  /// Every keyframe stores three values: in tangent, value and out tangent, same as in gltf.
+  CubicSpline,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum ChannelProperty {
+  Translation,
+  /// Rotations are stored as xyzw quaternions.
+  Rotation,
+  Scale,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AnimationSampler {
+  pub timestamps: Vec<f32>,
+  /// Translations and scales only use the xyz components.
+  pub values: Vec<glm::Vec4>,
+  pub interpolation: Interpolation,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AnimationChannel {
+  pub node_index: usize,
+  pub property: ChannelProperty,
+  pub sampler: usize,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct AnimationClip {
+  pub name: String,
+  pub duration: f32,
+  pub loop_mode: LoopMode,
+  pub samplers: Vec<AnimationSampler>,
+  pub channels: Vec<AnimationChannel>,
+}
+
+impl AnimationClip {
+  pub fn load_animation(asset: AssetFile) -> Result<Self> {
+    if asset.asset_type != AssetType::Animation {
+      return Err(AssetError::IncorrectType("Animation", asset.asset_type.name()));
+    }
+
+    if asset.version < ANIMATION_VERSION {
+      return Err(AssetError::OldVersion);
+    }
+
+    let animation: Self = serde_json::from_str(&asset.json)?;
+    Ok(animation)
+  }
+
+  /// Evaluates the clip at `time` seconds and returns the local transform of every animated node.
+  /// `rest_transforms` are the local transforms of the nodes, indexed like the scene nodes, which properties without a channel keep.
+  /// Nodes past the end of `rest_transforms` rest at the identity.
+  pub fn sample(&self, time: f32, rest_transforms: &[glm::Mat4]) -> Vec<(usize, glm::Mat4)> {
+    let time = match self.loop_mode {
+      LoopMode::Clamp => time.clamp(0.0, self.duration.max(0.0)),
+      LoopMode::Loop if self.duration > 0.0 => time.rem_euclid(self.duration),
+      LoopMode::Loop => 0.0,
+    };
+
+    let mut poses: BTreeMap<usize, NodePose> = BTreeMap::new();
+    for channel in &self.channels {
+      let Some(sampler) = self.samplers.get(channel.sampler) else {
+        continue;
+      };
+      let Some(value) = sampler.sample(time, channel.property == ChannelProperty::Rotation) else {
+        continue;
+      };
+
+      let pose = poses
+        .entry(channel.node_index)
+        .or_insert_with(|| rest_transforms.get(channel.node_index).map(NodePose::from_matrix).unwrap_or_default());
+      match channel.property {
+        ChannelProperty::Translation => pose.translation = value.xyz(),
+        ChannelProperty::Rotation => pose.rotation = glm::Quat::from_vector(value),
+        ChannelProperty::Scale => pose.scale = value.xyz(),
+      }
+    }
+
+    poses.into_iter().map(|(node_index, pose)| (node_index, pose.to_matrix())).collect()
+  }
+}
+
+impl Asset for AnimationClip {
+  fn convert_to_asset(self) -> Result<AssetFile> {
+    let json = serde_json::to_string(&self)?;
+    Ok(AssetFile {
+      asset_type: AssetType::Animation,
+      version: ANIMATION_VERSION,
+      json,
+      blob: Vec::new(),
+    })
+  }
+}
+
+impl AnimationSampler {
+  fn sample(&self, time: f32, is_rotation: bool) -> Option<glm::Vec4> {
+    let keyframe_count = self.timestamps.len();
+    if keyframe_count == 0 {
+      return None;
+    }
+
+    let next = self.timestamps.partition_point(|timestamp| *timestamp <= time);
+    if next == 0 {
+      return self.keyframe_value(0);
+    }
+    if next == keyframe_count {
+      return self.keyframe_value(keyframe_count - 1);
+    }
+
+    let previous = next - 1;
+    let delta = self.timestamps[next] - self.timestamps[previous];
+    let factor = if delta > 0.0 { (time - self.timestamps[previous]) / delta } else { 0.0 };
+
+    let value = match self.interpolation {
+      Interpolation::Step => self.keyframe_value(previous)?,
+      Interpolation::Linear => {
+        let start = self.keyframe_value(previous)?;
+        let mut end = self.keyframe_value(next)?;
+        // Flipping the sign keeps the rotation on the shortest path
+        if is_rotation && start.dot(&end) < 0.0 {
+          end = -end;
+        }
+        glm::lerp(&start, &end, factor)
+      }
+      Interpolation::CubicSpline => {
+        let start = self.keyframe_value(previous)?;
+        let start_tangent = *self.values.get(previous * 3 + 2)? * delta;
+        let end = self.keyframe_value(next)?;
+        let end_tangent = *self.values.get(next * 3)? * delta;
+
+        let factor_2 = factor * factor;
+        let factor_3 = factor_2 * factor;
+        start * (2.0 * factor_3 - 3.0 * factor_2 + 1.0)
+          + start_tangent * (factor_3 - 2.0 * factor_2 + factor)
+          + end * (-2.0 * factor_3 + 3.0 * factor_2)
+          + end_tangent * (factor_3 - factor_2)
+      }
+    };
+
+    if is_rotation {
+      return Some(value.normalize());
+    }
+
+    Some(value)
+  }
+
+  fn keyframe_value(&self, keyframe: usize) -> Option<glm::Vec4> {
+    match self.interpolation {
+      Interpolation::CubicSpline => self.values.get(keyframe * 3 + 1).copied(),
+      _ => self.values.get(keyframe).copied(),
+    }
+  }
+}
+
+//----------------------------Helpers--------------------------------------
+
+struct NodePose {
+  translation: glm::Vec3,
+  rotation: glm::Quat,
+  scale: glm::Vec3,
+}
+
+impl Default for NodePose {
+  fn default() -> Self {
+    Self {
+      translation: glm::Vec3::zeros(),
+      rotation: glm::Quat::identity(),
+      scale: glm::Vec3::new(1.0, 1.0, 1.0),
+    }
+  }
+}
+
+impl NodePose {
+  /// Splits a transform built from translation, rotation and scale back into them, shear is lost.
+  fn from_matrix(matrix: &glm::Mat4) -> Self {
+    let translation = glm::column(matrix, 3).xyz();
+    let scale = glm::vec3(glm::column(matrix, 0).xyz().norm(), glm::column(matrix, 1).xyz().norm(), glm::column(matrix, 2).xyz().norm());
+
+    // A zero scale leaves nothing to recover the rotation from
+    let rotation = if scale.min() > 0.0 {
+      glm::mat3_to_quat(&glm::mat4_to_mat3(&glm::scale(matrix, &glm::vec3(1.0 / scale.x, 1.0 / scale.y, 1.0 / scale.z))))
+    } else {
+      glm::Quat::identity()
+    };
+
+    Self { translation, rotation, scale }
+  }
+
+  fn to_matrix(&self) -> glm::Mat4 {
+    glm::translation(&self.translation) * glm::quat_to_mat4(&self.rotation) * glm::scaling(&self.scale)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn quarter_turn() -> glm::Quat {
+    glm::quat_angle_axis(std::f32::consts::FRAC_PI_2, &glm::Vec3::y())
+  }
+
+  fn single_channel_clip(property: ChannelProperty, values: Vec<glm::Vec4>) -> AnimationClip {
+    AnimationClip {
+      name: "clip".to_owned(),
+      duration: 1.0,
+      loop_mode: LoopMode::Clamp,
+      samplers: vec![AnimationSampler {
+        timestamps: vec![0.0, 1.0],
+        values,
+        interpolation: Interpolation::Linear,
+      }],
+      channels: vec![AnimationChannel { node_index: 0, property, sampler: 0 }],
+    }
+  }
+
+  fn assert_mat_eq(actual: &glm::Mat4, expected: &glm::Mat4) {
+    for (a, e) in actual.iter().zip(expected.iter()) {
+      assert!((a - e).abs() < 1e-5, "{actual} != {expected}");
+    }
+  }
+
+  #[test]
+  fn rotation_only_clip_keeps_rest_translation_and_scale() {
+    let clip = single_channel_clip(ChannelProperty::Rotation, vec![glm::Quat::identity().coords, quarter_turn().coords]);
+    let rest = glm::translation(&glm::vec3(1.0, 2.0, 3.0)) * glm::scaling(&glm::vec3(2.0, 2.0, 2.0));
+
+    let poses = clip.sample(1.0, &[rest, glm::Mat4::identity()]);
+
+    assert_eq!(poses.len(), 1);
+    assert_eq!(poses[0].0, 0);
+    let expected = glm::translation(&glm::vec3(1.0, 2.0, 3.0)) * glm::quat_to_mat4(&quarter_turn()) * glm::scaling(&glm::vec3(2.0, 2.0, 2.0));
+    assert_mat_eq(&poses[0].1, &expected);
+  }
+
+  #[test]
+  fn translation_only_clip_keeps_rest_rotation() {
+    let clip = single_channel_clip(ChannelProperty::Translation, vec![glm::vec4(0.0, 0.0, 0.0, 0.0), glm::vec4(4.0, 0.0, 0.0, 0.0)]);
+    let rest = glm::quat_to_mat4(&quarter_turn());
+
+    let poses = clip.sample(0.5, &[rest]);
+
+    let expected = glm::translation(&glm::vec3(2.0, 0.0, 0.0)) * glm::quat_to_mat4(&quarter_turn());
+    assert_mat_eq(&poses[0].1, &expected);
+  }
+
+  #[test]
+  fn nodes_without_rest_transform_rest_at_identity() {
+    let clip = single_channel_clip(ChannelProperty::Rotation, vec![quarter_turn().coords, quarter_turn().coords]);
+
+    let poses = clip.sample(0.0, &[]);
+
+    assert_mat_eq(&poses[0].1, &glm::quat_to_mat4(&quarter_turn()));
+  }
+}
@@ -1,4 +1,4 @@
-use super::Result;
+use super::{AssetWarning, Result};
 
 use serde::{Deserialize, Serialize};
 
@@ -6,6 +6,11 @@ use std::fs::File;
 
 pub trait Asset {
   fn convert_to_asset(self) -> Result<AssetFile>;
+
+  /// Checks the integrity of the asset, errors make the asset unusable while warnings are only worth reporting.
+  fn validate(&self) -> Result<Vec<AssetWarning>> {
+    Ok(Vec::new())
+  }
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
@@ -13,6 +18,8 @@ pub enum AssetType {
   Model = 1,
   Scene = 2,
   Pipeline = 3,
+  Texture = 4,
+  Animation = 5,
 }
 
 impl AssetType {
@@ -21,11 +28,13 @@ impl AssetType {
       AssetType::Model => "Model",
       AssetType::Scene => "Scene",
       AssetType::Pipeline => "Pipeline",
+      AssetType::Texture => "Texture",
+      AssetType::Animation => "Animation",
     }
   }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq)]
 pub struct AssetFile {
   pub(crate) asset_type: AssetType,
   pub(crate) version: u32,
@@ -76,6 +85,14 @@ impl AssetArchive {
     Ok(Self { zip_writer })
   }
 
+  /// Opens an existing archive to add more assets to it, the assets already in it are kept.
+  pub fn open(path: &str) -> Result<Self> {
+    let file = File::options().read(true).write(true).open(path)?;
+    let zip_writer = zip::ZipWriter::new_append(file)?;
+
+    Ok(Self { zip_writer })
+  }
+
   pub fn add_asset_file(&mut self, asset_file: AssetFile, filename: &str) -> Result<()> {
     let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
     self.zip_writer.start_file(filename, options)?;
@@ -88,6 +105,47 @@ impl AssetArchive {
     Ok(())
   }
 
+  /// Replaces a single asset of an existing archive, or adds it if the archive doesn't have it yet.
+  pub fn update_asset_in_place(path: &str, asset_name: &str, new_asset: AssetFile) -> Result<()> {
+    Self::update_assets_in_place(path, vec![(asset_name.to_owned(), new_asset)], &[])
+  }
+
+  /// Replaces or adds all the given assets of an existing archive at once and drops the entries named in `removed_assets`.
+  /// Zip files can't remove entries, so every other entry gets copied as is into a new archive which then replaces the old one.
+  /// The original archive stays untouched if anything fails.
+  pub fn update_assets_in_place(path: &str, assets: Vec<(String, AssetFile)>, removed_assets: &[String]) -> Result<()> {
+    let temp_path = format!("{path}.tmp");
+
+    // The archive has to be done writing before the original file gets replaced
+    let result = Self::write_updated_archive(path, &temp_path, assets, removed_assets).and_then(|_| std::fs::rename(&temp_path, path).map_err(Into::into));
+    if result.is_err() {
+      let _ = std::fs::remove_file(&temp_path);
+    }
+
+    result
+  }
+
+  fn write_updated_archive(path: &str, temp_path: &str, assets: Vec<(String, AssetFile)>, removed_assets: &[String]) -> Result<()> {
+    let file = File::open(path)?;
+    let mut zip_reader = zip::ZipArchive::new(file)?;
+    let mut archive = Self::new(temp_path)?;
+
+    for index in 0..zip_reader.len() {
+      let entry = zip_reader.by_index_raw(index)?;
+      if assets.iter().any(|(asset_name, _)| entry.name() == asset_name) || removed_assets.iter().any(|asset_name| entry.name() == asset_name) {
+        continue;
+      }
+
+      archive.zip_writer.raw_copy_file(entry)?;
+    }
+
+    for (asset_name, asset) in assets {
+      archive.add_asset_file(asset, &asset_name)?;
+    }
+
+    archive.finish()
+  }
+
   pub fn get_assets(path: &str) -> Result<Vec<AssetFile>> {
     let file = File::open(path)?;
     let mut zip_reader = zip::ZipArchive::new(file)?;
@@ -102,4 +160,28 @@ impl AssetArchive {
 
     Ok(assets)
   }
+
+  pub fn get_asset_by_name(archive_path: &str, asset_name: &str) -> Result<Option<AssetFile>> {
+    let file = File::open(archive_path)?;
+    let mut zip_reader = zip::ZipArchive::new(file)?;
+
+    let asset = match zip_reader.by_name(asset_name) {
+      Ok(asset) => asset,
+      Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+      Err(e) => return Err(e.into()),
+    };
+
+    Ok(Some(AssetFile::read_from_reader(asset)?))
+  }
+
+  pub fn asset_exists(archive_path: &str, asset_name: &str) -> bool {
+    let Ok(file) = File::open(archive_path) else {
+      return false;
+    };
+
+    match zip::ZipArchive::new(file) {
+      Ok(zip_reader) => zip_reader.file_names().any(|name| name == asset_name),
+      Err(_) => false,
+    }
+  }
 }
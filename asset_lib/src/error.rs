@@ -16,4 +16,27 @@ pub enum AssetError {
   IncorrectType(&'static str, &'static str),
   #[error("asset version is older than currently supported")]
   OldVersion,
+  #[error("mesh {0} references data past the end of the blob")]
+  MeshOutOfBounds(usize),
+  #[error("node {0} references model {1} which doesn't exist")]
+  InvalidModelReference(usize, usize),
+  #[error("node {0} references child {1} which doesn't exist")]
+  InvalidChildReference(usize, usize),
+  #[error("parent node {0} doesn't exist")]
+  InvalidParentNode(usize),
+  #[error("node {0} is part of a cycle in the scene graph")]
+  SceneCycle(usize),
+  #[error("model has {0} level of detail distances for {1} meshes")]
+  LodMismatch(usize, usize),
+  #[error("mesh {0} references material {1} which doesn't exist")]
+  InvalidMaterialReference(usize, usize),
+}
+
+/// Issues found by `Asset::validate` that don't stop the asset from being used.
+#[derive(Error, Debug)]
+pub enum AssetWarning {
+  #[error("mesh {0} has no vertices or indices")]
+  EmptyMesh(usize),
+  #[error("node {0} ({1}) has a transform with zero scale")]
+  ZeroScale(usize, String),
 }
@@ -1,13 +1,17 @@
+mod animation;
 mod asset;
 mod error;
 mod model;
 mod pipeline;
 mod scene;
+mod texture;
 
 pub(crate) use error::Result;
 
+pub use animation::{AnimationChannel, AnimationClip, AnimationSampler, ChannelProperty, Interpolation, LoopMode};
 pub use asset::{Asset, AssetArchive, AssetFile, AssetType};
-pub use error::AssetError;
-pub use model::{HashableVertex, Mesh, Model, Vertex};
-pub use pipeline::{Blending, Pipeline, PipelineManifest};
+pub use error::{AssetError, AssetWarning};
+pub use model::{AlphaMode, HashableVertex, IndexType, Material, Mesh, Model, Vertex};
+pub use pipeline::{Blending, CullMode, Pipeline, PipelineManifest, PolygonMode};
 pub use scene::{Node, Scene};
+pub use texture::{Texture, TextureFormat};
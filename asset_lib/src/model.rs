@@ -1,17 +1,28 @@
-use super::{Asset, AssetError, AssetFile, AssetType, Result};
+use super::{Asset, AssetError, AssetFile, AssetType, AssetWarning, Result};
 
 use nalgebra_glm as glm;
 use serde::{Deserialize, Serialize};
 
 use std::hash::{Hash, Hasher};
 
-const MODEL_VERSION: u32 = 1;
+const MODEL_VERSION: u32 = 5;
+/// Oldest model version that can still be migrated to the current one.
+const MIN_MODEL_VERSION: u32 = 1;
+/// Version 1 models didn't store a vertex stride, their vertices were always laid out as position, normal and tangent.
+const VERSION_1_VERTEX_STRIDE: u32 = 40;
 
-#[derive(Serialize, Deserialize, Default, Hash)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct Model {
   pub name: String,
   pub id: u128,
   pub meshes: Vec<Mesh>,
+  /// Camera distance from which each mesh gets drawn, in multiples of the model's bounding radius.
+  /// Empty for models without levels of detail, which draw all of their meshes.
+  #[serde(default)]
+  pub lod_distances: Vec<f32>,
+  /// Materials used by the meshes, models before version 5 have none.
+  #[serde(default)]
+  pub materials: Vec<Material>,
 
   #[serde(skip)]
   pub blob: Vec<u8>,
@@ -31,13 +42,17 @@ impl Model {
       return Err(AssetError::IncorrectType("Model", asset.asset_type.name()));
     }
 
-    if asset.version < MODEL_VERSION {
+    if asset.version < MIN_MODEL_VERSION {
       return Err(AssetError::OldVersion);
     }
 
     let mut model: Self = serde_json::from_str(&asset.json)?;
     model.blob = asset.blob;
 
+    if asset.version < 2 {
+      model.meshes.iter_mut().for_each(|mesh| mesh.vertex_stride = VERSION_1_VERTEX_STRIDE);
+    }
+
     Ok(model)
   }
 
@@ -49,20 +64,44 @@ impl Model {
 
     let index_count = indices.len() as u32;
     let index_offset = self.blob.len() as u32;
-    let mut index_data = bincode::serialize(&indices)?;
+    let index_type = match indices.iter().all(|index| *index <= u16::MAX as u32) {
+      true => IndexType::U16,
+      false => IndexType::U32,
+    };
+    let mut index_data = match index_type {
+      IndexType::U16 => bincode::serialize(&indices.iter().map(|index| *index as u16).collect::<Vec<u16>>())?,
+      IndexType::U32 => bincode::serialize(&indices)?,
+    };
     self.blob.extend_from_slice(&mut index_data[8..]);
+    // Vertices of the next mesh have to stay 4 byte aligned after an odd amount of 16 bit indices
+    self.blob.resize(self.blob.len().next_multiple_of(4), 0);
 
     let mesh = Mesh {
       vertex_count,
       vertex_offset,
+      vertex_stride: std::mem::size_of::<Vertex>() as u32,
       index_count,
       index_offset,
+      index_type,
+      material: None,
     };
     self.meshes.push(mesh);
     Ok(())
   }
 }
 
+// Written by hand, since the level of detail distances are floats
+impl Hash for Model {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.name.hash(state);
+    self.id.hash(state);
+    self.meshes.hash(state);
+    self.lod_distances.iter().for_each(|distance| state.write_u32(distance.to_bits()));
+    self.materials.hash(state);
+    self.blob.hash(state);
+  }
+}
+
 impl Asset for Model {
   fn convert_to_asset(self) -> Result<AssetFile> {
     let json = serde_json::to_string(&self)?;
@@ -73,14 +112,137 @@ impl Asset for Model {
       blob: self.blob,
     })
   }
+
+  fn validate(&self) -> Result<Vec<AssetWarning>> {
+    let mut warnings = Vec::new();
+
+    if !self.lod_distances.is_empty() && self.lod_distances.len() != self.meshes.len() {
+      return Err(AssetError::LodMismatch(self.lod_distances.len(), self.meshes.len()));
+    }
+
+    for (mesh_index, mesh) in self.meshes.iter().enumerate() {
+      let vertex_end = mesh.vertex_offset as usize + mesh.vertex_count as usize * mesh.vertex_stride as usize;
+      let index_end = mesh.index_offset as usize + mesh.index_count as usize * mesh.index_type.size();
+      if vertex_end > self.blob.len() || index_end > self.blob.len() {
+        return Err(AssetError::MeshOutOfBounds(mesh_index));
+      }
+
+      if let Some(material) = mesh.material.filter(|material| *material as usize >= self.materials.len()) {
+        return Err(AssetError::InvalidMaterialReference(mesh_index, material as usize));
+      }
+
+      if mesh.vertex_count == 0 || mesh.index_count == 0 {
+        warnings.push(AssetWarning::EmptyMesh(mesh_index));
+      }
+    }
+
+    Ok(warnings)
+  }
 }
 
-#[derive(Serialize, Deserialize, Hash)]
+#[derive(Serialize, Deserialize, Hash, Clone, PartialEq, Eq, Debug)]
 pub struct Mesh {
   pub vertex_count: u32,  // amount if vertices in the mesh
   pub vertex_offset: u32, // offset into the buffer where the vertices begin
+  #[serde(default)]
+  pub vertex_stride: u32, // size of a single vertex in the buffer
   pub index_count: u32,   // amount of indices
   pub index_offset: u32,  // offset into the buffer where the indices begin
+  #[serde(default)]
+  pub index_type: IndexType, // models before version 3 always used 32 bit indices
+  #[serde(default)]
+  pub material: Option<u32>, // index into the materials of the model
+}
+
+/// Surface of a mesh. Textures are referenced by their asset name within the archive holding the model, like `Rock_normal.tex`.
+/// Missing fields take the defaults of the gltf specification.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Material {
+  pub name: String,
+  pub normal_texture: Option<String>,
+  pub base_color_factor: glm::Vec4,
+  pub emissive_factor: glm::Vec3,
+  pub metallic_factor: f32,
+  pub roughness_factor: f32,
+  pub normal_scale: f32,
+  pub occlusion_strength: f32,
+  pub alpha_mode: AlphaMode,
+  pub alpha_cutoff: f32,
+  pub double_sided: bool,
+  /// `None` for opaque materials, otherwise how much light passes through the surface.
+  pub transmission_factor: Option<f32>,
+  /// Thickness of the volume behind the surface, zero for thin walled materials.
+  pub thickness_factor: f32,
+  pub attenuation_color: glm::Vec3,
+}
+
+impl Default for Material {
+  fn default() -> Self {
+    Self {
+      name: String::new(),
+      normal_texture: None,
+      base_color_factor: glm::vec4(1.0, 1.0, 1.0, 1.0),
+      emissive_factor: glm::Vec3::zeros(),
+      metallic_factor: 1.0,
+      roughness_factor: 1.0,
+      normal_scale: 1.0,
+      occlusion_strength: 1.0,
+      alpha_mode: AlphaMode::Opaque,
+      alpha_cutoff: 0.5,
+      double_sided: false,
+      transmission_factor: None,
+      thickness_factor: 0.0,
+      attenuation_color: glm::vec3(1.0, 1.0, 1.0),
+    }
+  }
+}
+
+// Written by hand, since most of the factors are floats
+impl Hash for Material {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.name.hash(state);
+    self.normal_texture.hash(state);
+    self.alpha_mode.hash(state);
+    self.double_sided.hash(state);
+    self.transmission_factor.map(f32::to_bits).hash(state);
+
+    let factors = self.base_color_factor.iter().chain(self.emissive_factor.iter()).chain(self.attenuation_color.iter());
+    let scalars = [
+      self.metallic_factor,
+      self.roughness_factor,
+      self.normal_scale,
+      self.occlusion_strength,
+      self.alpha_cutoff,
+      self.thickness_factor,
+    ];
+    factors.chain(scalars.iter()).for_each(|factor| state.write_u32(factor.to_bits()));
+  }
+}
+
+#[derive(Serialize, Deserialize, Hash, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum AlphaMode {
+  #[default]
+  Opaque,
+  Mask,
+  Blend,
+}
+
+#[derive(Serialize, Deserialize, Hash, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum IndexType {
+  U16,
+  #[default]
+  U32,
+}
+
+impl IndexType {
+  /// Size of a single index in bytes.
+  pub fn size(&self) -> usize {
+    match self {
+      IndexType::U16 => std::mem::size_of::<u16>(),
+      IndexType::U32 => std::mem::size_of::<u32>(),
+    }
+  }
 }
 
 #[derive(Serialize, Clone, Copy, PartialEq)]
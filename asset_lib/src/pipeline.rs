@@ -1,4 +1,4 @@
-use super::{Asset, AssetFile, AssetType, Result};
+use super::{Asset, AssetError, AssetFile, AssetType, Result};
 
 use serde::{Deserialize, Serialize};
 
@@ -9,12 +9,36 @@ pub struct Blending {
   pub test: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+  #[default]
+  None,
+  Front,
+  Back,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonMode {
+  #[default]
+  Fill,
+  Line,
+  Point,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Pipeline {
   pub name: String,
   pub vertex_shader: Vec<u8>,
   pub fragment_shader: Vec<u8>,
   pub blending: Blending,
+  #[serde(default = "default_true")]
+  pub depth_test: bool,
+  #[serde(default = "default_true")]
+  pub depth_write: bool,
+  #[serde(default)]
+  pub cull_mode: CullMode,
+  #[serde(default)]
+  pub polygon_mode: PolygonMode,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,6 +47,35 @@ pub struct PipelineManifest {
   pub vertex_shader: String,
   pub fragment_shader: String,
   pub blending: Blending,
+  /// Overrides the vertex shader entry point, defaults to `main` for GLSL and `VSMain` for HLSL.
+  #[serde(default)]
+  pub entry_point_vs: Option<String>,
+  /// Overrides the fragment shader entry point, defaults to `main` for GLSL and `PSMain` for HLSL.
+  #[serde(default)]
+  pub entry_point_fs: Option<String>,
+  #[serde(default = "default_true")]
+  pub depth_test: bool,
+  #[serde(default = "default_true")]
+  pub depth_write: bool,
+  #[serde(default)]
+  pub cull_mode: CullMode,
+  #[serde(default)]
+  pub polygon_mode: PolygonMode,
+}
+
+impl Pipeline {
+  pub fn load_pipeline(asset: AssetFile) -> Result<Self> {
+    if asset.asset_type != AssetType::Pipeline {
+      return Err(AssetError::IncorrectType("Pipeline", asset.asset_type.name()));
+    }
+
+    if asset.version < PIPELINE_VERSION {
+      return Err(AssetError::OldVersion);
+    }
+
+    let pipeline: Self = serde_json::from_str(&asset.json)?;
+    Ok(pipeline)
+  }
 }
 
 impl Asset for Pipeline {
@@ -36,3 +89,7 @@ impl Asset for Pipeline {
     })
   }
 }
+
+fn default_true() -> bool {
+  true
+}
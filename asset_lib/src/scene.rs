@@ -1,9 +1,11 @@
-use super::{Asset, AssetError, AssetFile, AssetType, Result};
+use super::{Asset, AssetError, AssetFile, AssetType, AssetWarning, Result};
 
 use nalgebra_glm as glm;
 use serde::{Deserialize, Serialize};
 
-const SCENE_VERSION: u32 = 1;
+const SCENE_VERSION: u32 = 2;
+/// Oldest scene version that still has a migration path to the current one.
+const MIN_SCENE_VERSION: u32 = 1;
 
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct Scene {
@@ -13,12 +15,31 @@ pub struct Scene {
   parent_nodes: Vec<usize>,
 }
 
-#[derive(Serialize, Deserialize, Default, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Node {
   pub name: String,
   pub transform: glm::Mat4,
   pub children: Vec<usize>,
   pub model: Option<usize>,
+  /// Layers the node belongs to, it only gets drawn by cameras sharing at least one of them.
+  #[serde(default = "default_visibility_mask")]
+  pub visibility_mask: u32,
+}
+
+impl Default for Node {
+  fn default() -> Self {
+    Self {
+      name: String::new(),
+      transform: glm::Mat4::default(),
+      children: Vec::new(),
+      model: None,
+      visibility_mask: default_visibility_mask(),
+    }
+  }
+}
+
+fn default_visibility_mask() -> u32 {
+  u32::MAX
 }
 
 impl Scene {
@@ -27,11 +48,18 @@ impl Scene {
       return Err(AssetError::IncorrectType("Scene", asset.asset_type.name()));
     }
 
-    if asset.version < SCENE_VERSION {
+    if asset.version < MIN_SCENE_VERSION {
       return Err(AssetError::OldVersion);
     }
 
-    let scene: Self = serde_json::from_str(&asset.json)?;
+    let mut json = asset.json;
+    for migration in scene_migrations() {
+      if migration.from_version() >= asset.version && migration.from_version() < SCENE_VERSION {
+        json = migration.migrate(&json)?;
+      }
+    }
+
+    let scene: Self = serde_json::from_str(&json)?;
     Ok(scene)
   }
 
@@ -57,6 +85,10 @@ impl Scene {
     self.nodes.as_ref()
   }
 
+  pub fn nodes_mut(&mut self) -> &mut [Node] {
+    self.nodes.as_mut()
+  }
+
   pub fn parent_nodes(&self) -> &[usize] {
     self.parent_nodes.as_ref()
   }
@@ -72,4 +104,130 @@ impl Asset for Scene {
       blob: Vec::new(),
     })
   }
+
+  fn validate(&self) -> Result<Vec<AssetWarning>> {
+    let mut warnings = Vec::new();
+
+    if let Some(parent_node) = self.parent_nodes.iter().find(|node| **node >= self.nodes.len()) {
+      return Err(AssetError::InvalidParentNode(*parent_node));
+    }
+
+    for (node_index, node) in self.nodes.iter().enumerate() {
+      if let Some(model) = node.model.filter(|model| *model >= self.models.len()) {
+        return Err(AssetError::InvalidModelReference(node_index, model));
+      }
+
+      if let Some(child) = node.children.iter().find(|child| **child >= self.nodes.len()) {
+        return Err(AssetError::InvalidChildReference(node_index, *child));
+      }
+
+      if glm::mat4_to_mat3(&node.transform).determinant().abs() <= f32::EPSILON {
+        warnings.push(AssetWarning::ZeroScale(node_index, node.name.clone()));
+      }
+    }
+
+    // Every node has to be checked, nodes that aren't reachable from a parent node can still form a cycle
+    let mut visit_states = vec![VisitState::Unvisited; self.nodes.len()];
+    for node_index in 0..self.nodes.len() {
+      find_cycle(self, node_index, &mut visit_states)?;
+    }
+
+    Ok(warnings)
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VisitState {
+  Unvisited,
+  InProgress,
+  Done,
+}
+
+fn find_cycle(scene: &Scene, node_index: usize, visit_states: &mut [VisitState]) -> Result<()> {
+  match visit_states[node_index] {
+    VisitState::Done => return Ok(()),
+    VisitState::InProgress => return Err(AssetError::SceneCycle(node_index)),
+    VisitState::Unvisited => (),
+  }
+
+  visit_states[node_index] = VisitState::InProgress;
+  for child in &scene.nodes[node_index].children {
+    find_cycle(scene, *child, visit_states)?;
+  }
+  visit_states[node_index] = VisitState::Done;
+
+  Ok(())
+}
+
+//----------------------------Migrations--------------------------------------
+
+/// Upgrades the json of a scene asset by a single version.
+pub(crate) trait SceneMigration {
+  /// Version of the scenes this migration accepts, the output is always one version newer.
+  fn from_version(&self) -> u32;
+  fn migrate(&self, json: &str) -> Result<String>;
+}
+
+/// Every migration in order of the version it upgrades from.
+fn scene_migrations() -> Vec<Box<dyn SceneMigration>> {
+  vec![Box::new(V1ToV2)]
+}
+
+/// Version 2 added visibility masks to nodes, old nodes should stay visible to every camera.
+struct V1ToV2;
+
+impl SceneMigration for V1ToV2 {
+  fn from_version(&self) -> u32 {
+    1
+  }
+
+  fn migrate(&self, json: &str) -> Result<String> {
+    let mut scene: serde_json::Value = serde_json::from_str(json)?;
+
+    if let Some(nodes) = scene.get_mut("nodes").and_then(|nodes| nodes.as_array_mut()) {
+      for node in nodes.iter_mut().filter_map(|node| node.as_object_mut()) {
+        node.entry("visibility_mask").or_insert(serde_json::Value::from(default_visibility_mask()));
+      }
+    }
+
+    Ok(serde_json::to_string(&scene)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn version_1_scene_loads_with_default_visibility_masks() {
+    let identity = "[1.0,0.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,0.0,1.0]";
+    let json = format!(
+      r#"{{
+        "name": "Old Stage",
+        "models": [7],
+        "nodes": [
+          {{ "name": "Child", "transform": {identity}, "children": [], "model": 0 }},
+          {{ "name": "Root", "transform": {identity}, "children": [0], "model": null }}
+        ],
+        "parent_nodes": [1]
+      }}"#
+    );
+
+    let asset = AssetFile {
+      asset_type: AssetType::Scene,
+      version: 1,
+      json,
+      blob: Vec::new(),
+    };
+    let scene = Scene::load_scene(asset).unwrap();
+
+    assert_eq!(scene.name, "Old Stage");
+    assert_eq!(scene.models(), &[7]);
+    assert_eq!(scene.parent_nodes(), &[1]);
+    assert_eq!(scene.nodes().len(), 2);
+    assert!(scene.nodes().iter().all(|node| node.visibility_mask == default_visibility_mask()));
+    assert_eq!(scene.nodes()[1].children, vec![0]);
+    assert_eq!(scene.nodes()[0].model, Some(0));
+    assert_eq!(scene.nodes()[0].transform, glm::Mat4::identity());
+  }
 }
@@ -0,0 +1,56 @@
+use super::{Asset, AssetError, AssetFile, AssetType, Result};
+
+use serde::{Deserialize, Serialize};
+
+const TEXTURE_VERSION: u32 = 1;
+
+/// Texture stored as tightly packed RGBA8 pixels.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Texture {
+  pub name: String,
+  pub width: u32,
+  pub height: u32,
+  pub format: TextureFormat,
+
+  #[serde(skip)]
+  pub data: Vec<u8>,
+}
+
+impl Texture {
+  pub fn load_texture(asset: AssetFile) -> Result<Self> {
+    if asset.asset_type != AssetType::Texture {
+      return Err(AssetError::IncorrectType("Texture", asset.asset_type.name()));
+    }
+
+    if asset.version < TEXTURE_VERSION {
+      return Err(AssetError::OldVersion);
+    }
+
+    let mut texture: Self = serde_json::from_str(&asset.json)?;
+    texture.data = asset.blob;
+
+    Ok(texture)
+  }
+}
+
+/// How the RGBA8 pixels of a texture are interpreted.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum TextureFormat {
+  /// Colors, like base color or emissive textures.
+  #[default]
+  Srgb,
+  /// Data that isn't a color, like normal maps.
+  Unorm,
+}
+
+impl Asset for Texture {
+  fn convert_to_asset(self) -> Result<AssetFile> {
+    let json = serde_json::to_string(&self)?;
+    Ok(AssetFile {
+      asset_type: AssetType::Texture,
+      version: TEXTURE_VERSION,
+      json,
+      blob: self.data,
+    })
+  }
+}
@@ -0,0 +1,104 @@
+use asset_lib::{Asset, AssetError, AssetFile, Material, Model, Node, Scene, Vertex};
+
+use nalgebra_glm as glm;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+  Vertex {
+    position: glm::vec3(x, y, z),
+    normal: glm::vec3(0.0, 0.0, 1.0),
+    tangent: glm::vec4(1.0, 0.0, 0.0, 1.0),
+  }
+}
+
+/// Same id the converter gives its models, a hash of the model before the id is set.
+fn hash_model(model: &Model) -> u128 {
+  let mut hasher = DefaultHasher::new();
+  model.hash(&mut hasher);
+  hasher.finish() as u128
+}
+
+fn test_model() -> Model {
+  let mut model = Model::new("Triangles", 0);
+  let triangle = [vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0)];
+  model.add_mesh(&triangle, &[0, 1, 2]).unwrap();
+
+  let quad = [vertex(0.0, 0.0, 1.0), vertex(1.0, 0.0, 1.0), vertex(1.0, 1.0, 1.0), vertex(0.0, 1.0, 1.0)];
+  model.add_mesh(&quad, &[0, 1, 2, 2, 3, 0]).unwrap();
+
+  model.materials.push(Material {
+    name: "Rock".to_owned(),
+    normal_texture: Some("Rock_normal.tex".to_owned()),
+    roughness_factor: 0.8,
+    transmission_factor: Some(0.5),
+    ..Default::default()
+  });
+  model.meshes[1].material = Some(0);
+
+  model.id = hash_model(&model);
+  model
+}
+
+#[test]
+fn model_roundtrip() {
+  let model = test_model();
+  let (id, meshes, materials, blob) = (model.id, model.meshes.clone(), model.materials.clone(), model.blob.clone());
+
+  let mut loaded = Model::load_model(model.convert_to_asset().unwrap()).unwrap();
+
+  assert_eq!(loaded.name, "Triangles");
+  assert_eq!(loaded.meshes, meshes);
+  assert_eq!(loaded.materials, materials);
+  assert_eq!(loaded.blob, blob);
+  assert_eq!(loaded.id, id);
+
+  loaded.id = 0;
+  assert_eq!(hash_model(&loaded), id);
+}
+
+#[test]
+fn scene_roundtrip() {
+  let mut scene = Scene::default();
+  scene.name = "Stage".to_owned();
+  let model = scene.insert_model(42);
+
+  let leaf = scene.insert_node(Node {
+    name: "Leaf".to_owned(),
+    transform: glm::translation(&glm::vec3(0.0, 1.0, 0.0)),
+    model: Some(model),
+    visibility_mask: 0b10,
+    ..Default::default()
+  });
+  let branch = scene.insert_node(Node {
+    name: "Branch".to_owned(),
+    transform: glm::Mat4::identity(),
+    children: vec![leaf],
+    ..Default::default()
+  });
+  let root = scene.insert_node(Node {
+    name: "Root".to_owned(),
+    transform: glm::scaling(&glm::vec3(2.0, 2.0, 2.0)),
+    children: vec![branch],
+    model: Some(model),
+    ..Default::default()
+  });
+  scene.insert_parent_node(root);
+
+  let loaded = Scene::load_scene(scene.clone().convert_to_asset().unwrap()).unwrap();
+
+  assert_eq!(loaded.name, scene.name);
+  assert_eq!(loaded.nodes(), scene.nodes());
+  assert_eq!(loaded.models(), scene.models());
+  assert_eq!(loaded.parent_nodes(), scene.parent_nodes());
+}
+
+#[test]
+fn old_model_version_is_rejected() {
+  let mut asset = serde_json::to_value(test_model().convert_to_asset().unwrap()).unwrap();
+  asset["version"] = serde_json::Value::from(0);
+  let asset: AssetFile = serde_json::from_value(asset).unwrap();
+
+  assert!(matches!(Model::load_model(asset), Err(AssetError::OldVersion)));
+}
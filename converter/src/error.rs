@@ -12,4 +12,18 @@ pub(crate) enum ConverterError {
   MissingResource,
   #[error("couldn't parse resource: {0}")]
   ParsingError(&'static str),
+  #[error("file error: {0}")]
+  IoError(#[from] std::io::Error),
+  #[error("failed to import gltf file: {0}")]
+  GltfError(#[from] gltf::Error),
+  #[error("failed to deserialize manifest: {0}")]
+  ManifestError(#[from] serde_yaml::Error),
+  #[error("failed to compile shader: {0}")]
+  ShaderError(#[from] shaderc::Error),
+  #[error("converted assets failed validation with {0} issues")]
+  ValidationError(usize),
+  #[error("failed to parse project manifest: {0}")]
+  ProjectManifestError(#[from] toml::de::Error),
+  #[error("{0} assets of the project failed to convert")]
+  ProjectError(usize),
 }
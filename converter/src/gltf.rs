@@ -1,4 +1,6 @@
-use super::{Converter, ConverterError, Result};
+use super::normal_map::generate_normal_map;
+use super::validate::{print_reports, Validate};
+use super::{Converter, ConverterError, ConverterOptions, Result};
 
 use asset_lib as ast;
 use log::{error, info};
@@ -7,9 +9,17 @@ use num_traits::{AsPrimitive, FromPrimitive};
 
 use std::any::Any;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Suffix of gltf meshes holding a level of detail of another mesh, followed by the level, like `Rock_lod1`.
+const LOD_SUFFIX: &str = "_lod";
+/// Camera distance between two levels of detail, in multiples of the model's bounding radius.
+const LOD_DISTANCE_STEP: f32 = 10.0;
+/// Models left out with `--strip-unused`, `*` matches any number of characters.
+/// Covers the default objects of modelling tools, which are often exported by accident.
+const STRIPPED_MODEL_PATTERNS: &[&str] = &["Cube", "Cube.*", "Sphere", "Sphere.*", "Helper_*"];
 
 enum DataType {
   I8,
@@ -23,22 +33,36 @@ enum DataType {
 pub struct GLTFConverter {
   document: gltf::Document,
   buffers: Vec<gltf::buffer::Data>,
-  _images: Vec<gltf::image::Data>,
+  images: Vec<gltf::image::Data>,
   file_name: String,
   output_dir: String,
   models: Vec<ast::Model>,
+  /// Index into `models` for every gltf mesh that got converted, levels of detail all map to their combined model.
+  mesh_models: HashMap<usize, usize>,
+  /// Meshes of the lower levels of detail, nodes using them are left without a model since the first level draws them all.
+  secondary_lod_meshes: HashSet<usize>,
   scenes: Vec<ast::Scene>,
+  textures: Vec<ast::Texture>,
+  strip_unused: bool,
+  stripped: StrippedAssets,
+}
+
+/// Everything left out of the conversion with `--strip-unused`.
+#[derive(Default)]
+struct StrippedAssets {
+  /// Gltf meshes of stripped models, nodes using them are left out of the scenes.
+  gltf_meshes: HashSet<usize>,
+  /// Materials no kept mesh uses, no textures get generated for them.
+  materials: HashSet<usize>,
+  /// Names of the stripped models, updating an archive converted without stripping removes them from it.
+  model_names: Vec<String>,
+  mesh_count: usize,
+  bytes: usize,
 }
 
 impl Converter for GLTFConverter {
-  fn parse_file(src_file: &str, output_dir: &str) {
-    let (document, buffers, images) = match gltf::import(src_file) {
-      Ok(contents) => contents,
-      Err(e) => {
-        error!("Failed to open GLTF file: {}", e);
-        return;
-      }
-    };
+  fn parse_file(src_file: &str, output_dir: &str, options: &ConverterOptions) -> Result<()> {
+    let (document, buffers, images) = gltf::import(src_file)?;
 
     let mut file = PathBuf::new();
     file.push(src_file);
@@ -47,24 +71,52 @@ impl Converter for GLTFConverter {
     let mut converter = Self {
       document,
       buffers,
-      _images: images,
+      images,
       file_name,
       output_dir: output_dir.to_owned(),
       models: Vec::new(),
+      mesh_models: HashMap::new(),
+      secondary_lod_meshes: HashSet::new(),
       scenes: Vec::new(),
+      textures: Vec::new(),
+      strip_unused: options.strip_unused,
+      stripped: StrippedAssets::default(),
     };
 
     converter.parse_models();
+    if converter.strip_unused {
+      converter.find_unused_materials();
+    }
     converter.parse_scenes();
-    converter.write_files();
+    if let Some(suffix) = &options.height_map_suffix {
+      converter.generate_normal_maps(suffix);
+    }
+
+    if converter.strip_unused {
+      let stripped = &converter.stripped;
+      let saved_megabytes = stripped.bytes as f32 / 1_000_000.0;
+      info!("Stripped {} meshes, {} materials, saved {:.1} MB", stripped.mesh_count, stripped.materials.len(), saved_megabytes);
+    }
+
+    if options.validate {
+      return converter.validate_files(src_file);
+    }
+    converter.write_files(options)
   }
 }
 
 impl GLTFConverter {
   fn parse_models(&mut self) {
     let meshes = self.document.meshes();
+    // Sorted by base name, so the models of level of detail groups end up in the archive in the same order every time
+    let mut lod_groups: BTreeMap<String, Vec<(u32, gltf::Mesh)>> = BTreeMap::new();
 
     for mesh in meshes {
+      if let Some((base_name, level)) = mesh.name().and_then(split_lod_name) {
+        lod_groups.entry(base_name.to_owned()).or_default().push((level, mesh));
+        continue;
+      }
+
       let model = match self.parse_model(&mesh) {
         Ok(model) => model,
         Err(e) => {
@@ -73,10 +125,57 @@ impl GLTFConverter {
         }
       };
 
+      if self.strip_unused && self.stripped.strip_model(&model, &[mesh.index()]) {
+        continue;
+      }
+
+      self.mesh_models.insert(mesh.index(), self.models.len());
+      self.models.push(model);
+    }
+
+    for (base_name, mut levels) in lod_groups {
+      levels.sort_by_key(|(level, _)| *level);
+      let meshes = levels.into_iter().map(|(_, mesh)| mesh).collect::<Vec<gltf::Mesh>>();
+
+      let model = match self.parse_lod_model(&base_name, &meshes) {
+        Ok(model) => model,
+        Err(e) => {
+          error!("Failed to convert levels of detail of {}: {}", base_name, e);
+          continue;
+        }
+      };
+
+      if self.strip_unused && self.stripped.strip_model(&model, &meshes.iter().map(|mesh| mesh.index()).collect::<Vec<usize>>()) {
+        continue;
+      }
+
+      for mesh in &meshes {
+        self.mesh_models.insert(mesh.index(), self.models.len());
+      }
+      self.secondary_lod_meshes.extend(meshes.iter().skip(1).map(|mesh| mesh.index()));
       self.models.push(model);
     }
   }
 
+  /// Materials not referenced by a primitive of any kept mesh, so materials only stripped models used count as well.
+  fn find_unused_materials(&mut self) {
+    let used_materials = self
+      .document
+      .meshes()
+      .filter(|mesh| self.mesh_models.contains_key(&mesh.index()))
+      .flat_map(|mesh| mesh.primitives().filter_map(|primitive| primitive.material().index()).collect::<Vec<usize>>())
+      .collect::<HashSet<usize>>();
+
+    for material in self.document.materials() {
+      let Some(index) = material.index().filter(|index| !used_materials.contains(index)) else {
+        continue;
+      };
+
+      info!("Stripped material {}", material.name().unwrap_or("unnamed"));
+      self.stripped.materials.insert(index);
+    }
+  }
+
   fn parse_model(&self, mesh: &gltf::Mesh) -> Result<ast::Model> {
     let mut model = ast::Model::default();
 
@@ -86,8 +185,31 @@ impl GLTFConverter {
     for primitive in mesh.primitives() {
       let (vertices, indices) = self.parse_primitive(&primitive)?;
       model.add_mesh(&vertices, &indices)?;
+      set_mesh_material(&mut model, &primitive.material());
+    }
+
+    model.id = hash_model(&model);
+
+    Ok(model)
+  }
+
+  /// Combines the levels of detail into a single model with one mesh per level, ordered from the most detailed one.
+  fn parse_lod_model(&self, base_name: &str, meshes: &[gltf::Mesh]) -> Result<ast::Model> {
+    let mut model = ast::Model::default();
+    model.name = base_name.to_owned();
+
+    for mesh in meshes {
+      let mut primitives = mesh.primitives();
+      let (Some(primitive), None) = (primitives.next(), primitives.next()) else {
+        return Err(ConverterError::ParsingError("level of detail meshes need exactly one primitive!"));
+      };
+
+      let (vertices, indices) = self.parse_primitive(&primitive)?;
+      model.add_mesh(&vertices, &indices)?;
+      set_mesh_material(&mut model, &primitive.material());
     }
 
+    model.lod_distances = (0..meshes.len()).map(|level| level as f32 * LOD_DISTANCE_STEP).collect();
     model.id = hash_model(&model);
 
     Ok(model)
@@ -243,50 +365,114 @@ impl GLTFConverter {
 
     let nodes = scene.nodes();
     for node in nodes {
-      let node = self.parse_node(&mut parsed_scene, &node)?;
-      let index = parsed_scene.insert_node(node);
-      parsed_scene.insert_parent_node(index);
+      for index in self.parse_node(&mut parsed_scene, &node)? {
+        parsed_scene.insert_parent_node(index);
+      }
     }
 
     Ok(parsed_scene)
   }
 
-  fn parse_node(&self, scene: &mut ast::Scene, node: &gltf::Node) -> Result<ast::Node> {
+  /// Inserts the node and everything below it into the scene, returning the indices its parent should hold as children.
+  /// Nodes of stripped models aren't inserted, their children take their place with the node's transform applied.
+  fn parse_node(&self, scene: &mut ast::Scene, node: &gltf::Node) -> Result<Vec<usize>> {
     let children = node.children();
-    let mut parsed_node = ast::Node::default();
+    let transform = glm::Mat4::from(node.transform().matrix());
+
+    if node.mesh().is_some_and(|mesh| self.stripped.gltf_meshes.contains(&mesh.index())) {
+      info!("Stripped scene node {}", node.name().unwrap_or("Node"));
+      let mut indices = Vec::new();
+      for child in children {
+        for index in self.parse_node(scene, &child)? {
+          let child_node = &mut scene.nodes_mut()[index];
+          child_node.transform = transform * child_node.transform;
+          indices.push(index);
+        }
+      }
+      return Ok(indices);
+    }
 
-    parsed_node.transform = glm::Mat4::from(node.transform().matrix());
+    let mut parsed_node = ast::Node::default();
+    parsed_node.transform = transform;
     parsed_node.name = "Node".to_owned();
 
-    if let Some(mesh) = node.mesh() {
-      let model_name = self.models.get(mesh.index()).ok_or(ConverterError::MissingResource)?.name.clone();
-      let model_id = self.models.get(mesh.index()).ok_or(ConverterError::MissingResource)?.id.clone();
-      let index = scene.insert_model(model_id);
+    if let Some(mesh) = node.mesh().filter(|mesh| !self.secondary_lod_meshes.contains(&mesh.index())) {
+      let model = self.mesh_models.get(&mesh.index()).and_then(|index| self.models.get(*index)).ok_or(ConverterError::MissingResource)?;
+      let index = scene.insert_model(model.id);
       parsed_node.model = Some(index);
-      parsed_node.name = model_name;
+      parsed_node.name = model.name.clone();
     };
 
     for node in children {
-      let node = self.parse_node(scene, &node)?;
-      let index = scene.insert_node(node);
-      parsed_node.children.push(index);
+      let indices = self.parse_node(scene, &node)?;
+      parsed_node.children.extend(indices);
     }
 
-    Ok(parsed_node)
+    Ok(vec![scene.insert_node(parsed_node)])
   }
 
-  fn write_files(mut self) {
-    let output_dir = self.output_dir;
-    let file_name = self.file_name;
-    let archive_name = format!("{output_dir}/{file_name}.ast");
-    let mut archive = match ast::AssetArchive::new(&archive_name) {
-      Ok(archive) => {
-        info!("Created asset archive: {}", archive_name);
-        archive
+  fn generate_normal_maps(&mut self, height_map_suffix: &str) {
+    for material in self.document.materials() {
+      if material.normal_texture().is_some() || material.index().is_some_and(|index| self.stripped.materials.contains(&index)) {
+        continue;
       }
-      Err(e) => {
-        error!("Failed to create asset archive for gltf file: {}", e);
-        return;
+
+      let Some(material_name) = material.name() else {
+        continue;
+      };
+
+      // Height maps are named after their material, like `Rock_height` or `Rock_albedo_height`, the closest name wins
+      let height_map = self
+        .document
+        .textures()
+        .filter_map(|texture| Some((texture.name().or(texture.source().name())?.to_owned(), texture)))
+        .filter(|(name, _)| name.starts_with(material_name) && name.ends_with(height_map_suffix))
+        .min_by_key(|(name, _)| name.len());
+
+      let Some((height_map_name, height_map)) = height_map else {
+        continue;
+      };
+
+      let Some(image) = self.images.get(height_map.source().index()) else {
+        error!("Height map {} refers to a missing image, skipping.", height_map_name);
+        continue;
+      };
+
+      let data = match generate_normal_map(image) {
+        Ok(data) => data,
+        Err(e) => {
+          error!("Failed to generate normal map from {}: {}", height_map_name, e);
+          continue;
+        }
+      };
+
+      info!("Generated normal map for material {} from {}", material_name, height_map_name);
+      let texture_name = format!("{material_name}_normal");
+      link_normal_texture(&mut self.models, material_name, &format!("{texture_name}.tex"));
+      self.textures.push(ast::Texture {
+        name: texture_name,
+        width: image.width,
+        height: image.height,
+        format: ast::TextureFormat::Unorm,
+        data,
+      });
+    }
+  }
+
+  fn write_files(mut self, options: &ConverterOptions) -> Result<()> {
+    let output_dir = &self.output_dir;
+    let file_name = &self.file_name;
+    let archive_name = options.output_archive.clone().unwrap_or(format!("{output_dir}/{file_name}.ast"));
+    if options.update && Path::new(&archive_name).is_file() {
+      return self.update_files(&archive_name);
+    }
+
+    // A shared archive already holds the assets converted before this file
+    let mut archive = match options.output_archive {
+      Some(_) => ast::AssetArchive::open(&archive_name)?,
+      None => {
+        info!("Created asset archive: {}", archive_name);
+        ast::AssetArchive::new(&archive_name)?
       }
     };
 
@@ -304,12 +490,169 @@ impl GLTFConverter {
       save_asset(scene, &scene_name, &mut archive);
     }
 
-    archive.finish().unwrap();
+    for texture in self.textures.drain(..) {
+      let texture_name = texture.name.to_owned();
+      let texture_name = format!("{texture_name}.tex");
+      info!("Adding generated texture to archive: {}", texture_name);
+      save_asset(texture, &texture_name, &mut archive);
+    }
+
+    archive.finish()?;
+    Ok(())
+  }
+
+  fn update_files(mut self, archive_name: &str) -> Result<()> {
+    info!("Updating asset archive: {}", archive_name);
+    let mut changed_assets = Vec::new();
+
+    for model in self.models.drain(..) {
+      let model_name = format!("{}.mesh", model.name);
+      update_asset(model, model_name, archive_name, &mut changed_assets)?;
+    }
+
+    for scene in self.scenes.drain(..) {
+      let scene_name = format!("{}.scn", scene.name);
+      update_asset(scene, scene_name, archive_name, &mut changed_assets)?;
+    }
+
+    for texture in self.textures.drain(..) {
+      let texture_name = format!("{}.tex", texture.name);
+      update_asset(texture, texture_name, archive_name, &mut changed_assets)?;
+    }
+
+    // The archive might hold stripped models from an earlier conversion, nothing references them anymore
+    let removed_assets = self
+      .stripped
+      .model_names
+      .iter()
+      .map(|model_name| format!("{model_name}.mesh"))
+      .filter(|asset_name| ast::AssetArchive::asset_exists(archive_name, asset_name))
+      .collect::<Vec<String>>();
+    removed_assets.iter().for_each(|asset_name| info!("Removing stripped asset from archive: {}", asset_name));
+
+    if changed_assets.is_empty() && removed_assets.is_empty() {
+      info!("No assets changed, archive left as is");
+      return Ok(());
+    }
+
+    // Rewriting the archive copies every entry, so it only happens once for all changed assets
+    info!("Rewriting archive with {} changed and {} removed assets", changed_assets.len(), removed_assets.len());
+    ast::AssetArchive::update_assets_in_place(archive_name, changed_assets, &removed_assets)?;
+    Ok(())
+  }
+
+  fn validate_files(self, src_file: &str) -> Result<()> {
+    let mut reports = Vec::new();
+    reports.extend(self.models.into_iter().map(|model| {
+      let model_name = format!("{}.mesh", model.name);
+      model.validate_output(&model_name)
+    }));
+    reports.extend(self.scenes.into_iter().map(|scene| {
+      let scene_name = format!("{}.scn", scene.name);
+      scene.validate_output(&scene_name)
+    }));
+    reports.extend(self.textures.into_iter().map(|texture| {
+      let texture_name = format!("{}.tex", texture.name);
+      texture.validate_output(&texture_name)
+    }));
+
+    print_reports(src_file, &reports)
+  }
+}
+
+impl StrippedAssets {
+  /// Records the model as stripped if it matches `STRIPPED_MODEL_PATTERNS`. `meshes` are the gltf meshes it was made from.
+  fn strip_model(&mut self, model: &ast::Model, meshes: &[usize]) -> bool {
+    if !STRIPPED_MODEL_PATTERNS.iter().any(|pattern| matches_pattern(&model.name, pattern)) {
+      return false;
+    }
+
+    info!("Stripped model {}", model.name);
+    self.model_names.push(model.name.clone());
+    self.gltf_meshes.extend(meshes);
+    self.mesh_count += model.meshes.len();
+    self.bytes += model.blob.len();
+    true
   }
 }
 
 //----------------------------Helpers--------------------------------------
 
+/// Points the last added mesh at its material, adding the material to the model if no other mesh used it yet.
+/// Primitives without a material are left on the default one.
+fn set_mesh_material(model: &mut ast::Model, material: &gltf::Material) {
+  let Some(index) = material.index() else {
+    return;
+  };
+
+  let name = material.name().map(|name| name.to_owned()).unwrap_or(format!("Material_{index}"));
+  let material_index = match model.materials.iter().position(|material| material.name == name) {
+    Some(material_index) => material_index,
+    None => {
+      model.materials.push(parse_material(material, name));
+      model.materials.len() - 1
+    }
+  };
+
+  if let Some(mesh) = model.meshes.last_mut() {
+    mesh.material = Some(material_index as u32);
+  }
+}
+
+/// Reads the factors of a gltf material, textures only get linked once they're part of the archive.
+/// Transmission only makes the surface see-through, the volume extension adds the medium behind it and is optional.
+fn parse_material(material: &gltf::Material, name: String) -> ast::Material {
+  let pbr = material.pbr_metallic_roughness();
+
+  let alpha_mode = match material.alpha_mode() {
+    gltf::material::AlphaMode::Opaque => ast::AlphaMode::Opaque,
+    gltf::material::AlphaMode::Mask => ast::AlphaMode::Mask,
+    gltf::material::AlphaMode::Blend => ast::AlphaMode::Blend,
+  };
+
+  let mut parsed_material = ast::Material {
+    name,
+    base_color_factor: glm::Vec4::from(pbr.base_color_factor()),
+    emissive_factor: glm::Vec3::from(material.emissive_factor()),
+    metallic_factor: pbr.metallic_factor(),
+    roughness_factor: pbr.roughness_factor(),
+    alpha_mode,
+    alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+    double_sided: material.double_sided(),
+    transmission_factor: material.transmission().map(|transmission| transmission.transmission_factor()),
+    ..Default::default()
+  };
+
+  if let Some(normal_texture) = material.normal_texture() {
+    parsed_material.normal_scale = normal_texture.scale();
+  }
+  if let Some(occlusion_texture) = material.occlusion_texture() {
+    parsed_material.occlusion_strength = occlusion_texture.strength();
+  }
+  if let Some(volume) = material.volume() {
+    parsed_material.thickness_factor = volume.thickness_factor();
+    parsed_material.attenuation_color = glm::Vec3::from(volume.attenuation_color());
+  }
+
+  parsed_material
+}
+
+/// Sets the normal texture of every model material with the given name, the ids of changed models get recomputed.
+fn link_normal_texture(models: &mut [ast::Model], material_name: &str, texture_asset_name: &str) {
+  for model in models {
+    let mut linked = false;
+    for material in model.materials.iter_mut().filter(|material| material.name == material_name) {
+      material.normal_texture = Some(texture_asset_name.to_owned());
+      linked = true;
+    }
+
+    if linked {
+      model.id = 0;
+      model.id = hash_model(model);
+    }
+  }
+}
+
 fn save_asset(asset: impl ast::Asset, asset_name: &str, archive: &mut ast::AssetArchive) {
   let asset = match asset.convert_to_asset() {
     Ok(asset) => asset,
@@ -325,6 +668,20 @@ fn save_asset(asset: impl ast::Asset, asset_name: &str, archive: &mut ast::Asset
   }
 }
 
+/// Queues the asset for replacing in the archive, unless the archive already holds the exact same asset.
+fn update_asset(asset: impl ast::Asset, asset_name: String, archive_name: &str, changed_assets: &mut Vec<(String, ast::AssetFile)>) -> Result<()> {
+  let asset = asset.convert_to_asset()?;
+
+  if ast::AssetArchive::get_asset_by_name(archive_name, &asset_name)?.is_some_and(|existing_asset| existing_asset == asset) {
+    info!("Asset {} is unchanged, skipping", asset_name);
+    return Ok(());
+  }
+
+  info!("Updating asset in archive: {}", asset_name);
+  changed_assets.push((asset_name, asset));
+  Ok(())
+}
+
 #[derive(Default)]
 struct Attributes {
   position: Vec<glm::Vec3>,
@@ -458,22 +815,25 @@ fn convert_indices_from_strip(indices: Vec<u32>) -> Vec<u32> {
   }
 
   let mut new_indices = Vec::with_capacity(indices.len() * 3);
-  let mut indices = indices.iter().enumerate();
+  let mut indices = indices.iter();
 
-  let (_, mut first_index) = indices.next().unwrap();
-  let (_, mut second_index) = indices.next().unwrap();
+  let mut first_index = indices.next().unwrap();
+  let mut second_index = indices.next().unwrap();
 
-  for (even, index) in indices {
-    if even % 2 == 0 {
-      new_indices.push(*first_index);
+  // Every other triangle in a strip has its winding reversed, so swap the first two indices to keep them all facing the same way
+  let mut flip = false;
+  for index in indices {
+    if flip {
       new_indices.push(*second_index);
+      new_indices.push(*first_index);
       new_indices.push(*index);
     } else {
-      new_indices.push(*second_index);
       new_indices.push(*first_index);
+      new_indices.push(*second_index);
       new_indices.push(*index);
     }
 
+    flip = !flip;
     first_index = second_index;
     second_index = index;
   }
@@ -519,8 +879,87 @@ fn convert_indices_from_fan(indices: Vec<u32>) -> Vec<u32> {
 //   }
 // }
 
+/// Splits a name like `Rock_lod1` into the base name and the level of detail.
+fn split_lod_name(name: &str) -> Option<(&str, u32)> {
+  let (base_name, level) = name.rsplit_once(LOD_SUFFIX)?;
+  Some((base_name, level.parse().ok()?))
+}
+
+/// Glob style match of the whole name, `*` in the pattern matches any number of characters.
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+  let mut parts = pattern.split('*');
+  // Split always yields at least one part
+  let first = parts.next().unwrap();
+  let Some(mut rest) = name.strip_prefix(first) else {
+    return false;
+  };
+
+  let mut parts = parts.peekable();
+  while let Some(part) = parts.next() {
+    // The last part has to end the name, the ones before it can be found anywhere
+    if parts.peek().is_none() {
+      return rest.ends_with(part);
+    }
+
+    match rest.find(part) {
+      Some(position) => rest = &rest[position + part.len()..],
+      None => return false,
+    }
+  }
+
+  // No `*` in the pattern, so the name has to match it exactly
+  rest.is_empty()
+}
+
 fn hash_model(model: &ast::Model) -> u128 {
   let mut hasher = DefaultHasher::new();
   model.hash(&mut hasher);
   hasher.finish() as u128
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn strip_with_two_triangles_keeps_winding() {
+    assert_eq!(convert_indices_from_strip(vec![0, 1, 2, 3]), vec![0, 1, 2, 2, 1, 3]);
+  }
+
+  #[test]
+  fn strip_with_odd_triangle_count_keeps_winding() {
+    assert_eq!(convert_indices_from_strip(vec![0, 1, 2, 3, 4]), vec![0, 1, 2, 2, 1, 3, 2, 3, 4]);
+  }
+
+  #[test]
+  fn strip_without_a_full_triangle_is_left_as_is() {
+    assert_eq!(convert_indices_from_strip(vec![0, 1]), vec![0, 1]);
+  }
+
+  #[test]
+  fn pattern_without_wildcard_matches_the_whole_name() {
+    assert!(matches_pattern("Cube", "Cube"));
+    assert!(!matches_pattern("Cube.001", "Cube"));
+    assert!(!matches_pattern("Cubes", "Cube"));
+  }
+
+  #[test]
+  fn pattern_with_trailing_wildcard_matches_the_prefix() {
+    assert!(matches_pattern("Cube.001", "Cube.*"));
+    assert!(!matches_pattern("Cubes", "Cube.*"));
+    assert!(matches_pattern("Helper_Light", "Helper_*"));
+    assert!(matches_pattern("Helper_", "Helper_*"));
+    assert!(!matches_pattern("Helper", "Helper_*"));
+    assert!(!matches_pattern("MyHelper_Light", "Helper_*"));
+  }
+
+  #[test]
+  fn pattern_with_wildcard_in_the_middle_matches_both_ends() {
+    assert!(matches_pattern("Rock_lod_high", "Rock*high"));
+    assert!(matches_pattern("Rockhigh", "Rock*high"));
+    assert!(!matches_pattern("Rock_high_lod", "Rock*high"));
+    // The end can't reuse characters already matched by the start
+    assert!(!matches_pattern("Rock", "Roc*ck"));
+    assert!(matches_pattern("Helper_Light_01", "Helper_*_*"));
+  }
+}
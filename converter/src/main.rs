@@ -1,6 +1,9 @@
 mod error;
 mod gltf;
+mod manifest;
+mod normal_map;
 mod pipeline;
+mod validate;
 
 pub(crate) use error::{ConverterError, Result};
 
@@ -9,28 +12,70 @@ use log::{error, info};
 use log4rs::append::console::ConsoleAppender;
 use log4rs::config::{Appender, Config, Root};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
 pub(crate) trait Converter {
-  fn parse_file(src_file: &str, output_dir: &str);
+  fn parse_file(src_file: &str, output_dir: &str, options: &ConverterOptions) -> Result<()>;
+}
+
+#[derive(Default)]
+pub(crate) struct ConverterOptions {
+  /// Suffix of height map textures to generate missing normal maps from
+  pub(crate) height_map_suffix: Option<String>,
+  /// Load the converted assets back and report problems instead of writing them out
+  pub(crate) validate: bool,
+  /// Only replace the assets that changed in an already converted output
+  pub(crate) update: bool,
+  /// Archive to add the converted assets to, instead of an output named after the source file
+  pub(crate) output_archive: Option<String>,
+  /// Directories searched for shader includes after the directory of the pipeline manifest
+  pub(crate) include_dirs: Vec<PathBuf>,
+  /// Leave out placeholder models, the scene nodes using them and materials no mesh uses
+  pub(crate) strip_unused: bool,
 }
 
 #[derive(Parser)]
 #[command(version, about)]
 struct Args {
   /// assey file to convert
-  #[arg(id = "FILE")]
-  src_path: String,
+  #[arg(id = "FILE", required_unless_present = "project")]
+  src_path: Option<String>,
+  /// convert every asset listed in a project.toml manifest into the archive named in its [output] section
+  #[arg(long, value_name = "MANIFEST", conflicts_with_all = ["FILE", "output_path", "generate_normals_from_height", "strip_unused"])]
+  project: Option<String>,
   /// output file to produce
   #[arg(short, long)]
   output_path: Option<String>,
+  /// generate missing normal maps from height map textures named <material name><TEXTURE_SUFFIX>
+  #[arg(long, value_name = "TEXTURE_SUFFIX")]
+  generate_normals_from_height: Option<String>,
+  /// convert the file and check that the produced assets load back correctly, without writing any output
+  #[arg(long)]
+  validate: bool,
+  /// only rewrite the assets of an existing output that changed since the last conversion
+  #[arg(long, conflicts_with = "validate")]
+  update: bool,
+  /// leave out default cubes, spheres and Helper_* models, the scene nodes using them and materials no mesh uses
+  #[arg(long)]
+  strip_unused: bool,
 }
 
 fn main() -> ExitCode {
   initialize_logging();
 
-  let (src_file, output_dir) = match parse_args() {
+  let args = Args::parse();
+  if let Some(manifest_file) = &args.project {
+    return match manifest::convert_project(Path::new(manifest_file), args.validate, args.update) {
+      Ok(_) => ExitCode::SUCCESS,
+      Err(e) => {
+        error!("Failed to convert project {}: {}", manifest_file, e);
+        ExitCode::FAILURE
+      }
+    };
+  }
+
+  let (src_file, output_dir, options) = match parse_args(args) {
     Ok(files) => files,
     Err(e) => {
       error!("Failed to parse application arguments: {}", e);
@@ -38,9 +83,7 @@ fn main() -> ExitCode {
     }
   };
 
-  convert_file(&src_file, &output_dir);
-
-  ExitCode::SUCCESS
+  convert_file(&src_file, &output_dir, &options)
 }
 
 fn initialize_logging() {
@@ -67,10 +110,9 @@ fn initialize_default_logger() {
   log4rs::init_config(config).unwrap();
 }
 
-fn parse_args() -> Result<(PathBuf, PathBuf)> {
-  let args = Args::parse();
+fn parse_args(args: Args) -> Result<(PathBuf, PathBuf, ConverterOptions)> {
   let mut src_file = PathBuf::new();
-  src_file.push(args.src_path);
+  src_file.push(args.src_path.ok_or(ConverterError::ArgsError("No source file provided!"))?);
 
   if !src_file.is_file() {
     return Err(ConverterError::ArgsError("Provided source path is not a file!"));
@@ -88,24 +130,46 @@ fn parse_args() -> Result<(PathBuf, PathBuf)> {
     }
   }
 
-  Ok((src_file, output_dir))
+  let options = ConverterOptions {
+    height_map_suffix: args.generate_normals_from_height,
+    validate: args.validate,
+    update: args.update,
+    strip_unused: args.strip_unused,
+    ..Default::default()
+  };
+
+  Ok((src_file, output_dir, options))
 }
 
-fn convert_file(src_file: &PathBuf, output_dir: &PathBuf) {
-  let extension = src_file.extension().unwrap().to_str().unwrap();
+fn convert_file(src_file: &PathBuf, output_dir: &PathBuf, options: &ConverterOptions) -> ExitCode {
+  match run_converter(src_file, output_dir, options) {
+    Ok(_) => ExitCode::SUCCESS,
+    Err(e) => {
+      error!("Failed to convert file {}: {}", src_file.display(), e);
+      ExitCode::FAILURE
+    }
+  }
+}
+
+/// Picks the converter backend from the extension of the source file.
+pub(crate) fn run_converter(src_file: &Path, output_dir: &Path, options: &ConverterOptions) -> Result<()> {
+  let extension = src_file.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
 
-  let src_file = src_file.to_str().unwrap();
-  let output_dir = output_dir.to_str().unwrap();
+  let src_file = src_file.to_str().ok_or(ConverterError::ArgsError("Source path is not valid unicode!"))?;
+  let output_dir = output_dir.to_str().ok_or(ConverterError::ArgsError("Output path is not valid unicode!"))?;
 
   match extension {
     "gltf" | "glb" | "vrm" => {
       info!("Parsing gltf file {}", src_file);
-      gltf::GLTFConverter::parse_file(src_file, output_dir);
+      gltf::GLTFConverter::parse_file(src_file, output_dir, options)
     }
     "pipmf" => {
       info!("Parsing pipline manifest {}", src_file);
-      pipeline::PipelineConverter::parse_file(src_file, output_dir);
+      pipeline::PipelineConverter::parse_file(src_file, output_dir, options)
+    }
+    _ => {
+      error!("file {} has an unknown format, skipping...", src_file);
+      Ok(())
     }
-    _ => error!("file {} has an unknown format, skipping...", src_file),
   }
 }
@@ -0,0 +1,134 @@
+use super::{run_converter, ConverterError, ConverterOptions, Result};
+
+use asset_lib as ast;
+use log::{error, info};
+use serde::Deserialize;
+
+use std::path::Path;
+
+/// Contents of a `project.toml`, listing every asset of a project together with its conversion settings.
+/// All paths are relative to the directory of the manifest, and options the converter doesn't know are rejected instead of ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectManifest {
+  output: OutputSettings,
+  #[serde(default, rename = "model")]
+  models: Vec<ModelEntry>,
+  #[serde(default, rename = "pipeline")]
+  pipelines: Vec<PipelineEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutputSettings {
+  /// Single archive all assets of the project are written to.
+  archive: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelEntry {
+  src: String,
+  /// Same as the `--generate-normals-from-height` argument.
+  generate_normals_from_height: Option<String>,
+  /// Same as the `--strip-unused` argument.
+  #[serde(default)]
+  strip_unused: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PipelineEntry {
+  src: String,
+}
+
+/// Converts every asset of the manifest, a failing asset doesn't stop the others from being converted.
+pub(crate) fn convert_project(manifest_file: &Path, validate: bool, update: bool) -> Result<()> {
+  let manifest: ProjectManifest = toml::from_str(&std::fs::read_to_string(manifest_file)?)?;
+  let project_dir = manifest_file.parent().map(Path::to_path_buf).unwrap_or_default();
+
+  let archive_path = project_dir.join(&manifest.output.archive);
+  let archive_name = archive_path.to_str().ok_or(ConverterError::ArgsError("Output archive path is not valid unicode!"))?.to_owned();
+
+  // Assets get added to the archive one source file at a time, so it has to exist before the first one
+  if !validate && !(update && archive_path.is_file()) {
+    ast::AssetArchive::new(&archive_name)?.finish()?;
+    info!("Created asset archive: {}", archive_name);
+  }
+
+  let project_options = || ConverterOptions {
+    validate,
+    update,
+    output_archive: Some(archive_name.clone()),
+    include_dirs: vec![project_dir.clone()],
+    ..Default::default()
+  };
+
+  let mut failures = 0;
+  for model in &manifest.models {
+    let options = ConverterOptions {
+      height_map_suffix: model.generate_normals_from_height.clone(),
+      strip_unused: model.strip_unused,
+      ..project_options()
+    };
+
+    if !convert_entry(&project_dir.join(&model.src), &project_dir, &options) {
+      failures += 1;
+    }
+  }
+
+  for pipeline in &manifest.pipelines {
+    if !convert_entry(&project_dir.join(&pipeline.src), &project_dir, &project_options()) {
+      failures += 1;
+    }
+  }
+
+  match failures {
+    0 => Ok(()),
+    failures => Err(ConverterError::ProjectError(failures)),
+  }
+}
+
+fn convert_entry(src_file: &Path, project_dir: &Path, options: &ConverterOptions) -> bool {
+  info!("Converting project asset {}", src_file.display());
+
+  match run_converter(src_file, project_dir, options) {
+    Ok(_) => true,
+    Err(e) => {
+      error!("Failed to convert file {}: {}", src_file.display(), e);
+      false
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MANIFEST: &str = r#"
+[output]
+archive = "assets.ast"
+
+[[model]]
+src = "models/circus.glb"
+strip_unused = true
+
+[[pipeline]]
+src = "pipelines/default.pipmf"
+"#;
+
+  #[test]
+  fn manifest_lists_models_and_pipelines() {
+    let manifest: ProjectManifest = toml::from_str(MANIFEST).unwrap();
+    assert_eq!(manifest.output.archive, "assets.ast");
+    assert_eq!(manifest.models.len(), 1);
+    assert!(manifest.models[0].strip_unused);
+    assert_eq!(manifest.pipelines[0].src, "pipelines/default.pipmf");
+  }
+
+  #[test]
+  fn unknown_model_options_are_rejected() {
+    let manifest = MANIFEST.replace("strip_unused = true", "optimize = true");
+    assert!(toml::from_str::<ProjectManifest>(&manifest).is_err());
+  }
+}
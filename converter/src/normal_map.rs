@@ -0,0 +1,80 @@
+use super::{ConverterError, Result};
+
+use gltf::image::Format;
+use nalgebra_glm as glm;
+
+/// Generates an RGBA8 tangent space normal map out of the first channel of a height map using a Sobel filter.
+pub(crate) fn generate_normal_map(height_map: &gltf::image::Data) -> Result<Vec<u8>> {
+  let width = height_map.width as usize;
+  let height = height_map.height as usize;
+  let heights = read_heights(height_map)?;
+
+  let sample = |x: isize, y: isize| -> f32 {
+    let x = x.clamp(0, width as isize - 1) as usize;
+    let y = y.clamp(0, height as isize - 1) as usize;
+    heights[y * width + x]
+  };
+
+  let mut normal_map = Vec::with_capacity(width * height * 4);
+  for y in 0..height as isize {
+    for x in 0..width as isize {
+      let top_left = sample(x - 1, y - 1);
+      let top = sample(x, y - 1);
+      let top_right = sample(x + 1, y - 1);
+      let left = sample(x - 1, y);
+      let right = sample(x + 1, y);
+      let bottom_left = sample(x - 1, y + 1);
+      let bottom = sample(x, y + 1);
+      let bottom_right = sample(x + 1, y + 1);
+
+      let dx = (top_right + 2.0 * right + bottom_right) - (top_left + 2.0 * left + bottom_left);
+      let dy = (top_left + 2.0 * top + top_right) - (bottom_left + 2.0 * bottom + bottom_right);
+
+      let normal = glm::normalize(&glm::Vec3::new(-dx, -dy, 1.0));
+      normal_map.push(to_unorm(normal.x));
+      normal_map.push(to_unorm(normal.y));
+      normal_map.push(to_unorm(normal.z));
+      normal_map.push(u8::MAX);
+    }
+  }
+
+  Ok(normal_map)
+}
+
+//----------------------------Helpers--------------------------------------
+
+fn read_heights(image: &gltf::image::Data) -> Result<Vec<f32>> {
+  let (channels, channel_size) = match image.format {
+    Format::R8 => (1, 1),
+    Format::R8G8 => (2, 1),
+    Format::R8G8B8 => (3, 1),
+    Format::R8G8B8A8 => (4, 1),
+    Format::R16 => (1, 2),
+    Format::R16G16 => (2, 2),
+    Format::R16G16B16 => (3, 2),
+    Format::R16G16B16A16 => (4, 2),
+    Format::R32G32B32FLOAT => (3, 4),
+    Format::R32G32B32A32FLOAT => (4, 4),
+  };
+
+  let pixel_size = channels * channel_size;
+  if image.pixels.len() != image.width as usize * image.height as usize * pixel_size {
+    return Err(ConverterError::ParsingError("height map pixel data doesn't match its dimensions!"));
+  }
+
+  let heights = image
+    .pixels
+    .chunks_exact(pixel_size)
+    .map(|pixel| match channel_size {
+      1 => pixel[0] as f32 / u8::MAX as f32,
+      2 => u16::from_le_bytes([pixel[0], pixel[1]]) as f32 / u16::MAX as f32,
+      _ => f32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]),
+    })
+    .collect();
+
+  Ok(heights)
+}
+
+fn to_unorm(value: f32) -> u8 {
+  ((value * 0.5 + 0.5) * u8::MAX as f32).round() as u8
+}
@@ -1,77 +1,140 @@
-use super::Converter;
+use super::validate::{print_reports, Validate};
+use super::{Converter, ConverterError, ConverterOptions, Result};
 
 use asset_lib as ast;
 use ast::Asset;
-use log::error;
+use log::info;
 use serde_yaml as yml;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 pub(crate) struct PipelineConverter {}
 
 impl Converter for PipelineConverter {
-  fn parse_file(src_file: &str, output_dir: &str) {
+  fn parse_file(src_file: &str, output_dir: &str, options: &ConverterOptions) -> Result<()> {
     let mut path = PathBuf::new();
     path.push(src_file);
     let mut vertex_shader_path = path.clone();
     let mut fragment_shader_path = path.clone();
 
-    let file = match std::fs::File::open(path) {
-      Ok(file) => file,
-      Err(e) => {
-        error!("Failed to open pipeline file: {}", e);
-        return;
-      }
-    };
-
-    let document: ast::PipelineManifest = match yml::from_reader(file) {
-      Ok(document) => document,
-      Err(e) => {
-        error!("Failed to deserialize file: {}", e);
-        return;
-      }
-    };
+    let file = std::fs::File::open(path)?;
+    let document: ast::PipelineManifest = yml::from_reader(file)?;
 
     vertex_shader_path.pop();
     vertex_shader_path.push(document.vertex_shader);
     fragment_shader_path.pop();
     fragment_shader_path.push(document.fragment_shader);
 
-    let vertex_file = match std::fs::read_to_string(vertex_shader_path) {
-      Ok(file) => file,
-      Err(e) => {
-        error!("Failed to open vertex shader file: {}", e);
-        return;
-      }
-    };
+    let vertex_language = get_source_language(&vertex_shader_path);
+    let fragment_language = get_source_language(&fragment_shader_path);
+    let vertex_entry_point = document.entry_point_vs.unwrap_or(get_default_entry_point(vertex_language, shaderc::ShaderKind::Vertex).to_owned());
+    let fragment_entry_point = document.entry_point_fs.unwrap_or(get_default_entry_point(fragment_language, shaderc::ShaderKind::Fragment).to_owned());
 
-    let fragmet_file = match std::fs::read_to_string(fragment_shader_path) {
-      Ok(file) => file,
-      Err(e) => {
-        error!("Failed to open fragment shader file: {}", e);
-        return;
-      }
-    };
+    let vertex_file = std::fs::read_to_string(vertex_shader_path)?;
+    let fragmet_file = std::fs::read_to_string(fragment_shader_path)?;
+
+    // Includes are looked up next to the manifest first
+    let mut manifest_dir = PathBuf::from(src_file);
+    manifest_dir.pop();
+    let include_dirs = [vec![manifest_dir], options.include_dirs.clone()].concat();
 
-    let vertex_shader = compile_shader(&vertex_file, shaderc::ShaderKind::Vertex, &document.name);
-    let fragment_shader = compile_shader(&fragmet_file, shaderc::ShaderKind::Fragment, &document.name);
+    let vertex_shader = compile_shader(&vertex_file, shaderc::ShaderKind::Vertex, &document.name, &vertex_entry_point, vertex_language, &include_dirs)?;
+    let fragment_shader = compile_shader(&fragmet_file, shaderc::ShaderKind::Fragment, &document.name, &fragment_entry_point, fragment_language, &include_dirs)?;
 
     let pipeline = ast::Pipeline {
       name: document.name.clone(),
       blending: document.blending,
+      depth_test: document.depth_test,
+      depth_write: document.depth_write,
+      cull_mode: document.cull_mode,
+      polygon_mode: document.polygon_mode,
       vertex_shader: vertex_shader.as_binary_u8().to_owned(),
       fragment_shader: fragment_shader.as_binary_u8().to_owned(),
     };
 
     let name = document.name;
+    if options.validate {
+      let report = pipeline.validate_output(&format!("{name}.pipl"));
+      return print_reports(src_file, &[report]);
+    }
+
+    let asset = pipeline.convert_to_asset()?;
+    if let Some(archive_name) = &options.output_archive {
+      return add_to_archive(asset, &format!("{name}.pipl"), archive_name, options.update);
+    }
+
     let path = format!("{output_dir}/{name}.pipl");
-    pipeline.convert_to_asset().unwrap().save_to_file(&path);
+
+    if options.update && Path::new(&path).is_file() {
+      if ast::AssetFile::load_from_file(&path).is_ok_and(|existing_asset| existing_asset == asset) {
+        info!("Pipeline {} is unchanged, skipping", path);
+        return Ok(());
+      }
+    }
+
+    asset.save_to_file(&path)?;
+    Ok(())
   }
 }
 
-fn compile_shader(code: &str, shader_type: shaderc::ShaderKind, filename: &str) -> shaderc::CompilationArtifact {
-  let compiler = shaderc::Compiler::new().unwrap();
-  let mut options = shaderc::CompileOptions::new().unwrap();
+/// Writes the pipeline into a shared archive, in update mode it only gets replaced if it changed.
+fn add_to_archive(asset: ast::AssetFile, asset_name: &str, archive_name: &str, update: bool) -> Result<()> {
+  if update {
+    if ast::AssetArchive::get_asset_by_name(archive_name, asset_name)?.is_some_and(|existing_asset| existing_asset == asset) {
+      info!("Pipeline {} is unchanged, skipping", asset_name);
+      return Ok(());
+    }
+
+    return Ok(ast::AssetArchive::update_asset_in_place(archive_name, asset_name, asset)?);
+  }
+
+  let mut archive = ast::AssetArchive::open(archive_name)?;
+  archive.add_asset_file(asset, asset_name)?;
+  archive.finish()?;
+  Ok(())
+}
+
+fn compile_shader(
+  code: &str,
+  shader_type: shaderc::ShaderKind,
+  filename: &str,
+  entry_point: &str,
+  language: shaderc::SourceLanguage,
+  include_dirs: &[PathBuf],
+) -> Result<shaderc::CompilationArtifact> {
+  let compiler = shaderc::Compiler::new().ok_or(ConverterError::ParsingError("failed to initialize the shader compiler"))?;
+  let mut options = shaderc::CompileOptions::new().ok_or(ConverterError::ParsingError("failed to initialize the shader compiler options"))?;
   options.set_target_env(shaderc::TargetEnv::Vulkan, shaderc::EnvVersion::Vulkan1_2 as u32);
-  options.set_source_language(shaderc::SourceLanguage::GLSL);
-  compiler.compile_into_spirv(code, shader_type, filename, "main", None).unwrap()
+  options.set_source_language(language);
+  options.set_include_callback(|requested, _, _, _| resolve_include(requested, include_dirs));
+  Ok(compiler.compile_into_spirv(code, shader_type, filename, entry_point, Some(&options))?)
+}
+
+/// Looks for an included file in each of the directories in order.
+fn resolve_include(requested: &str, include_dirs: &[PathBuf]) -> std::result::Result<shaderc::ResolvedInclude, String> {
+  for include_dir in include_dirs {
+    let path = include_dir.join(requested);
+    if let Ok(content) = std::fs::read_to_string(&path) {
+      return Ok(shaderc::ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+      });
+    }
+  }
+
+  Err(format!("couldn't find included file {}", requested))
+}
+
+fn get_source_language(shader_path: &Path) -> shaderc::SourceLanguage {
+  match shader_path.extension().and_then(|extension| extension.to_str()) {
+    Some("hlsl") => shaderc::SourceLanguage::HLSL,
+    _ => shaderc::SourceLanguage::GLSL,
+  }
+}
+
+fn get_default_entry_point(language: shaderc::SourceLanguage, shader_type: shaderc::ShaderKind) -> &'static str {
+  match (language, shader_type) {
+    (shaderc::SourceLanguage::HLSL, shaderc::ShaderKind::Vertex) => "VSMain",
+    (shaderc::SourceLanguage::HLSL, shaderc::ShaderKind::Fragment) => "PSMain",
+    _ => "main",
+  }
 }
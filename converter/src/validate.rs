@@ -0,0 +1,121 @@
+use super::{ConverterError, Result};
+
+use asset_lib as ast;
+use ast::Asset;
+use log::{error, info, warn};
+
+/// Problems found in a single converted asset.
+pub(crate) struct ValidationReport {
+  asset_name: String,
+  issues: Vec<String>,
+  warnings: Vec<ast::AssetWarning>,
+}
+
+impl ValidationReport {
+  fn new(asset_name: &str) -> Self {
+    Self {
+      asset_name: asset_name.to_owned(),
+      issues: Vec::new(),
+      warnings: Vec::new(),
+    }
+  }
+
+  fn add_issue(&mut self, issue: String) {
+    self.issues.push(issue);
+  }
+
+  fn add_warnings(&mut self, warnings: Vec<ast::AssetWarning>) {
+    self.warnings.extend(warnings);
+  }
+}
+
+/// Loads a converted asset back the same way the engine would, instead of writing it to the output.
+pub(crate) trait Validate: ast::Asset + Sized {
+  fn load_and_check(asset_file: ast::AssetFile, report: &mut ValidationReport) -> std::result::Result<(), ast::AssetError>;
+
+  fn validate_output(self, asset_name: &str) -> ValidationReport {
+    let mut report = ValidationReport::new(asset_name);
+    let result = self.convert_to_asset().and_then(|asset_file| Self::load_and_check(asset_file, &mut report));
+
+    if let Err(e) = result {
+      report.add_issue(format!("converted asset is invalid: {}", e));
+    }
+
+    report
+  }
+}
+
+impl Validate for ast::Model {
+  fn load_and_check(asset_file: ast::AssetFile, report: &mut ValidationReport) -> std::result::Result<(), ast::AssetError> {
+    let model = ast::Model::load_model(asset_file)?;
+
+    if model.meshes.is_empty() {
+      report.add_issue("model has no meshes".to_owned());
+    }
+
+    report.add_warnings(model.validate()?);
+    Ok(())
+  }
+}
+
+impl Validate for ast::Scene {
+  fn load_and_check(asset_file: ast::AssetFile, report: &mut ValidationReport) -> std::result::Result<(), ast::AssetError> {
+    let scene = ast::Scene::load_scene(asset_file)?;
+    report.add_warnings(scene.validate()?);
+    Ok(())
+  }
+}
+
+impl Validate for ast::Texture {
+  fn load_and_check(asset_file: ast::AssetFile, report: &mut ValidationReport) -> std::result::Result<(), ast::AssetError> {
+    let texture = ast::Texture::load_texture(asset_file)?;
+    let expected_size = texture.width as usize * texture.height as usize * 4;
+
+    if texture.data.len() != expected_size {
+      report.add_issue(format!("texture data is {} bytes, expected {} for {}x{} rgba", texture.data.len(), expected_size, texture.width, texture.height));
+    }
+
+    Ok(())
+  }
+}
+
+impl Validate for ast::Pipeline {
+  fn load_and_check(asset_file: ast::AssetFile, report: &mut ValidationReport) -> std::result::Result<(), ast::AssetError> {
+    let pipeline = ast::Pipeline::load_pipeline(asset_file)?;
+
+    if pipeline.vertex_shader.is_empty() {
+      report.add_issue("vertex shader is empty".to_owned());
+    }
+
+    if pipeline.fragment_shader.is_empty() {
+      report.add_issue("fragment shader is empty".to_owned());
+    }
+
+    Ok(())
+  }
+}
+
+/// Logs the reports of all assets converted from `src_file`, fails if any of them had issues.
+pub(crate) fn print_reports(src_file: &str, reports: &[ValidationReport]) -> Result<()> {
+  for report in reports {
+    for warning in &report.warnings {
+      warn!("{}: {}", report.asset_name, warning);
+    }
+  }
+
+  let issue_count = reports.iter().map(|report| report.issues.len()).sum::<usize>();
+  if issue_count == 0 {
+    info!("OK {}", src_file);
+    return Ok(());
+  }
+
+  error!("Validation of {} failed:", src_file);
+  for report in reports.iter().filter(|report| !report.issues.is_empty()) {
+    error!("  {}:", report.asset_name);
+    for issue in &report.issues {
+      error!("    - {}", issue);
+    }
+  }
+
+  Err(ConverterError::ValidationError(issue_count))
+}
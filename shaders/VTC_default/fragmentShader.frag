@@ -4,6 +4,22 @@
 layout(location = 0) in float light_intensity;
 layout(location = 1) in vec4 frag_color;
 // layout(location = 2) in vec2 frag_texcoord;
+layout(location = 3) in vec3 world_position;
+layout(location = 4) in vec3 world_normal;
+
+layout(set = 0, binding = 0) uniform UniformBufferObject 
+{
+    mat4 view;
+    mat4 proj;
+    float environment_intensity;
+    uint has_environment_map;
+} ubo;
+
+// The material info is tightly packed on the CPU side, only the leading base color factor lines up with std140 so far
+layout(set = 1, binding = 0) uniform MaterialData 
+{
+    vec4 base_color_factor;             // 0 - 15
+} material;
 
 // layout(set = 1, binding = 0) uniform MaterialData 
 // {
@@ -14,6 +30,9 @@ layout(location = 1) in vec4 frag_color;
 //     float occlusion_strength_factor;    // 44 - 47
 //     float alpha_cutoff;                 // 48 - 51
 //     uint flags;                         // 52 - 55
+//     float transmission_factor;          // 56 - 59
+//     float thickness_factor;             // 60 - 63
+//     vec3 attenuation_color;             // 64 - 79
 // } material;
 
 layout(set = 1, binding = 1) uniform sampler2D tex_sampler;
@@ -22,13 +41,45 @@ layout(set = 1, binding = 3) uniform sampler2D normals_sampler;
 layout(set = 1, binding = 4) uniform sampler2D occlusion_sampler;
 layout(set = 1, binding = 5) uniform sampler2D emissive_sampler;
 
+layout(set = 2, binding = 0) uniform sampler2DShadow shadow_sampler;
+layout(set = 2, binding = 1) uniform LightData
+{
+    mat4 view_projection;
+} light;
+
+// Only written while ubo.has_environment_map is set
+layout(set = 4, binding = 0) uniform samplerCube environment_map;
+
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    vec4 tex_color = frag_color;
+    vec4 tex_color = frag_color * material.base_color_factor;
     // vec4 tex_color = texture(tex_sampler, frag_texcoord);
     // debugPrintfEXT("alpha_cutoff: %f, tex_alpha: %f \n", material.alpha_cutoff, tex_color.w);
     if(tex_color.w < 0.5) discard;
+    // Biased towards the light, surfaces would shadow themselves otherwise
+    vec4 shadow_coord = light.view_projection * vec4(world_position, 1.0);
+    vec3 shadow_ndc = shadow_coord.xyz / shadow_coord.w;
+    float shadow = texture(shadow_sampler, vec3(shadow_ndc.xy * 0.5 + 0.5, min(shadow_ndc.z, 1.0) - 0.002));
     // outColor = frag_color * material.base_color_factor * tex_color * light_intensity;
-    outColor = frag_color * light_intensity;
+    vec3 normal = normalize(world_normal);
+
+    // Flat ambient term for scenes without an environment map
+    vec4 irradiance = vec4(0.1, 0.1, 0.1, 0.0);
+    if(ubo.has_environment_map != 0) {
+        // The map has no prefiltered mip chain yet, the light arriving along the normal stands in for the diffuse irradiance
+        irradiance = vec4(texture(environment_map, normal).rgb, 0.0);
+    }
+    outColor = tex_color * light_intensity * shadow + tex_color * irradiance * ubo.environment_intensity;
+
+    if(ubo.has_environment_map != 0) {
+        // The camera sits at the origin of view space
+        vec3 camera_position = vec3(inverse(ubo.view)[3]);
+        vec3 view_direction = normalize(world_position - camera_position);
+
+        // Schlick's approximation with the reflectance of common dielectrics, surfaces mostly reflect at grazing angles
+        float fresnel = 0.04 + 0.96 * pow(1.0 - clamp(dot(-view_direction, normal), 0.0, 1.0), 5.0);
+        vec4 reflection = texture(environment_map, reflect(view_direction, normal));
+        outColor = mix(outColor, vec4(reflection.rgb, outColor.a), fresnel);
+    }
 }
\ No newline at end of file
@@ -13,20 +13,28 @@ layout(location = 2) in vec4 tangent;
 
 layout(set = 0, binding = 0) uniform UniformBufferObject 
 {
-    mat4 model;
     mat4 view;
     mat4 proj;
+    float environment_intensity;
+    uint has_environment_map;
 } ubo;
 
+layout(std430, set = 3, binding = 0) readonly buffer NodeTransforms
+{
+    mat4 transforms[];
+} node_transforms;
+
 layout( push_constant ) uniform constants
 {
+    uint node_index;
 	float time;
-    mat4 model_matrix;
 } push_constants;
 
 layout(location = 0) out float light_intensity;
 layout(location = 1) out vec4 frag_color;
 // layout(location = 2) out vec2 frag_texcoord;
+layout(location = 3) out vec3 world_position;
+layout(location = 4) out vec3 world_normal;
 
 vec4 quaternionFromEuler(vec3 euler)
 {
@@ -82,7 +90,9 @@ void main() {
     vec3 euler = vec3(1.570796, 0.0, push_constants.time / 1000);
     vec4 quaternion = quaternionFromEuler(euler);
     mat4 rotation = matrixFromQuaternion(quaternion);
-    mat4 model_location = ubo.view * ubo.model * rotation;
+    mat4 model_matrix = node_transforms.transforms[push_constants.node_index];
+    mat4 world_location = model_matrix * rotation;
+    mat4 model_location = ubo.view * world_location;
 
     vec3 calcNormal = mat3(model_location) * normal;
 	vec3 lightDirection = normalize(mat3(ubo.view) * vec3(1.0));
@@ -90,6 +100,8 @@ void main() {
 
     gl_Position = ubo.proj * model_location  * vec4(pos, 1.0);
     light_intensity = lightIntensity;
+    world_position = vec3(world_location * vec4(pos, 1.0));
+    world_normal = mat3(world_location) * normal;
     frag_color = vec4(1.0, 1.0, 1.0, 1.0);
     // frag_color = color;
     // frag_texcoord = texcoord;
@@ -0,0 +1,11 @@
+#version 460
+
+layout(location = 0) in vec2 quad_coord;
+layout(location = 1) in vec4 frag_color;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    float falloff = 1.0 - clamp(length(quad_coord), 0.0, 1.0);
+    out_color = vec4(frag_color.rgb, frag_color.a * falloff);
+}
@@ -0,0 +1,55 @@
+#version 460
+#extension GL_EXT_buffer_reference : require
+
+struct Particle
+{
+    float position[3];
+    float velocity[3];
+    float age;
+    float color[4];
+};
+
+layout(std430, buffer_reference, buffer_reference_align = 4) buffer ParticleBuffer
+{
+    Particle particles[];
+};
+
+layout( push_constant ) uniform constants
+{
+    mat4 view_projection;
+    ParticleBuffer particle_buffer;
+    float delta_time;
+    float lifetime;
+    vec3 emitter_position;
+    uint max_particles;
+} push_constants;
+
+layout(location = 0) out vec2 quad_coord;
+layout(location = 1) out vec4 frag_color;
+
+const float PARTICLE_SIZE = 0.02;
+const vec2 QUAD_CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+void main() {
+    Particle particle = push_constants.particle_buffer.particles[gl_InstanceIndex];
+    vec2 corner = QUAD_CORNERS[gl_VertexIndex];
+
+    quad_coord = corner;
+    frag_color = vec4(particle.color[0], particle.color[1], particle.color[2], particle.color[3]);
+
+    // Particles that haven't been emitted yet get a position outside of the clip volume
+    if (particle.age < 0.0) {
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        return;
+    }
+
+    vec3 position = vec3(particle.position[0], particle.position[1], particle.position[2]);
+    vec4 clip_position = push_constants.view_projection * vec4(position, 1.0);
+
+    // Offsetting in clip space keeps the quad facing the camera
+    clip_position.xy += corner * PARTICLE_SIZE;
+    gl_Position = clip_position;
+}
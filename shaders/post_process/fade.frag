@@ -0,0 +1,16 @@
+#version 460
+
+layout(set = 0, binding = 0) uniform sampler2D captured_frame;
+
+layout( push_constant ) uniform constants
+{
+    float alpha;
+} push_constants;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    // The captured frame has the same size as the swapchain, so pixels map one to one
+    vec3 color = texelFetch(captured_frame, ivec2(gl_FragCoord.xy), 0).rgb;
+    out_color = vec4(color, push_constants.alpha);
+}
@@ -0,0 +1,47 @@
+#version 460
+
+// One row for the VRAM summary and one per memory heap, has to match MEMORY_HUD_MAX_ROWS
+layout( push_constant ) uniform constants
+{
+    float usages[17];
+    uint row_count;
+    uint warning_rows;
+} push_constants;
+
+layout(location = 0) out vec4 out_color;
+
+const vec2 PANEL_OFFSET = vec2(16.0, 16.0);
+const float PANEL_PADDING = 6.0;
+const vec2 BAR_SIZE = vec2(240.0, 10.0);
+const float ROW_HEIGHT = 16.0;
+
+const vec4 PANEL_COLOR = vec4(0.0, 0.0, 0.0, 0.6);
+const vec4 EMPTY_COLOR = vec4(0.3, 0.3, 0.3, 0.8);
+const vec4 USED_COLOR = vec4(0.2, 0.8, 0.3, 0.9);
+const vec4 WARNING_COLOR = vec4(0.9, 0.1, 0.1, 0.9);
+
+void main() {
+    // Relative to the top left corner of the first bar, Vulkan puts the origin of gl_FragCoord in the top left corner too
+    vec2 position = gl_FragCoord.xy - PANEL_OFFSET - vec2(PANEL_PADDING);
+    vec2 bars_size = vec2(BAR_SIZE.x, float(push_constants.row_count) * ROW_HEIGHT - (ROW_HEIGHT - BAR_SIZE.y));
+
+    if (any(lessThan(position, vec2(-PANEL_PADDING))) || any(greaterThan(position, bars_size + vec2(PANEL_PADDING)))) {
+        discard;
+    }
+
+    uint row = uint(max(position.y, 0.0) / ROW_HEIGHT);
+    float row_position = position.y - float(row) * ROW_HEIGHT;
+    if (position.x < 0.0 || position.x > BAR_SIZE.x || position.y < 0.0 || row >= push_constants.row_count || row_position > BAR_SIZE.y) {
+        out_color = PANEL_COLOR;
+        return;
+    }
+
+    float usage = clamp(push_constants.usages[row], 0.0, 1.0);
+    if (position.x > usage * BAR_SIZE.x) {
+        out_color = EMPTY_COLOR;
+        return;
+    }
+
+    bool over_threshold = (push_constants.warning_rows & (1u << row)) != 0u;
+    out_color = over_threshold ? WARNING_COLOR : USED_COLOR;
+}
@@ -0,0 +1,91 @@
+use asset_lib as ast;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SPIRV_MAGIC: u32 = 0x07230203;
+
+// Content problems are reported as warnings only, broken assets shouldn't stop the code from compiling
+// Shaders aren't compiled here, pipeline assets embed theirs and the remaining ones are compiled by hand, see the readme
+fn main() {
+  println!("cargo:rerun-if-changed=models/");
+  println!("cargo:rerun-if-changed=config/");
+  let project_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+
+  let mut issues = 0;
+  for directory in ["models", "config"] {
+    let mut content_dir = project_dir.clone();
+    content_dir.push(directory);
+
+    let mut files = Vec::new();
+    collect_files(&content_dir, &mut files);
+
+    for file in files {
+      match file.extension().and_then(|extension| extension.to_str()) {
+        Some("ast") => issues += validate_archive(&file),
+        Some("pipl") => issues += validate_pipeline(&file),
+        _ => (),
+      }
+    }
+  }
+
+  if issues > 0 {
+    println!("cargo:warning=asset validation found {} issue(s)", issues);
+  }
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) {
+  // Missing content directories are fine, there just isn't anything to validate
+  let Ok(entries) = fs::read_dir(directory) else {
+    return;
+  };
+
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      collect_files(&path, files);
+    } else {
+      files.push(path);
+    }
+  }
+}
+
+fn validate_archive(path: &Path) -> u32 {
+  match ast::AssetArchive::get_assets(path.to_str().unwrap()) {
+    Ok(_) => 0,
+    Err(e) => {
+      println!("cargo:warning=asset archive {} can't be read: {}", path.display(), e);
+      1
+    }
+  }
+}
+
+fn validate_pipeline(path: &Path) -> u32 {
+  let pipeline = match ast::AssetFile::load_from_file(path.to_str().unwrap()).and_then(ast::Pipeline::load_pipeline) {
+    Ok(pipeline) => pipeline,
+    Err(e) => {
+      println!("cargo:warning=pipeline {} can't be read: {}", path.display(), e);
+      return 1;
+    }
+  };
+
+  let mut issues = 0;
+  for (stage, shader) in [("vertex", &pipeline.vertex_shader), ("fragment", &pipeline.fragment_shader)] {
+    if !is_spirv(shader) {
+      println!("cargo:warning=pipeline {} is missing a valid {} shader", path.display(), stage);
+      issues += 1;
+    }
+  }
+
+  issues
+}
+
+fn is_spirv(code: &[u8]) -> bool {
+  if code.len() < 4 || code.len() % 4 != 0 {
+    return false;
+  }
+
+  let magic = u32::from_le_bytes([code[0], code[1], code[2], code[3]]);
+  magic == SPIRV_MAGIC
+}
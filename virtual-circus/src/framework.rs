@@ -1,3 +1,12 @@
+pub(crate) mod camera;
+pub(crate) mod debug;
 pub(crate) mod model;
+pub(crate) mod particles;
+pub(crate) mod picking;
+pub(crate) mod texture_streaming;
 
+pub(crate) use camera::Camera;
+pub(crate) use debug::DebugFlags;
 pub(crate) use model::Model;
+pub(crate) use particles::{ParticleEmitter, ParticleSystem};
+pub(crate) use picking::{Aabb, Ray};
@@ -0,0 +1,40 @@
+use nalgebra_glm as glm;
+
+/// Visibility layer for HUD and UI elements, which only make sense from the main point of view.
+pub(crate) const HUD_LAYER: u32 = 1 << 31;
+
+pub(crate) struct Camera {
+  /// Nodes are only drawn when their visibility mask shares a layer with this mask.
+  pub(crate) camera_mask: u32,
+}
+
+impl Camera {
+  pub(crate) fn main() -> Self {
+    Self { camera_mask: u32::MAX }
+  }
+
+  pub(crate) fn shadow() -> Self {
+    Self { camera_mask: !HUD_LAYER }
+  }
+
+  pub(crate) fn sees(&self, visibility_mask: u32) -> bool {
+    visibility_mask & self.camera_mask != 0
+  }
+}
+
+/// Camera transform at `time` along a path of `(camera transform, time in seconds)` keyframes sorted by time.
+/// Positions are interpolated linearly and rotations spherically, outside of the path the closest keyframe is held.
+pub(crate) fn camera_path_transform(camera_path: &[(glm::Mat4, f32)], time: f32) -> Option<glm::Mat4> {
+  let next = camera_path.iter().position(|(_, keyframe_time)| *keyframe_time > time);
+  let (from, to) = match next {
+    Some(0) => return camera_path.first().map(|(transform, _)| *transform),
+    Some(next) => (&camera_path[next - 1], &camera_path[next]),
+    None => return camera_path.last().map(|(transform, _)| *transform),
+  };
+
+  let factor = (time - from.1) / (to.1 - from.1);
+  let position = glm::lerp(&from.0.column(3).xyz(), &to.0.column(3).xyz(), factor);
+  let rotation = glm::quat_slerp(&glm::to_quat(&from.0), &glm::to_quat(&to.0), factor);
+
+  Some(glm::translation(&position) * glm::quat_to_mat4(&rotation))
+}
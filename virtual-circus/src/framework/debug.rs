@@ -0,0 +1,67 @@
+use crate::vulkan::allocator::MemoryStats;
+use crate::vulkan::rendering_context::{MemoryHudPushConstant, MEMORY_HUD_MAX_ROWS};
+
+use bitmask_enum::bitmask;
+
+/// Share of a heap's budget after which the memory HUD highlights it.
+const MEMORY_WARNING_THRESHOLD: f64 = 0.8;
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+#[bitmask(u32)]
+pub(crate) enum DebugFlags {
+  MemoryHud = 0b00000001,
+}
+
+/// Single line of the memory HUD.
+pub(crate) struct MemoryHudLine {
+  pub(crate) text: String,
+  /// Share of the budget that is used, drawn as the length of the line's bar.
+  pub(crate) usage: f32,
+  /// Usage is above `MEMORY_WARNING_THRESHOLD` of the budget.
+  pub(crate) over_threshold: bool,
+}
+
+/// Lines of the memory HUD, starting with a "VRAM: 1.2 GB / 8.0 GB" summary of the device local heaps followed by every heap on its own.
+pub(crate) fn memory_hud_lines(stats: &MemoryStats) -> Vec<MemoryHudLine> {
+  let device_local_heaps = stats.heaps.iter().filter(|heap| heap.device_local);
+  let (used, budget) = device_local_heaps.fold((0, 0), |(used, budget), heap| (used + heap.used, budget + heap.budget));
+
+  let mut lines = vec![memory_hud_line("VRAM", used, budget)];
+  for (index, heap) in stats.heaps.iter().enumerate() {
+    lines.push(memory_hud_line(&format!("Heap {}", index), heap.used, heap.budget));
+  }
+
+  lines
+}
+
+/// Bars for the memory HUD pass, one per line with the lines over the threshold drawn in red.
+pub(crate) fn memory_hud_bars(lines: &[MemoryHudLine]) -> MemoryHudPushConstant {
+  let mut bars = MemoryHudPushConstant {
+    usages: [0.0; MEMORY_HUD_MAX_ROWS],
+    row_count: 0,
+    warning_rows: 0,
+  };
+
+  for (row, line) in lines.iter().take(MEMORY_HUD_MAX_ROWS).enumerate() {
+    bars.usages[row] = line.usage;
+    bars.row_count += 1;
+    if line.over_threshold {
+      bars.warning_rows |= 1 << row;
+    }
+  }
+
+  bars
+}
+
+fn memory_hud_line(label: &str, used: u64, budget: u64) -> MemoryHudLine {
+  let usage = match budget {
+    0 => 0.0,
+    budget => used as f64 / budget as f64,
+  };
+
+  MemoryHudLine {
+    text: format!("{}: {:.1} GB / {:.1} GB", label, used as f64 / BYTES_PER_GB, budget as f64 / BYTES_PER_GB),
+    usage: usage as f32,
+    over_threshold: usage > MEMORY_WARNING_THRESHOLD,
+  }
+}
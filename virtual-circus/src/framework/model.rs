@@ -1,31 +1,121 @@
-use crate::utils::tools::Result;
+use super::Aabb;
+use crate::utils::tools::{ModelError, Result};
 use crate::vulkan::allocator::{Buffer, BufferType};
+use crate::vulkan::descriptors::MaterialTextureSlot;
 use crate::vulkan::Allocator;
 
 use ash::vk;
 use asset_lib as ast;
+use nalgebra_glm as glm;
+
+const VERTEX_SIZE: usize = std::mem::size_of::<ast::Vertex>();
 
 pub(crate) struct Model {
   pub(crate) name: String,
   pub(crate) id: u128,
   pub(crate) meshes: Vec<ast::Mesh>,
   pub(crate) buffer: Buffer,
+  /// Index type to bind the index data of each mesh with, indexed the same way as the meshes.
+  pub(crate) index_types: Vec<vk::IndexType>,
+  /// Model space bounds of all meshes, `None` for models without vertices.
+  pub(crate) bounds: Option<Aabb>,
+  /// Camera distance from which each mesh is drawn, in multiples of the bounding radius. Empty when all meshes are drawn together.
+  pub(crate) lod_distances: Vec<f32>,
+  /// Materials referenced by the meshes, meshes without a material get drawn with the default one.
+  pub(crate) materials: Vec<ast::Material>,
+  /// Textures to stream in for the materials, as material index, texture slot and texture asset path.
+  pub(crate) textures: Vec<(usize, MaterialTextureSlot, String)>,
 }
 
 impl Model {
-  pub(crate) fn new(model: ast::Model, allocator: &mut Allocator) -> Result<Self> {
+  /// `texture_path` turns the texture names of the materials into paths the textures can be loaded from.
+  pub(crate) fn new(model: ast::Model, allocator: &mut Allocator, texture_path: impl Fn(&str) -> String) -> Result<Self> {
+    validate_model(&model)?;
+
     let usage_flags = vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER;
     let buffer = allocator.create_buffer_from_data(&model.blob, usage_flags, BufferType::GpuOnly)?;
 
+    let index_types = model
+      .meshes
+      .iter()
+      .map(|mesh| match mesh.index_type {
+        ast::IndexType::U16 => vk::IndexType::UINT16,
+        ast::IndexType::U32 => vk::IndexType::UINT32,
+      })
+      .collect();
+
+    let bounds = model.meshes.iter().filter_map(|mesh| mesh_bounds(&model.blob, mesh)).reduce(|a, b| a.merge(&b));
+
+    let textures = model
+      .materials
+      .iter()
+      .enumerate()
+      .filter_map(|(index, material)| Some((index, MaterialTextureSlot::Normal, texture_path(material.normal_texture.as_deref()?))))
+      .collect();
+
     Ok(Self {
       name: model.name,
       id: model.id,
       meshes: model.meshes,
       buffer,
+      index_types,
+      bounds,
+      lod_distances: model.lod_distances,
+      materials: model.materials,
+      textures,
     })
   }
 }
 
+//-----Helpers-----
+
+/// Positions are the first field of every vertex, validate_model already made sure the vertices are within the blob.
+fn mesh_bounds(blob: &[u8], mesh: &ast::Mesh) -> Option<Aabb> {
+  let positions = (0..mesh.vertex_count as usize).map(|vertex| {
+    let offset = mesh.vertex_offset as usize + vertex * mesh.vertex_stride as usize;
+    let component = |index: usize| {
+      let start = offset + index * std::mem::size_of::<f32>();
+      f32::from_le_bytes(blob[start..start + 4].try_into().unwrap())
+    };
+    glm::Vec3::new(component(0), component(1), component(2))
+  });
+
+  Aabb::from_points(positions)
+}
+
+fn validate_model(model: &ast::Model) -> std::result::Result<(), ModelError> {
+  if model.blob.is_empty() {
+    return Err(ModelError::NoResource("model blob is empty"));
+  }
+
+  if !model.lod_distances.is_empty() && model.lod_distances.len() != model.meshes.len() {
+    return Err(ModelError::InvalidField("model needs a level of detail distance for every mesh"));
+  }
+
+  for mesh in &model.meshes {
+    // The vertex input of the pipelines is built around the current vertex layout
+    if mesh.vertex_stride as usize != VERTEX_SIZE {
+      return Err(ModelError::InvalidField("mesh vertex stride doesn't match the supported vertex format"));
+    }
+
+    let vertex_end = mesh.vertex_offset as usize + mesh.vertex_count as usize * mesh.vertex_stride as usize;
+    if vertex_end > model.blob.len() {
+      return Err(ModelError::InvalidField("mesh vertices extend past the end of the model blob"));
+    }
+
+    let index_end = mesh.index_offset as usize + mesh.index_count as usize * mesh.index_type.size();
+    if index_end > model.blob.len() {
+      return Err(ModelError::InvalidField("mesh indices extend past the end of the model blob"));
+    }
+
+    if mesh.material.is_some_and(|material| material as usize >= model.materials.len()) {
+      return Err(ModelError::InvalidField("mesh references a material that doesn't exist"));
+    }
+  }
+
+  Ok(())
+}
+
 // pub(crate) trait ModelRequest {
 //   fn wait_finalize()
 // }
@@ -0,0 +1,52 @@
+use crate::utils::tools::Result;
+use crate::vulkan::allocator::{Buffer, BufferType};
+use crate::vulkan::Allocator;
+
+use ash::vk;
+use nalgebra_glm as glm;
+use serde::Serialize;
+
+/// Description of a particle emitter, the particles themselves only ever live on the GPU.
+#[derive(Clone, Debug)]
+pub(crate) struct ParticleSystem {
+  pub(crate) emitter_position: glm::Vec3,
+  pub(crate) max_particles: u32,
+  /// How long a single particle lives in seconds before it gets emitted again.
+  pub(crate) lifetime: f32,
+}
+
+// Matches the particle struct of the particle shaders, which uses float arrays to avoid the std430 padding of vec3
+#[derive(Serialize)]
+#[repr(C)]
+struct Particle {
+  position: glm::Vec3,
+  velocity: glm::Vec3,
+  age: f32,
+  color: glm::Vec4,
+}
+
+pub(crate) struct ParticleEmitter {
+  pub(crate) system: ParticleSystem,
+  pub(crate) buffer: Buffer,
+}
+
+impl ParticleEmitter {
+  pub(crate) fn new(system: ParticleSystem, allocator: &mut Allocator) -> Result<Self> {
+    // Particles start with staggered negative ages so they get emitted evenly over the first lifetime instead of all at once
+    let mut data = Vec::with_capacity(std::mem::size_of::<Particle>() * system.max_particles as usize);
+    for i in 0..system.max_particles {
+      let particle = Particle {
+        position: system.emitter_position,
+        velocity: glm::Vec3::zeros(),
+        age: -system.lifetime * i as f32 / system.max_particles as f32,
+        color: glm::Vec4::new(1.0, 1.0, 1.0, 1.0),
+      };
+      data.extend(bincode::serialize(&particle).unwrap());
+    }
+
+    let usage_flags = vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+    let buffer = allocator.create_buffer_from_data(&data, usage_flags, BufferType::GpuOnly)?;
+
+    Ok(Self { system, buffer })
+  }
+}
@@ -0,0 +1,88 @@
+use nalgebra_glm as glm;
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Aabb {
+  pub(crate) min: glm::Vec3,
+  pub(crate) max: glm::Vec3,
+}
+
+impl Aabb {
+  pub(crate) fn from_points(points: impl IntoIterator<Item = glm::Vec3>) -> Option<Self> {
+    let mut points = points.into_iter();
+    let first = points.next()?;
+
+    let bounds = points.fold(Self { min: first, max: first }, |bounds, point| Self {
+      min: glm::min2(&bounds.min, &point),
+      max: glm::max2(&bounds.max, &point),
+    });
+
+    Some(bounds)
+  }
+
+  pub(crate) fn merge(&self, other: &Self) -> Self {
+    Self {
+      min: glm::min2(&self.min, &other.min),
+      max: glm::max2(&self.max, &other.max),
+    }
+  }
+
+  /// Box enclosing this box after the transform, it can be larger than the transformed geometry itself.
+  pub(crate) fn transformed(&self, transform: &glm::Mat4) -> Self {
+    let corners = (0..8).map(|corner| {
+      let x = if corner & 1 == 0 { self.min.x } else { self.max.x };
+      let y = if corner & 2 == 0 { self.min.y } else { self.max.y };
+      let z = if corner & 4 == 0 { self.min.z } else { self.max.z };
+      (transform * glm::Vec4::new(x, y, z, 1.0)).xyz()
+    });
+
+    // There are always eight corners, so there are always bounds
+    Self::from_points(corners).unwrap()
+  }
+}
+
+pub(crate) struct Ray {
+  pub(crate) origin: glm::Vec3,
+  pub(crate) direction: glm::Vec3,
+}
+
+impl Ray {
+  /// World space ray going through the cursor position, given in pixels from the top left corner of the window.
+  /// Vulkan's clip space y already points down like window coordinates, so the cursor doesn't get flipped.
+  pub(crate) fn from_cursor(cursor: (f64, f64), window_size: (i32, i32), view: &glm::Mat4, projection: &glm::Mat4) -> Self {
+    let ndc_x = (2.0 * cursor.0 / window_size.0.max(1) as f64 - 1.0) as f32;
+    let ndc_y = (2.0 * cursor.1 / window_size.1.max(1) as f64 - 1.0) as f32;
+
+    let inverse_view_projection = glm::inverse(&(projection * view));
+    let unproject = |depth: f32| {
+      let point = inverse_view_projection * glm::Vec4::new(ndc_x, ndc_y, depth, 1.0);
+      point.xyz() / point.w
+    };
+
+    // The projection comes from glm::perspective, which maps depth to the -1 to 1 range of OpenGL
+    let near = unproject(-1.0);
+    let far = unproject(1.0);
+
+    Self {
+      origin: near,
+      direction: (far - near).normalize(),
+    }
+  }
+
+  /// Distance along the ray to the first intersection with the box, `None` if the ray misses it.
+  pub(crate) fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+    let mut t_min = 0.0_f32;
+    let mut t_max = f32::INFINITY;
+
+    for axis in 0..3 {
+      let inverse_direction = 1.0 / self.direction[axis];
+      let t_0 = (aabb.min[axis] - self.origin[axis]) * inverse_direction;
+      let t_1 = (aabb.max[axis] - self.origin[axis]) * inverse_direction;
+
+      t_min = t_min.max(t_0.min(t_1));
+      t_max = t_max.min(t_0.max(t_1));
+    }
+
+    (t_min <= t_max).then_some(t_min)
+  }
+}
@@ -0,0 +1,208 @@
+use crate::utils::tools::{EngineError, Result};
+use crate::vulkan::allocator::{Image, ImagePurpose};
+use crate::vulkan::descriptors::{MaterialDescriptorSets, MaterialTextureSlot, TextureInfo};
+use crate::vulkan::elements::{ImageView, Sampler};
+use crate::vulkan::{Allocator, Device};
+
+use ash::vk;
+use asset_lib as ast;
+use log::{error, trace};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+
+const PLACEHOLDER_SIZE: u32 = 4;
+/// Uploading full resolution textures stalls the allocator, so only a few get swapped in per tick.
+const MAX_UPLOADS_PER_TICK: usize = 2;
+
+/// A material of a loaded model, materials are indexed per model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct MaterialId {
+  pub(crate) model: u128,
+  pub(crate) material: usize,
+}
+
+struct StreamedTexture {
+  _image: Image,
+  image_view: ImageView,
+}
+
+struct CompletedLoad {
+  material: MaterialId,
+  slot: MaterialTextureSlot,
+  texture: ast::Texture,
+}
+
+/// Hands out small placeholder textures right away and swaps in the full resolution ones once they're loaded in the background.
+pub(crate) struct TextureStreamingManager {
+  sampler: Sampler,
+  color_placeholder: StreamedTexture,
+  normal_placeholder: StreamedTexture,
+  textures: HashMap<(MaterialId, MaterialTextureSlot), StreamedTexture>,
+  // Draws are recorded while only the renderer is borrowed, so the counts need interior mutability
+  draw_counts: RefCell<HashMap<MaterialId, u32>>,
+  pending_loads: Vec<CompletedLoad>,
+  load_sender: Sender<CompletedLoad>,
+  load_receiver: Receiver<CompletedLoad>,
+}
+
+impl TextureStreamingManager {
+  pub(crate) fn new(device: &Arc<Device>, allocator: &mut Allocator) -> Result<Self> {
+    let sampler = Sampler::new(
+      device,
+      vk::Filter::LINEAR,
+      vk::Filter::LINEAR,
+      vk::SamplerMipmapMode::LINEAR,
+      vk::SamplerAddressMode::REPEAT,
+      vk::SamplerAddressMode::REPEAT,
+    )?;
+
+    // White leaves colors untouched, the normal placeholder points straight out of the surface
+    let pixel_count = (PLACEHOLDER_SIZE * PLACEHOLDER_SIZE) as usize;
+    let color_placeholder = create_texture(allocator, &[255u8; 4].repeat(pixel_count), PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, ast::TextureFormat::Srgb)?;
+    let normal_placeholder = create_texture(allocator, &[128u8, 128, 255, 255].repeat(pixel_count), PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, ast::TextureFormat::Unorm)?;
+    let (load_sender, load_receiver) = std::sync::mpsc::channel();
+
+    Ok(Self {
+      sampler,
+      color_placeholder,
+      normal_placeholder,
+      textures: HashMap::new(),
+      draw_counts: RefCell::new(HashMap::new()),
+      pending_loads: Vec::new(),
+      load_sender,
+      load_receiver,
+    })
+  }
+
+  /// Queues loading the full texture asset at `path`, which uses the same `archive.ast::asset_name` form as asset requests.
+  /// The material keeps its placeholder until `tick` swaps the texture in.
+  pub(crate) fn request_texture(&mut self, material: MaterialId, slot: MaterialTextureSlot, path: String) {
+    let load_sender = self.load_sender.clone();
+    std::thread::spawn(move || {
+      let texture = match load_texture(&path) {
+        Ok(texture) => texture,
+        Err(e) => {
+          error!("Failed to stream texture {}: {}", path, e);
+          return;
+        }
+      };
+
+      // The manager might already be gone, in which case nobody is waiting for the texture anymore
+      let _ = load_sender.send(CompletedLoad { material, slot, texture });
+    });
+  }
+
+  /// Should be called whenever a material is drawn, materials drawn more often get their textures streamed in first.
+  pub(crate) fn record_draw(&self, material: MaterialId) {
+    *self.draw_counts.borrow_mut().entry(material).or_default() += 1;
+  }
+
+  /// Whether `tick` has textures to swap in.
+  pub(crate) fn has_completed_loads(&mut self) -> bool {
+    self.pending_loads.extend(self.load_receiver.try_iter());
+    !self.pending_loads.is_empty()
+  }
+
+  /// Uploads the most drawn finished loads and points their materials at the full resolution textures.
+  /// Returns the materials whose descriptors changed, the same restrictions as `MaterialDescriptorSets::update_texture` apply.
+  pub(crate) fn tick(&mut self, allocator: &mut Allocator, material_descriptor_sets: &mut HashMap<u128, MaterialDescriptorSets>) -> Vec<MaterialId> {
+    self.pending_loads.extend(self.load_receiver.try_iter());
+    // Models dropped since their textures were requested don't need them anymore
+    self.pending_loads.retain(|load| material_descriptor_sets.contains_key(&load.material.model));
+
+    let draw_counts = self.draw_counts.get_mut();
+    self.pending_loads.sort_by_key(|load| std::cmp::Reverse(draw_counts.get(&load.material).copied().unwrap_or(0)));
+
+    let upload_count = self.pending_loads.len().min(MAX_UPLOADS_PER_TICK);
+    let mut updated_materials = Vec::with_capacity(upload_count);
+
+    let loads = self.pending_loads.drain(..upload_count).collect::<Vec<CompletedLoad>>();
+    for load in loads {
+      let texture = &load.texture;
+      let streamed_texture = match create_texture(allocator, &texture.data, texture.width, texture.height, texture.format) {
+        Ok(streamed_texture) => streamed_texture,
+        Err(e) => {
+          error!("Failed to upload streamed texture {}: {}", texture.name, e);
+          continue;
+        }
+      };
+
+      let texture_info = TextureInfo {
+        image_view: &streamed_texture.image_view,
+        sampler: &self.sampler,
+      };
+      let descriptor_sets = material_descriptor_sets.get_mut(&load.material.model).unwrap();
+      if let Err(e) = descriptor_sets.update_texture(load.material.material, load.slot, &texture_info) {
+        error!("Failed to swap in streamed texture {}: {}", texture.name, e);
+        continue;
+      }
+
+      trace!("Streamed in texture {} for material {:?}", texture.name, load.material);
+      self.textures.insert((load.material, load.slot), streamed_texture);
+      updated_materials.push(load.material);
+    }
+
+    // Only recent draws should matter for the priority, so older counts fade out over time
+    draw_counts.values_mut().for_each(|count| *count /= 2);
+
+    updated_materials
+  }
+
+  /// Currently bound texture of a material, a placeholder until `tick` swaps in the full resolution texture.
+  pub(crate) fn texture_info(&self, material: MaterialId, slot: MaterialTextureSlot) -> TextureInfo {
+    let placeholder = match slot {
+      MaterialTextureSlot::Normal => &self.normal_placeholder,
+      _ => &self.color_placeholder,
+    };
+    let texture = self.textures.get(&(material, slot)).unwrap_or(placeholder);
+
+    TextureInfo {
+      image_view: &texture.image_view,
+      sampler: &self.sampler,
+    }
+  }
+
+  /// Forgets the textures of a dropped model, its descriptor sets have to be dropped along with them.
+  pub(crate) fn drop_model(&mut self, model: u128) {
+    self.textures.retain(|(material, _), _| material.model != model);
+    self.draw_counts.get_mut().retain(|material, _| material.model != model);
+    self.pending_loads.retain(|load| load.material.model != model);
+  }
+}
+
+fn load_texture(path: &str) -> Result<ast::Texture> {
+  let asset = match path.split_once("::") {
+    Some((archive_path, asset_name)) => ast::AssetArchive::get_asset_by_name(archive_path, asset_name)?.ok_or(EngineError::CreationError("streamed texture is not present in the archive"))?,
+    None => ast::AssetFile::load_from_file(path)?,
+  };
+
+  Ok(ast::Texture::load_texture(asset)?)
+}
+
+fn create_texture(allocator: &mut Allocator, data: &[u8], width: u32, height: u32, format: ast::TextureFormat) -> Result<StreamedTexture> {
+  let format = match format {
+    ast::TextureFormat::Srgb => vk::Format::R8G8B8A8_SRGB,
+    ast::TextureFormat::Unorm => vk::Format::R8G8B8A8_UNORM,
+  };
+
+  let image_create_info = vk::ImageCreateInfo {
+    format,
+    tiling: vk::ImageTiling::OPTIMAL,
+    usage: vk::ImageUsageFlags::SAMPLED,
+    image_type: vk::ImageType::TYPE_2D,
+    samples: vk::SampleCountFlags::TYPE_1,
+    mip_levels: 1,
+    array_layers: 1,
+    extent: vk::Extent3D { width, height, depth: 1 },
+    ..Default::default()
+  };
+
+  let image = allocator.create_image(data, image_create_info, ImagePurpose::Texture)?;
+  allocator.flush();
+  let image_view = image.make_image_view()?;
+
+  Ok(StreamedTexture { _image: image, image_view })
+}
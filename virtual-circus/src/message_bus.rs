@@ -1,15 +1,27 @@
 mod messages;
 
 use crate::utils::thread::Threaded;
-pub(crate) use messages::{Message, MessageData};
+pub(crate) use messages::{Message, MessageData, MessageFilter};
 
 use log::error;
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// What message boxes send to the bus, delayed messages wait in the bus until they are due.
+enum BusMessage {
+  Immediate(Message),
+  Delayed { message: Message, deliver_at: Instant, cancel_token: CancelToken },
+}
 
 //--------------------------------------Message Box-----------------------------------------------------
 pub(crate) struct MessageBox {
-  bus_sender: Sender<Message>,
+  bus_sender: Sender<BusMessage>,
   system_receiver: Receiver<Message>,
+  // Shared with the bus, which only forwards the messages this system subscribed to
+  filter: Arc<AtomicU64>,
   should_close: bool,
 }
 
@@ -34,22 +46,83 @@ impl MessageBox {
   }
 
   pub(crate) fn post_message(&self, message: Message) {
+    self.send_to_bus(BusMessage::Immediate(message));
+  }
+
+  /// Posts the message once `delay` has passed, unless it gets cancelled through the returned token before that.
+  #[allow(dead_code)]
+  pub(crate) fn post_message_delayed(&self, message: Message, delay: Duration) -> CancelToken {
+    let cancel_token = CancelToken::default();
+    self.send_to_bus(BusMessage::Delayed {
+      message,
+      deliver_at: Instant::now() + delay,
+      cancel_token: cancel_token.clone(),
+    });
+
+    cancel_token
+  }
+
+  fn send_to_bus(&self, message: BusMessage) {
     match self.bus_sender.send(message) {
       Ok(_) => (),
-      Err(e) => error!("Failed to send a message to the bus: {}", e.to_string()),
+      Err(_) => error!("Failed to send a message to the bus, channel already closed!"),
     }
   }
 
   pub(crate) fn should_close(&self) -> bool {
     self.should_close
   }
+
+  /// Restricts the messages the bus delivers to this box, the `Stop` message always gets through.
+  pub(crate) fn subscribe(self, filter: MessageFilter) -> FilteredMessageBox {
+    self.filter.store((filter | MessageFilter::STOP).bits(), Ordering::Relaxed);
+    FilteredMessageBox { message_box: self }
+  }
+}
+
+/// Message box which only receives the messages it subscribed to, otherwise used the same way as a `MessageBox`.
+pub(crate) struct FilteredMessageBox {
+  message_box: MessageBox,
+}
+
+impl std::ops::Deref for FilteredMessageBox {
+  type Target = MessageBox;
+
+  fn deref(&self) -> &Self::Target {
+    &self.message_box
+  }
+}
+
+impl std::ops::DerefMut for FilteredMessageBox {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.message_box
+  }
+}
+
+/// Handle to a delayed message, cancelling it keeps the bus from ever delivering the message.
+#[derive(Clone, Default)]
+pub(crate) struct CancelToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+  #[allow(dead_code)]
+  pub(crate) fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  pub(crate) fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
 }
 
 //---------------------------------------------------Message Bus System-------------------------------------------------
 pub(crate) struct MessageBus {
-  bus_sender: Sender<Message>,
-  bus_receiver: Receiver<Message>,
-  system_senders: Vec<Sender<Message>>,
+  bus_sender: Sender<BusMessage>,
+  bus_receiver: Receiver<BusMessage>,
+  system_senders: Vec<(Sender<Message>, Arc<AtomicU64>)>,
+  // Messages sharing a delivery time get delivered in the order they were posted
+  delayed_messages: BTreeMap<Instant, Vec<(Message, CancelToken)>>,
 }
 
 impl MessageBus {
@@ -60,25 +133,77 @@ impl MessageBus {
       bus_sender,
       bus_receiver,
       system_senders: Vec::new(),
+      delayed_messages: BTreeMap::new(),
     }
   }
 
   pub(crate) fn get_message_box(&mut self) -> MessageBox {
     let bus_sender = self.bus_sender.clone();
     let (system_sender, system_receiver) = std::sync::mpsc::channel();
-    self.system_senders.push(system_sender);
+    let filter = Arc::new(AtomicU64::new(MessageFilter::ALL.bits()));
+    self.system_senders.push((system_sender, filter.clone()));
     MessageBox {
       bus_sender,
       system_receiver,
+      filter,
       should_close: false,
     }
   }
+
+  /// Waits for the next message, but no longer than until the next delayed message is due.
+  fn receive(&self) -> Result<Option<BusMessage>, RecvTimeoutError> {
+    let Some(deliver_at) = self.delayed_messages.keys().next() else {
+      return self.bus_receiver.recv().map(Some).map_err(|_| RecvTimeoutError::Disconnected);
+    };
+
+    match self.bus_receiver.recv_timeout(deliver_at.saturating_duration_since(Instant::now())) {
+      Ok(message) => Ok(Some(message)),
+      Err(RecvTimeoutError::Timeout) => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Delivers every delayed message that is due, cancelled ones are dropped instead.
+  fn deliver_overdue_messages(&mut self) -> bool {
+    let now = Instant::now();
+    while let Some(entry) = self.delayed_messages.first_entry() {
+      if *entry.key() > now {
+        break;
+      }
+
+      for (message, cancel_token) in entry.remove() {
+        if !cancel_token.is_cancelled() && self.dispatch(message) {
+          return true;
+        }
+      }
+    }
+
+    false
+  }
+
+  /// Forwards the message to every system subscribed to it, `true` if it was the `Stop` message.
+  fn dispatch(&self, message: Message) -> bool {
+    message.log_message();
+    let message_filter = message.filter();
+    self.system_senders.iter().for_each(|(sender, filter)| {
+      if !MessageFilter::from_bits(filter.load(Ordering::Relaxed)).intersects(message_filter) {
+        return;
+      }
+
+      match sender.send(message.clone()) {
+        Ok(_) => (),
+        Err(_) => error!("Failed to send a message to a system, channel already closed!"),
+      };
+    });
+
+    matches!(message, Message::Stop)
+  }
 }
 
 impl Threaded for MessageBus {
   fn run(&mut self) {
     loop {
-      let message = match self.bus_receiver.recv() {
+      let message = match self.receive() {
         Ok(message) => message,
         Err(_) => {
           error! {"Message bus channel closed, cannot continue communication between systems!"};
@@ -86,15 +211,16 @@ impl Threaded for MessageBus {
         }
       };
 
-      message.log_message();
-      self.system_senders.iter().for_each(|sender| {
-        match sender.send(message.clone()) {
-          Ok(_) => (),
-          Err(_) => error!("Failed to send a message to a system, channel already closed!"),
-        };
-      });
+      let stopped = match message {
+        Some(BusMessage::Immediate(message)) => self.dispatch(message),
+        Some(BusMessage::Delayed { message, deliver_at, cancel_token }) => {
+          self.delayed_messages.entry(deliver_at).or_default().push((message, cancel_token));
+          false
+        }
+        None => false,
+      };
 
-      if let Message::Stop = message {
+      if stopped || self.deliver_overdue_messages() {
         break;
       };
     }
@@ -104,3 +230,54 @@ impl Threaded for MessageBus {
     "Message Bus".to_owned()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Runs the bus on the test thread until it delivers the `Stop` message, then collects everything the box received.
+  fn run_until_stopped(mut bus: MessageBus, message_box: MessageBox) -> Vec<Message> {
+    bus.run();
+    message_box.system_receiver.try_iter().collect()
+  }
+
+  #[test]
+  fn delayed_messages_are_delivered_in_order_of_their_delay() {
+    let mut bus = MessageBus::new();
+    let message_box = bus.get_message_box();
+
+    message_box.post_message_delayed(Message::SetTargetFps(2), Duration::from_millis(20));
+    message_box.post_message_delayed(Message::SetTargetFps(1), Duration::from_millis(10));
+    message_box.post_message_delayed(Message::Stop, Duration::from_millis(40));
+
+    let received = run_until_stopped(bus, message_box);
+    assert!(matches!(received[..], [Message::SetTargetFps(1), Message::SetTargetFps(2), Message::Stop]));
+  }
+
+  #[test]
+  fn cancelled_messages_are_never_delivered() {
+    let mut bus = MessageBus::new();
+    let message_box = bus.get_message_box();
+
+    let cancel_token = message_box.post_message_delayed(Message::SetTargetFps(1), Duration::from_millis(10));
+    message_box.post_message_delayed(Message::SetTargetFps(2), Duration::from_millis(10));
+    message_box.post_message_delayed(Message::Stop, Duration::from_millis(20));
+    cancel_token.cancel();
+
+    let received = run_until_stopped(bus, message_box);
+    assert!(matches!(received[..], [Message::SetTargetFps(2), Message::Stop]));
+  }
+
+  #[test]
+  fn overdue_messages_posted_after_stop_are_not_delivered() {
+    let mut bus = MessageBus::new();
+    let message_box = bus.get_message_box();
+
+    message_box.post_message_delayed(Message::SetTargetFps(1), Duration::ZERO);
+    message_box.post_message(Message::Stop);
+    message_box.post_message_delayed(Message::SetTargetFps(2), Duration::ZERO);
+
+    let received = run_until_stopped(bus, message_box);
+    assert!(matches!(received[..], [Message::SetTargetFps(1), Message::Stop]));
+  }
+}
@@ -1,8 +1,11 @@
-use crate::framework::Model;
+use crate::framework::{DebugFlags, Model, ParticleSystem};
+use crate::systems::NodeQueryResult;
 use crate::vulkan::WindowResources;
 
-use log::debug;
+use log::{debug, trace};
+use nalgebra_glm as glm;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub(crate) enum Message {
@@ -13,9 +16,62 @@ pub(crate) enum Message {
   ModelReady(MessageData<Model>),
   SceneReady(MessageData<asset_lib::Scene>),
   CurrentScene(MessageData<asset_lib::Scene>),
+  TransitionToScene { scene: MessageData<asset_lib::Scene>, duration_ms: f32 },
+  SetSceneTransform(glm::Mat4),
+  SetTargetFps(u32),
+  SetNodeVisibility { node_path: String, mask: u32 },
+  SetEnvironmentIntensity(f32),
+  PipelineStats { vertex_invocations: u64, fragment_invocations: u64, primitives_generated: u64 },
+  DropModel(u128),
+  LookupModelId { name: String },
+  ModelId { name: String, id: Option<u128> },
+  SpawnParticleSystem(ParticleSystem),
+  ParticleSystemSpawned(u32),
+  RemoveParticleSystem(u32),
+  QueryNodesInSphere { center: glm::Vec3, radius: f32 },
+  NodeQueryResults(Vec<NodeQueryResult>),
+  /// CPU time spent drawing each node over the last profiling interval, slowest nodes first.
+  FrameNodeProfile(Vec<(String, Duration)>),
+  SetDebugVisualization(DebugFlags),
+  NodeSelected { node_name: String, model_id: u128, distance: f32 },
+  /// Position to render the dynamic environment map from, `None` stops rendering it.
+  SetEnvironmentProbe(Option<glm::Vec3>),
+  /// Renders the current scene along `(camera transform, time in seconds)` keyframes into `<output_dir>/frame_<N>.png`.
+  RenderFileSequence { camera_path: Vec<(glm::Mat4, f32)>, output_dir: String, fps: u32 },
 }
 
 impl Message {
+  pub(crate) fn filter(&self) -> MessageFilter {
+    match self {
+      Message::Stop => MessageFilter::STOP,
+      Message::RequestWindowResources => MessageFilter::REQUEST_WINDOW_RESOURCES,
+      Message::RequestAsset(_) => MessageFilter::REQUEST_ASSET,
+      Message::WindowResourcesReady(_) => MessageFilter::WINDOW_RESOURCES_READY,
+      Message::ModelReady(_) => MessageFilter::MODEL_READY,
+      Message::SceneReady(_) => MessageFilter::SCENE_READY,
+      Message::CurrentScene(_) => MessageFilter::CURRENT_SCENE,
+      Message::TransitionToScene { .. } => MessageFilter::TRANSITION_TO_SCENE,
+      Message::SetSceneTransform(_) => MessageFilter::SET_SCENE_TRANSFORM,
+      Message::SetTargetFps(_) => MessageFilter::SET_TARGET_FPS,
+      Message::SetNodeVisibility { .. } => MessageFilter::SET_NODE_VISIBILITY,
+      Message::SetEnvironmentIntensity(_) => MessageFilter::SET_ENVIRONMENT_INTENSITY,
+      Message::PipelineStats { .. } => MessageFilter::PIPELINE_STATS,
+      Message::DropModel(_) => MessageFilter::DROP_MODEL,
+      Message::LookupModelId { .. } => MessageFilter::LOOKUP_MODEL_ID,
+      Message::ModelId { .. } => MessageFilter::MODEL_ID,
+      Message::SpawnParticleSystem(_) => MessageFilter::SPAWN_PARTICLE_SYSTEM,
+      Message::ParticleSystemSpawned(_) => MessageFilter::PARTICLE_SYSTEM_SPAWNED,
+      Message::RemoveParticleSystem(_) => MessageFilter::REMOVE_PARTICLE_SYSTEM,
+      Message::QueryNodesInSphere { .. } => MessageFilter::QUERY_NODES_IN_SPHERE,
+      Message::NodeQueryResults(_) => MessageFilter::NODE_QUERY_RESULTS,
+      Message::FrameNodeProfile(_) => MessageFilter::FRAME_NODE_PROFILE,
+      Message::SetDebugVisualization(_) => MessageFilter::SET_DEBUG_VISUALIZATION,
+      Message::NodeSelected { .. } => MessageFilter::NODE_SELECTED,
+      Message::SetEnvironmentProbe(_) => MessageFilter::SET_ENVIRONMENT_PROBE,
+      Message::RenderFileSequence { .. } => MessageFilter::RENDER_FILE_SEQUENCE,
+    }
+  }
+
   pub(super) fn log_message(&self) {
     match self {
       Message::Stop => debug!("Message: Stop"),
@@ -25,10 +81,84 @@ impl Message {
       Message::ModelReady(_) => debug!("Message: ModelReady"),
       Message::SceneReady(_) => debug!("Message: SceneReady"),
       Message::CurrentScene(_) => debug!("Message: CurrentScene"),
+      Message::TransitionToScene { duration_ms, .. } => debug!("Message: TransitionToScene {}ms", duration_ms),
+      Message::SetSceneTransform(_) => debug!("Message: SetSceneTransform"),
+      Message::SetTargetFps(fps) => debug!("Message: SetTargetFps {}", fps),
+      Message::SetNodeVisibility { node_path, mask } => debug!("Message: SetNodeVisibility {} {:#x}", node_path, mask),
+      Message::SetEnvironmentIntensity(intensity) => debug!("Message: SetEnvironmentIntensity {}", intensity),
+      Message::PipelineStats { .. } => trace!("Message: PipelineStats"),
+      Message::DropModel(id) => debug!("Message: DropModel {}", id),
+      Message::LookupModelId { name } => debug!("Message: LookupModelId {}", name),
+      Message::ModelId { name, id } => debug!("Message: ModelId {} {:?}", name, id),
+      Message::SpawnParticleSystem(system) => debug!("Message: SpawnParticleSystem {:?}", system),
+      Message::ParticleSystemSpawned(id) => debug!("Message: ParticleSystemSpawned {}", id),
+      Message::RemoveParticleSystem(id) => debug!("Message: RemoveParticleSystem {}", id),
+      Message::QueryNodesInSphere { center, radius } => debug!("Message: QueryNodesInSphere {:?} {}", center, radius),
+      Message::NodeQueryResults(results) => debug!("Message: NodeQueryResults {}", results.len()),
+      Message::FrameNodeProfile(profile) => debug!("Message: FrameNodeProfile {:?}", profile.first()),
+      Message::SetDebugVisualization(flags) => debug!("Message: SetDebugVisualization {:#b}", flags.bits()),
+      Message::NodeSelected { node_name, model_id, distance } => debug!("Message: NodeSelected {} {} {}", node_name, model_id, distance),
+      Message::SetEnvironmentProbe(position) => debug!("Message: SetEnvironmentProbe {:?}", position),
+      Message::RenderFileSequence { camera_path, output_dir, fps } => debug!("Message: RenderFileSequence {} keyframes {} {}fps", camera_path.len(), output_dir, fps),
     }
   }
 }
 
+/// Bitmask of message types, every `Message` variant has a bit of its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct MessageFilter(u64);
+
+#[allow(dead_code)]
+impl MessageFilter {
+  pub(crate) const ALL: Self = Self(u64::MAX);
+  pub(crate) const STOP: Self = Self(1 << 0);
+  pub(crate) const REQUEST_WINDOW_RESOURCES: Self = Self(1 << 1);
+  pub(crate) const REQUEST_ASSET: Self = Self(1 << 2);
+  pub(crate) const WINDOW_RESOURCES_READY: Self = Self(1 << 3);
+  pub(crate) const MODEL_READY: Self = Self(1 << 4);
+  pub(crate) const SCENE_READY: Self = Self(1 << 5);
+  pub(crate) const CURRENT_SCENE: Self = Self(1 << 6);
+  pub(crate) const TRANSITION_TO_SCENE: Self = Self(1 << 7);
+  pub(crate) const SET_SCENE_TRANSFORM: Self = Self(1 << 8);
+  pub(crate) const SET_TARGET_FPS: Self = Self(1 << 9);
+  pub(crate) const SET_NODE_VISIBILITY: Self = Self(1 << 10);
+  pub(crate) const SET_ENVIRONMENT_INTENSITY: Self = Self(1 << 11);
+  pub(crate) const PIPELINE_STATS: Self = Self(1 << 12);
+  pub(crate) const DROP_MODEL: Self = Self(1 << 13);
+  pub(crate) const LOOKUP_MODEL_ID: Self = Self(1 << 14);
+  pub(crate) const MODEL_ID: Self = Self(1 << 15);
+  pub(crate) const SPAWN_PARTICLE_SYSTEM: Self = Self(1 << 16);
+  pub(crate) const PARTICLE_SYSTEM_SPAWNED: Self = Self(1 << 17);
+  pub(crate) const REMOVE_PARTICLE_SYSTEM: Self = Self(1 << 18);
+  pub(crate) const QUERY_NODES_IN_SPHERE: Self = Self(1 << 19);
+  pub(crate) const NODE_QUERY_RESULTS: Self = Self(1 << 20);
+  pub(crate) const FRAME_NODE_PROFILE: Self = Self(1 << 21);
+  pub(crate) const SET_DEBUG_VISUALIZATION: Self = Self(1 << 22);
+  pub(crate) const NODE_SELECTED: Self = Self(1 << 23);
+  pub(crate) const SET_ENVIRONMENT_PROBE: Self = Self(1 << 24);
+  pub(crate) const RENDER_FILE_SEQUENCE: Self = Self(1 << 25);
+
+  pub(super) fn from_bits(bits: u64) -> Self {
+    Self(bits)
+  }
+
+  pub(super) fn bits(&self) -> u64 {
+    self.0
+  }
+
+  pub(crate) fn intersects(&self, other: MessageFilter) -> bool {
+    self.0 & other.0 != 0
+  }
+}
+
+impl std::ops::BitOr for MessageFilter {
+  type Output = Self;
+
+  fn bitor(self, rhs: Self) -> Self::Output {
+    Self(self.0 | rhs.0)
+  }
+}
+
 pub(crate) struct MessageData<T> {
   content: Arc<Mutex<Option<T>>>,
 }
@@ -4,7 +4,7 @@ mod scene_manager;
 
 pub(crate) use asset_manager::AssetManager;
 pub(crate) use renderer::Renderer;
-pub(crate) use scene_manager::SceneManager;
+pub(crate) use scene_manager::{NodeQueryResult, SceneManager};
 
 use crate::utils::thread::{Thread, Threaded};
 
@@ -2,9 +2,9 @@ use crate::framework::Model;
 use crate::message_bus::{Message, MessageBox, MessageData};
 use crate::utils::constants::*;
 use crate::utils::thread::Threaded;
-use crate::utils::tools::Result;
+use crate::utils::tools::{EngineError, Result};
 use crate::vulkan::allocator::{Image, ImagePurpose};
-use crate::vulkan::descriptors::{GlobalDescriptorSetLayout, MaterialDescriptorSetLayout};
+use crate::vulkan::descriptors::{GlobalDescriptorSetLayout, MaterialDescriptorSetLayout, SkyboxDescriptorSetLayout, TransformDescriptorSetLayout};
 use crate::vulkan::WindowResources;
 use crate::vulkan::{Allocator, Vulkan};
 
@@ -12,7 +12,7 @@ use ash::vk;
 use asset_lib as ast;
 
 use ast::AssetFile;
-use log::error;
+use log::{error, warn};
 use std::sync::mpsc::TryRecvError;
 use std::sync::Arc;
 
@@ -21,6 +21,8 @@ pub(crate) struct AssetManager {
   allocator: Allocator,
   global_descriptor_set_layout: Arc<GlobalDescriptorSetLayout>,
   material_descriptor_set_layout: Arc<MaterialDescriptorSetLayout>,
+  transform_descriptor_set_layout: Arc<TransformDescriptorSetLayout>,
+  skybox_descriptor_set_layout: Arc<SkyboxDescriptorSetLayout>,
 }
 
 #[derive(Default)]
@@ -34,12 +36,16 @@ impl AssetManager {
     let allocator = vulkan.create_allocator()?;
     let global_descriptor_set_layout = vulkan.get_global_descriptor_set_layout();
     let material_descriptor_set_layout = vulkan.get_material_descriptor_set_layout();
+    let transform_descriptor_set_layout = vulkan.get_transform_descriptor_set_layout();
+    let skybox_descriptor_set_layout = vulkan.get_skybox_descriptor_set_layout();
 
     Ok(Self {
       message_box,
       allocator,
       global_descriptor_set_layout,
       material_descriptor_set_layout,
+      transform_descriptor_set_layout,
+      skybox_descriptor_set_layout,
     })
   }
 
@@ -52,7 +58,7 @@ impl AssetManager {
       }
     };
 
-    let models = match asset_group.convert_models(&mut self.allocator) {
+    let models = match asset_group.convert_models(&mut self.allocator, &path) {
       Ok(models) => models,
       Err(e) => {
         error!("Failed to convert model assets: {}", e);
@@ -75,8 +81,24 @@ impl AssetManager {
   }
 
   fn prepare_window_resources(&mut self) {
-    let Ok(global_descriptor_sets) = self.global_descriptor_set_layout.create_descriptor_sets(&mut self.allocator, 1) else {
-      error!("Failed to create global descriptor set for window request");
+    // Updated while other frames are still in flight, so each frame in flight needs its own set
+    let Ok(global_descriptor_sets) = self.global_descriptor_set_layout.create_descriptor_sets(&mut self.allocator, MAX_FRAMES_IN_FLIGHT as usize) else {
+      error!("Failed to create global descriptor sets for window request");
+      return;
+    };
+
+    // Transforms get rewritten every frame, so each frame in flight needs its own set
+    let Ok(transform_descriptor_sets) = self
+      .transform_descriptor_set_layout
+      .create_descriptor_sets(&mut self.allocator, MAX_FRAMES_IN_FLIGHT as usize)
+    else {
+      error!("Failed to create transform descriptor sets for window request");
+      return;
+    };
+
+    // Only rewritten once the frames in flight are done, so a single set is enough
+    let Ok(skybox_descriptor_sets) = self.skybox_descriptor_set_layout.create_descriptor_sets(&mut self.allocator, 1) else {
+      error!("Failed to create skybox descriptor set for window request");
       return;
     };
 
@@ -105,6 +127,8 @@ impl AssetManager {
       depth_images,
       color_images,
       global_descriptor_sets,
+      transform_descriptor_sets,
+      skybox_descriptor_sets,
     };
     let resources = MessageData::new(resources);
 
@@ -172,18 +196,47 @@ impl AssetGroup {
     match asset.asset_type() {
       ast::AssetType::Model => self.models.push(ast::Model::load_model(asset)?),
       ast::AssetType::Scene => self.scenes.push(ast::Scene::load_scene(asset)?),
+      ast::AssetType::Pipeline | ast::AssetType::Texture | ast::AssetType::Animation => warn!("{} assets aren't handled by the asset manager yet, skipping.", asset.asset_type().name()),
     }
 
     Ok(())
   }
 
-  fn convert_models(&mut self, allocator: &mut Allocator) -> Result<Vec<Model>> {
-    self.models.drain(..).map(|model| Model::new(model, allocator)).collect::<Result<Vec<Model>>>()
+  fn convert_models(&mut self, allocator: &mut Allocator, asset_path: &str) -> Result<Vec<Model>> {
+    self
+      .models
+      .drain(..)
+      .map(|model| Model::new(model, allocator, |texture_name| texture_path(asset_path, texture_name)))
+      .collect::<Result<Vec<Model>>>()
+  }
+}
+
+/// The converter stores generated textures next to the models they belong to, either in the same archive or in the same directory.
+fn texture_path(asset_path: &str, texture_name: &str) -> String {
+  if let Some((archive_path, _)) = asset_path.split_once("::") {
+    return format!("{archive_path}::{texture_name}");
+  }
+
+  let path = std::path::Path::new(asset_path);
+  match path.extension().and_then(|extension| extension.to_str()) {
+    Some("ast") => format!("{asset_path}::{texture_name}"),
+    _ => path.with_file_name(texture_name).to_string_lossy().into_owned(),
   }
 }
 
 fn parse_asset_file(path: &str) -> Result<AssetGroup> {
   let mut asset_group = AssetGroup::default();
+
+  // Paths of the form `archive.ast::asset_name` only load a single asset out of the archive
+  if let Some((archive_path, asset_name)) = path.split_once("::") {
+    let Some(asset) = ast::AssetArchive::get_asset_by_name(archive_path, asset_name)? else {
+      return Err(EngineError::CreationError("requested asset is not present in the archive"));
+    };
+
+    asset_group.add_asset(asset)?;
+    return Ok(asset_group);
+  }
+
   let path_buf = std::path::PathBuf::from(path);
 
   match path_buf.extension().unwrap().to_str().unwrap() {
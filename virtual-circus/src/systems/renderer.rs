@@ -1,47 +1,378 @@
-use crate::framework::Model;
+use crate::framework::camera::camera_path_transform;
+use crate::framework::debug::{memory_hud_bars, memory_hud_lines};
+use crate::framework::texture_streaming::{MaterialId, TextureStreamingManager};
+use crate::framework::{Aabb, Camera, DebugFlags, Model, ParticleEmitter, ParticleSystem};
 use crate::message_bus::{Message, MessageBox, MessageData};
+use crate::utils::constants::{CAMERA_POSITION, MAX_SCENE_NODES};
+use crate::utils::frame_limiter::{FrameLimiter, RenderConfig};
 use crate::utils::thread::Threaded;
 use crate::utils::tools::{EngineError, Result};
+use crate::vulkan::descriptors::{MaterialDescriptorSetInfo, MaterialDescriptorSets, MaterialInfo, MaterialTextureSlot};
 use crate::vulkan::rendering_context::RenderingContext;
-use crate::vulkan::{Vulkan, WindowResources};
+use crate::vulkan::{Allocator, DynamicEnvironmentMap, OffscreenTarget, ShadowMap, Vulkan, Window, WindowResources};
 
-use asset_lib::{Node, Scene};
-use log::error;
+use ash::vk;
+use asset_lib as ast;
+use asset_lib::Scene;
+use glfw::{Action, WindowEvent};
+use log::{error, info, warn};
 use nalgebra_glm as glm;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
 
 pub(crate) struct Renderer {
   models: HashMap<u128, Model>,
+  model_names: HashMap<String, u128>,
   vulkan: Vulkan,
   message_box: MessageBox,
   scene: Option<Scene>,
+  scene_transform: glm::Mat4,
+  camera: Camera,
+  frame_limiter: FrameLimiter,
+  pending_environment_intensity: Option<f32>,
+  // Particle buffers are created by the renderer itself, since particle systems are spawned from messages handled here
+  allocator: Allocator,
+  particle_emitters: HashMap<u32, ParticleEmitter>,
+  next_particle_system_id: u32,
+  last_frame: Instant,
+  pending_transition: Option<(Scene, Duration)>,
+  // The old scene is drawn one last time and captured before the switch happens
+  capturing_transition: Option<(Scene, Duration)>,
+  scene_transition: Option<SceneTransition>,
+  // Only set when running with --profile-nodes
+  node_profile: Option<NodeProfile>,
+  debug_flags: DebugFlags,
+  memory_hud_frame_count: u32,
+  environment_map: Option<DynamicEnvironmentMap>,
+  // Always present while the renderer runs, only taken out while it gets rendered or dropped at shutdown
+  shadow_map: Option<ShadowMap>,
+  // The window samples the environment map, it has to be told whenever the map gets created or dropped
+  pending_environment_map: bool,
+  // Both hold textures and buffers of the renderer allocator, so they're dropped before it gets cleaned up
+  texture_streaming: Option<TextureStreamingManager>,
+  /// Descriptor sets of every loaded model, one per material and a last one with the default material.
+  material_descriptor_sets: HashMap<u128, MaterialDescriptorSets>,
+}
+
+struct SceneTransition {
+  started: Instant,
+  duration: Duration,
+}
+
+const PROFILE_NODES_FLAG: &str = "--profile-nodes";
+const NODE_PROFILE_INTERVAL: u32 = 300;
+const MEMORY_HUD_INTERVAL: u32 = 60;
+const OFFSCREEN_EXTENT: vk::Extent2D = vk::Extent2D { width: 1920, height: 1080 };
+
+#[derive(Default)]
+struct NodeProfile {
+  // draw_node only borrows the renderer, so the timings need interior mutability
+  node_times: RefCell<HashMap<String, Duration>>,
+  frame_count: u32,
 }
 
 impl Renderer {
   pub(crate) fn new(vulkan: Vulkan, message_box: MessageBox) -> Result<Self> {
+    let config = RenderConfig::load();
+    let mut allocator = vulkan.create_allocator()?;
+    let texture_streaming = TextureStreamingManager::new(&vulkan.get_device(), &mut allocator)?;
+    let shadow_map = ShadowMap::new(&vulkan, &mut allocator)?;
+    let node_profile = std::env::args().any(|argument| argument == PROFILE_NODES_FLAG).then(NodeProfile::default);
+
     Ok(Self {
       vulkan,
       message_box,
       models: HashMap::new(),
+      model_names: HashMap::new(),
       scene: None,
+      scene_transform: glm::Mat4::identity(),
+      camera: Camera::main(),
+      frame_limiter: FrameLimiter::new(config.target_fps),
+      pending_environment_intensity: None,
+      allocator,
+      particle_emitters: HashMap::new(),
+      next_particle_system_id: 0,
+      last_frame: Instant::now(),
+      pending_transition: None,
+      capturing_transition: None,
+      scene_transition: None,
+      node_profile,
+      debug_flags: DebugFlags::none(),
+      memory_hud_frame_count: 0,
+      environment_map: None,
+      shadow_map: Some(shadow_map),
+      pending_environment_map: false,
+      texture_streaming: Some(texture_streaming),
+      material_descriptor_sets: HashMap::new(),
     })
   }
 
   fn save_model(&mut self, model: MessageData<Model>) {
     if let Some(model) = model.take() {
+      if let Err(e) = self.create_materials(&model) {
+        error!("Failed to create materials of model {}: {}", model.name, e.to_string());
+        // An older model with the same id already lost its materials
+        self.drop_model(model.id);
+        return;
+      }
+
+      self.model_names.insert(model.name.clone(), model.id);
       self.models.insert(model.id, model);
     }
   }
 
+  /// Creates the material descriptor sets of a model with placeholder textures and requests its textures from the streamer.
+  fn create_materials(&mut self, model: &Model) -> Result<()> {
+    let Some(texture_streaming) = &mut self.texture_streaming else {
+      return Err(EngineError::CreationError("texture streaming is already shut down"));
+    };
+
+    // A model loaded again replaces the old one, whose materials might still be read by frames in flight
+    if self.material_descriptor_sets.contains_key(&model.id) {
+      self.vulkan.device_wait_idle();
+      self.material_descriptor_sets.remove(&model.id);
+      texture_streaming.drop_model(model.id);
+    }
+
+    let default_material = ast::Material::default();
+    let materials = model.materials.iter().chain(std::iter::once(&default_material));
+    let descriptor_infos = materials
+      .enumerate()
+      .map(|(index, material)| {
+        let material_id = MaterialId { model: model.id, material: index };
+        MaterialDescriptorSetInfo {
+          material_info: MaterialInfo::from(material),
+          texture: texture_streaming.texture_info(material_id, MaterialTextureSlot::BaseColor),
+          metallic_roughness_texture: texture_streaming.texture_info(material_id, MaterialTextureSlot::MetallicRoughness),
+          normal_texture: texture_streaming.texture_info(material_id, MaterialTextureSlot::Normal),
+          occlusion_texture: texture_streaming.texture_info(material_id, MaterialTextureSlot::Occlusion),
+          emissive_texture: texture_streaming.texture_info(material_id, MaterialTextureSlot::Emissive),
+        }
+      })
+      .collect::<Vec<MaterialDescriptorSetInfo>>();
+
+    let material_descriptor_sets = self.vulkan.get_material_descriptor_set_layout().create_descriptor_sets(&mut self.allocator, &descriptor_infos)?;
+    self.allocator.flush();
+    self.material_descriptor_sets.insert(model.id, material_descriptor_sets);
+
+    for (material, slot, path) in &model.textures {
+      texture_streaming.request_texture(MaterialId { model: model.id, material: *material }, *slot, path.clone());
+    }
+
+    Ok(())
+  }
+
+  /// Swaps in the streamed textures that finished loading.
+  fn update_streamed_textures(&mut self, window: &Window) {
+    let Some(texture_streaming) = &mut self.texture_streaming else {
+      return;
+    };
+
+    if !texture_streaming.has_completed_loads() {
+      return;
+    }
+
+    // Frames in flight read the material descriptors straight from the descriptor buffers
+    if let Err(e) = window.wait_for_frames_in_flight() {
+      error!("Failed to wait for frames in flight before swapping in textures: {}", e.to_string());
+      return;
+    }
+
+    let updated_materials = texture_streaming.tick(&mut self.allocator, &mut self.material_descriptor_sets);
+    if let Some(environment_map) = self.environment_map.as_mut().filter(|_| !updated_materials.is_empty()) {
+      environment_map.notify_material_updated();
+    }
+  }
+
+  fn drop_model(&mut self, id: u128) {
+    if self.material_descriptor_sets.contains_key(&id) {
+      // The materials of the model might still be in use by frames in flight
+      self.vulkan.device_wait_idle();
+      self.material_descriptor_sets.remove(&id);
+    }
+    if let Some(texture_streaming) = &mut self.texture_streaming {
+      texture_streaming.drop_model(id);
+    }
+
+    if let Some(model) = self.models.remove(&id) {
+      // Only forget the name if it still points at this model and not at a newer one with the same name
+      if self.model_names.get(&model.name) == Some(&id) {
+        self.model_names.remove(&model.name);
+      }
+    }
+  }
+
+  fn lookup_model_id(&self, name: String) {
+    let id = self.model_names.get(&name).copied();
+    self.message_box.post_message(Message::ModelId { name, id });
+  }
+
   fn save_scene(&mut self, scene: MessageData<Scene>) {
-    self.scene = scene.take();
+    self.set_scene(scene.take());
+  }
+
+  fn transition_to_scene(&mut self, scene: MessageData<Scene>, duration_ms: f32) {
+    if let Some(scene) = scene.take() {
+      // The window only exists inside of the render loop, so the old frame gets captured there
+      self.pending_transition = Some((scene, Duration::from_secs_f32(duration_ms.max(0.0) / 1000.0)));
+    }
+  }
+
+  /// Alpha of the captured frame of a running scene transition, fading from 1 to 0 over the transition.
+  fn fade_alpha(&mut self) -> Option<f32> {
+    let transition = self.scene_transition.as_ref()?;
+    let progress = transition.started.elapsed().as_secs_f32() / transition.duration.as_secs_f32().max(f32::EPSILON);
+
+    if progress >= 1.0 {
+      self.scene_transition = None;
+      return None;
+    }
+
+    Some(1.0 - progress)
+  }
+
+  fn set_scene(&mut self, scene: Option<Scene>) {
+    self.scene = scene;
+
+    if let Some(scene) = &self.scene {
+      if scene.nodes().len() > MAX_SCENE_NODES {
+        warn!("Scene {} has {} nodes, only the first {} will be drawn", scene.name, scene.nodes().len(), MAX_SCENE_NODES);
+      }
+    }
+  }
+
+  fn set_scene_transform(&mut self, transform: glm::Mat4) {
+    self.scene_transform = transform;
+  }
+
+  /// Node paths are the node names from a root node down to the target, separated by `/`.
+  fn set_node_visibility(&mut self, node_path: &str, mask: u32) {
+    let Some(scene) = &mut self.scene else {
+      warn!("Received node visibility without a scene loaded: {}", node_path);
+      return;
+    };
+
+    let Some(node_index) = find_node(scene, node_path) else {
+      warn!("Received node visibility for a node that doesn't exist: {}", node_path);
+      return;
+    };
+
+    scene.nodes_mut()[node_index].visibility_mask = mask;
+  }
+
+  fn set_debug_visualization(&mut self, flags: DebugFlags) {
+    self.debug_flags = flags;
+    self.memory_hud_frame_count = 0;
+  }
+
+  /// The window draws a bar per line of the memory HUD every frame, there's no font to draw the numbers with,
+  /// so those get logged every `MEMORY_HUD_INTERVAL` frames instead, over budget heaps as warnings.
+  fn update_memory_hud(&mut self, window: &mut Window) {
+    let stats = match self.debug_flags.contains(DebugFlags::MemoryHud) {
+      true => self.allocator.memory_stats(),
+      false => None,
+    };
+    let Some(stats) = stats else {
+      window.set_memory_hud(None);
+      return;
+    };
+
+    let lines = memory_hud_lines(&stats);
+    window.set_memory_hud(Some(memory_hud_bars(&lines)));
+
+    // Stats are queried every frame, but logging them that often would drown everything else
+    self.memory_hud_frame_count += 1;
+    if self.memory_hud_frame_count < MEMORY_HUD_INTERVAL {
+      return;
+    }
+    self.memory_hud_frame_count = 0;
+
+    for line in lines {
+      match line.over_threshold {
+        true => warn!("{}", line.text),
+        false => info!("{}", line.text),
+      }
+    }
+  }
+
+  fn set_environment_intensity(&mut self, intensity: f32) {
+    // The window only exists inside of the render loop, so the update gets applied there
+    self.pending_environment_intensity = Some(intensity.max(0.0));
+  }
+
+  fn set_environment_probe(&mut self, position: Option<glm::Vec3>) {
+    let Some(position) = position else {
+      if self.environment_map.is_some() {
+        // Frames in flight might still be sampling the cubemap
+        self.vulkan.device_wait_idle();
+        self.environment_map = None;
+        self.pending_environment_map = true;
+      }
+      return;
+    };
+
+    if let Some(environment_map) = &mut self.environment_map {
+      environment_map.set_position(position);
+      return;
+    }
+
+    match DynamicEnvironmentMap::new(&self.vulkan, &mut self.allocator, position) {
+      Ok(environment_map) => {
+        self.environment_map = Some(environment_map);
+        self.pending_environment_map = true;
+      }
+      Err(e) => error!("Failed to create dynamic environment map: {}", e.to_string()),
+    }
+  }
+
+  fn spawn_particle_system(&mut self, system: ParticleSystem) {
+    let id = self.next_particle_system_id;
+
+    let emitter = match ParticleEmitter::new(system, &mut self.allocator) {
+      Ok(emitter) => emitter,
+      Err(e) => {
+        error!("Failed to create particle system: {}", e.to_string());
+        return;
+      }
+    };
+    self.allocator.flush();
+
+    self.next_particle_system_id += 1;
+    self.particle_emitters.insert(id, emitter);
+    self.message_box.post_message(Message::ParticleSystemSpawned(id));
+  }
+
+  fn remove_particle_system(&mut self, id: u32) {
+    if !self.particle_emitters.contains_key(&id) {
+      warn!("Attempted to remove a particle system that doesn't exist: {}", id);
+      return;
+    }
+
+    // The particle buffer might still be in use by frames in flight
+    self.vulkan.device_wait_idle();
+    self.particle_emitters.remove(&id);
   }
 
   fn process_message(&mut self, message: Message) {
     match message {
       Message::ModelReady(model) => self.save_model(model),
       Message::CurrentScene(scene) => self.save_scene(scene),
+      Message::TransitionToScene { scene, duration_ms } => self.transition_to_scene(scene, duration_ms),
+      Message::SetSceneTransform(transform) => self.set_scene_transform(transform),
+      Message::SetTargetFps(fps) => self.frame_limiter.set_target_fps(fps),
+      Message::SetNodeVisibility { node_path, mask } => self.set_node_visibility(&node_path, mask),
+      Message::SetEnvironmentIntensity(intensity) => self.set_environment_intensity(intensity),
+      Message::DropModel(id) => self.drop_model(id),
+      Message::LookupModelId { name } => self.lookup_model_id(name),
+      Message::SpawnParticleSystem(system) => self.spawn_particle_system(system),
+      Message::RemoveParticleSystem(id) => self.remove_particle_system(id),
+      Message::SetDebugVisualization(flags) => self.set_debug_visualization(flags),
+      Message::SetEnvironmentProbe(position) => self.set_environment_probe(position),
+      Message::RenderFileSequence { camera_path, output_dir, fps } => self.render_current_scene_to_files(&camera_path, &output_dir, fps),
       _ => (),
     }
   }
@@ -57,21 +388,279 @@ impl Renderer {
     }
   }
 
-  fn draw_node(&self, matrix: glm::Mat4, node: &Node, rendering_context: &RenderingContext) {
-    let matrix = matrix * node.transform;
+  /// Computes the world space transform of every scene node, indexed the same way as the nodes of the scene.
+  fn collect_transforms(&self) -> Vec<glm::Mat4> {
+    let Some(scene) = &self.scene else {
+      return Vec::new();
+    };
+
+    let mut transforms = vec![glm::Mat4::identity(); scene.nodes().len().min(MAX_SCENE_NODES)];
+    for node in scene.parent_nodes() {
+      collect_node_transforms(scene, *node, self.scene_transform, &mut transforms);
+    }
+
+    transforms
+  }
+
+  /// Posts the visible node with a model closest along the cursor ray, tested against the world space bounds of its model.
+  fn pick_scene(&self, window: &Window) {
+    let Some(scene) = &self.scene else {
+      return;
+    };
+
+    let mut world_transforms = vec![glm::Mat4::identity(); scene.nodes().len()];
+    for node in scene.parent_nodes() {
+      collect_node_transforms(scene, *node, self.scene_transform, &mut world_transforms);
+    }
+
+    let ray = window.cursor_ray();
+    let mut closest_hit: Option<(usize, u128, f32)> = None;
+    for (node_index, node) in scene.nodes().iter().enumerate() {
+      let Some(model_index) = node.model.filter(|_| self.camera.sees(node.visibility_mask)) else {
+        continue;
+      };
+      let model_id = scene.models()[model_index];
+      let Some(bounds) = self.models.get(&model_id).and_then(|model| model.bounds) else {
+        continue;
+      };
+
+      let Some(distance) = ray.intersect_aabb(&bounds.transformed(&world_transforms[node_index])) else {
+        continue;
+      };
+      if closest_hit.map_or(true, |(_, _, closest_distance)| distance < closest_distance) {
+        closest_hit = Some((node_index, model_id, distance));
+      }
+    }
+
+    let Some((node_index, model_id, distance)) = closest_hit else {
+      return;
+    };
+
+    let node_name = scene.nodes()[node_index].name.clone();
+    info!("Selected node {} (model {}) at distance {}", node_name, model_id, distance);
+    self.message_box.post_message(Message::NodeSelected { node_name, model_id, distance });
+  }
+
+  fn process_window_event(&self, window: &Window, event: WindowEvent) {
+    if let WindowEvent::MouseButton(glfw::MouseButtonRight, Action::Press, _) = event {
+      self.pick_scene(window);
+    }
+  }
+
+  fn draw_scene(&self, rendering_context: &RenderingContext, transforms: &[glm::Mat4]) {
+    if let Some(scene) = &self.scene {
+      self.draw_scene_nodes(scene, transforms, glm::Vec3::from(CAMERA_POSITION), rendering_context);
+    }
+  }
+
+  /// Draws the shadow casters of the scene for the shadow pass of a window frame.
+  fn draw_shadow_casters(&self, rendering_context: &RenderingContext, transforms: &[glm::Mat4]) {
+    if let Some(scene) = &self.scene {
+      self.draw_shadow_caster_nodes(scene, transforms, rendering_context);
+    }
+  }
+
+  /// Levels of detail are picked for the main camera, so shadows match the meshes that are actually visible.
+  fn draw_shadow_caster_nodes(&self, scene: &Scene, transforms: &[glm::Mat4], rendering_context: &RenderingContext) {
+    let camera = Camera::shadow();
+    for node in scene.parent_nodes() {
+      self.draw_node(scene, *node, transforms, &camera, glm::Vec3::from(CAMERA_POSITION), rendering_context);
+    }
+  }
+
+  /// Draws every node seen by the main camera, with the shadow map bound for the mesh shaders to sample.
+  fn draw_scene_nodes(&self, scene: &Scene, transforms: &[glm::Mat4], camera_pos: glm::Vec3, rendering_context: &RenderingContext) {
+    if let Some(shadow_map) = &self.shadow_map {
+      rendering_context.bind_descriptor_buffer(shadow_map.descriptor_sets());
+      rendering_context.set_descriptor_set(&shadow_map.descriptor_sets()[0]);
+    }
+
+    for node in scene.parent_nodes() {
+      self.draw_node(scene, *node, transforms, &self.camera, camera_pos, rendering_context);
+    }
+  }
+
+  /// Redraws the shadow map from the light in a submission of its own, for drawing the scene outside of a window frame.
+  /// Has to happen before any such pass that draws the scene with the current transforms.
+  fn render_shadow_map(&mut self, scene: &Scene, transforms: &[glm::Mat4], time: f32) -> Result<()> {
+    // Taken out for the duration of the render, drawing the nodes needs the rest of the renderer
+    let Some(mut shadow_map) = self.shadow_map.take() else {
+      return Ok(());
+    };
+
+    let result = shadow_map.render(transforms, time, |rendering_context| self.draw_shadow_caster_nodes(scene, transforms, rendering_context));
+
+    self.shadow_map = Some(shadow_map);
+    result
+  }
+
+  /// Redraws the cubemap of the dynamic environment map once its update interval passed, viewed from the probe position.
+  fn render_environment_map(&mut self, transforms: &[glm::Mat4]) {
+    // Taken out for the duration of the render, drawing the nodes needs the rest of the renderer
+    let Some(mut environment_map) = self.environment_map.take() else {
+      return;
+    };
+
+    if environment_map.needs_update() {
+      if let Some(scene) = self.scene.take() {
+        // Submitted ahead of the frame, so it can't sample the shadow map the frame draws. The environment map draws at time 0.
+        if let Err(e) = self.render_shadow_map(&scene, transforms, 0.0) {
+          error!("Failed to render shadow map: {}", e.to_string());
+        }
+
+        let probe_pos = environment_map.position();
+        let result = environment_map.render(transforms, |rendering_context| self.draw_scene_nodes(&scene, transforms, probe_pos, rendering_context));
+
+        if let Err(e) = result {
+          error!("Failed to render dynamic environment map: {}", e.to_string());
+        }
+        self.scene = Some(scene);
+      }
+    }
+
+    self.environment_map = Some(environment_map);
+  }
+
+  /// Points the window at the current environment map, a new map is only handed over once it got rendered.
+  fn update_window_environment_map(&mut self, window: &mut Window) {
+    let environment_map = match &self.environment_map {
+      Some(environment_map) if !environment_map.is_rendered() => return,
+      environment_map => environment_map.as_ref().map(|environment_map| environment_map.skybox_descriptor_set_info()),
+    };
+
+    self.pending_environment_map = false;
+    if let Err(e) = window.set_environment_map(environment_map) {
+      error!("Failed to update the environment map of the window: {}", e.to_string());
+    }
+  }
+
+  fn render_current_scene_to_files(&mut self, camera_path: &[(glm::Mat4, f32)], output_dir: &str, fps: u32) {
+    // Taken out for the duration of the render, drawing the nodes needs the rest of the renderer
+    let Some(scene) = self.scene.take() else {
+      warn!("Received a file sequence render without a scene loaded");
+      return;
+    };
+
+    if let Err(e) = self.render_to_file_sequence(&scene, camera_path, output_dir, fps) {
+      error!("Failed to render file sequence to {}: {}", output_dir, e.to_string());
+    }
+
+    self.scene = Some(scene);
+  }
+
+  /// Renders the scene along the camera path without a window, writing every frame to `<output_dir>/frame_<N>.png`.
+  /// The path holds `(camera transform, time in seconds)` keyframes, frames are taken `fps` times a second until the last one.
+  pub(crate) fn render_to_file_sequence(&mut self, scene: &Scene, camera_path: &[(glm::Mat4, f32)], output_dir: &str, fps: u32) -> Result<()> {
+    let Some(end_time) = camera_path.last().map(|(_, time)| *time) else {
+      warn!("Camera path is empty, there are no frames to render");
+      return Ok(());
+    };
+
+    // Frames rendered before the models arrive would silently miss them
+    if scene.models().iter().any(|model| !self.models.contains_key(model)) {
+      return Err(EngineError::CreationError("scene references models that aren't loaded yet"));
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let mut target = OffscreenTarget::new(&self.vulkan, &mut self.allocator, OFFSCREEN_EXTENT)?;
+
+    let mut transforms = vec![glm::Mat4::identity(); scene.nodes().len().min(MAX_SCENE_NODES)];
+    for node in scene.parent_nodes() {
+      collect_node_transforms(scene, *node, self.scene_transform, &mut transforms);
+    }
+
+    let fps = fps.max(1);
+    let frame_count = (end_time.max(0.0) * fps as f32) as u32 + 1;
+    for frame in 0..frame_count {
+      let time = frame as f32 / fps as f32;
+      // The path isn't empty, so there always is a transform
+      let camera_transform = camera_path_transform(camera_path, time).unwrap();
+      let camera_pos = camera_transform.column(3).xyz();
+
+      // Shaders get the time in milliseconds
+      self.render_shadow_map(scene, &transforms, time * 1000.0)?;
+      target.render_frame(glm::inverse(&camera_transform), &transforms, time, |rendering_context| {
+        self.draw_scene_nodes(scene, &transforms, camera_pos, rendering_context)
+      })?;
+
+      let path = Path::new(output_dir).join(format!("frame_{}.png", frame));
+      write_png(&path, target.extent(), &target.capture_frame())?;
+    }
+
+    info!("Rendered {} frames to {}", frame_count, output_dir);
+    Ok(())
+  }
+
+  /// Index of the mesh to draw for a model with levels of detail. The camera distance is measured in bounding radii,
+  /// so larger models keep their detail for longer, the same way their size on screen would.
+  fn select_lod(model: &Model, camera_pos: glm::Vec3, node_transform: &glm::Mat4) -> usize {
+    lod_level(&model.lod_distances, model.bounds, camera_pos, node_transform)
+  }
+
+  fn finish_node_profile_frame(&mut self) {
+    let Some(node_profile) = &mut self.node_profile else {
+      return;
+    };
+
+    node_profile.frame_count += 1;
+    if node_profile.frame_count < NODE_PROFILE_INTERVAL {
+      return;
+    }
+
+    let mut node_times = node_profile.node_times.take().into_iter().collect::<Vec<(String, Duration)>>();
+    node_times.sort_by(|a, b| b.1.cmp(&a.1));
+    node_profile.frame_count = 0;
+
+    self.message_box.post_message(Message::FrameNodeProfile(node_times));
+  }
+
+  fn draw_particles(&self, window: &Window, rendering_context: &RenderingContext) {
+    let particle_emitters = self.particle_emitters.values().collect::<Vec<&ParticleEmitter>>();
+    window.draw_particles(rendering_context, &particle_emitters);
+  }
+
+  fn draw_node(&self, scene: &Scene, node_index: usize, transforms: &[glm::Mat4], camera: &Camera, camera_pos: glm::Vec3, rendering_context: &RenderingContext) {
+    let node = &scene.nodes()[node_index];
+
+    // Children keep their own masks, so hiding a node doesn't hide everything below it
+    // Nodes past the end of the transform buffer have no transform to draw with, their children might still have one
+    let model = node.model.filter(|_| node_index < MAX_SCENE_NODES && camera.sees(node.visibility_mask));
+    // Scenes can arrive before the models they reference, those nodes get drawn once their models are loaded
+    if let Some(model) = model.and_then(|model| self.models.get(&scene.models()[model])) {
+      let draw_start = Instant::now();
 
-    if let Some(model) = node.model {
-      let model = self.scene.as_ref().unwrap().models()[model];
-      let model = self.models.get(&model).unwrap();
+      rendering_context.cmd_push_constants(node_index as u32);
+      match model.lod_distances.is_empty() {
+        true => (0..model.meshes.len()).for_each(|mesh_index| self.draw_mesh(model, mesh_index, rendering_context)),
+        false => {
+          let lod = Self::select_lod(model, camera_pos, &transforms[node_index]);
+          self.draw_mesh(model, lod, rendering_context);
+        }
+      }
 
-      rendering_context.cmd_push_constants(&matrix);
-      rendering_context.draw_model(model);
+      // Only the node's own draw calls get timed, its children get entries of their own
+      if let Some(node_profile) = &self.node_profile {
+        *node_profile.node_times.borrow_mut().entry(node.name.clone()).or_default() += draw_start.elapsed();
+      }
     }
 
     for node in &node.children {
-      let node = &self.scene.as_ref().unwrap().nodes()[*node];
-      self.draw_node(matrix.clone(), node, rendering_context);
+      self.draw_node(scene, *node, transforms, camera, camera_pos, rendering_context);
+    }
+  }
+
+  fn draw_mesh(&self, model: &Model, mesh_index: usize, rendering_context: &RenderingContext) {
+    // Every saved model got its material descriptor sets created along with it
+    let material_descriptor_sets = &self.material_descriptor_sets[&model.id];
+    let material = model.meshes[mesh_index].material.map_or(model.materials.len(), |material| material as usize);
+
+    rendering_context.bind_descriptor_buffer(material_descriptor_sets);
+    rendering_context.set_descriptor_set(&material_descriptor_sets[material]);
+    if let Some(texture_streaming) = &self.texture_streaming {
+      texture_streaming.record_draw(MaterialId { model: model.id, material });
     }
+
+    rendering_context.draw_model_mesh(model, mesh_index);
   }
 }
 
@@ -92,20 +681,82 @@ impl Threaded for Renderer {
     };
 
     while !window.should_close() && !self.message_box.should_close() {
+      self.frame_limiter.begin_frame();
       self.vulkan.poll_events();
+      for (_, event) in glfw::flush_messages(&events) {
+        self.process_window_event(&window, event);
+      }
 
-      let Ok(rendering_context) = window.get_rendering_context() else {
-        error!("Failed to get rednering context of a window!");
-        continue;
-      };
+      if let Err(TryRecvError::Disconnected) = self.allocator.process_deallocations() {
+        error!("Renderer allocator unexpectedly lost ability to process deallocations, closing down");
+        break;
+      }
 
-      if let Some(scene) = &self.scene {
-        for node in scene.parent_nodes() {
-          self.draw_node(glm::Mat4::identity(), &scene.nodes()[*node], &rendering_context);
+      self.update_streamed_textures(&window);
+
+      if let Some(intensity) = self.pending_environment_intensity.take() {
+        window.update_global_descriptor(intensity);
+      }
+
+      // Read before recording the next frame, since recording resets the query of this frame slot
+      match window.read_pipeline_statistics() {
+        Ok(Some(stats)) => self.message_box.post_message(Message::PipelineStats {
+          vertex_invocations: stats.vertex_invocations,
+          fragment_invocations: stats.fragment_invocations,
+          primitives_generated: stats.primitives_generated,
+        }),
+        Ok(None) => (),
+        Err(e) => error!("Failed to read pipeline statistics: {}", e.to_string()),
+      }
+
+      if let Some(transition) = self.pending_transition.take() {
+        match window.capture_next_frame(&mut self.allocator) {
+          Ok(_) => self.capturing_transition = Some(transition),
+          Err(e) => {
+            error!("Failed to capture frame for scene transition: {}", e.to_string());
+            self.set_scene(Some(transition.0));
+          }
         }
       }
 
-      match window.draw_frame(rendering_context) {
+      let transforms = self.collect_transforms();
+      if let Err(e) = window.upload_transforms(&transforms) {
+        error!("Failed to upload scene transforms: {}", e.to_string());
+      }
+      self.render_environment_map(&transforms);
+      if self.pending_environment_map {
+        self.update_window_environment_map(&mut window);
+      }
+
+      let now = Instant::now();
+      let delta_time = now.duration_since(self.last_frame).as_secs_f32();
+      self.last_frame = now;
+
+      let particle_emitters = self.particle_emitters.values().collect::<Vec<&ParticleEmitter>>();
+      let frame = match window.begin_frame(&particle_emitters, delta_time) {
+        Ok(frame) => frame,
+        Err(EngineError::OldSwapchain) => {
+          window.recreate_swapchain().unwrap();
+          continue;
+        }
+        Err(e) => {
+          error!("Failed to begin frame: {}", e.to_string());
+          continue;
+        }
+      };
+
+      self.update_memory_hud(&mut window);
+      let fade_alpha = self.fade_alpha();
+      let draw_shadow_casters = |rendering_context: &RenderingContext| self.draw_shadow_casters(rendering_context, &transforms);
+      let draw = |rendering_context: &RenderingContext| {
+        self.draw_scene(rendering_context, &transforms);
+        self.draw_particles(&window, rendering_context);
+        if let Some(alpha) = fade_alpha {
+          window.draw_fade_overlay(rendering_context, alpha);
+        }
+      };
+
+      match window.end_frame(frame, self.shadow_map.as_ref(), draw_shadow_casters, draw) {
         Ok(_) => (),
         Err(EngineError::OldSwapchain) => window.recreate_swapchain().unwrap(),
         Err(e) => {
@@ -114,7 +765,15 @@ impl Threaded for Renderer {
         }
       };
 
+      self.finish_node_profile_frame();
+
+      if let Some((scene, duration)) = self.capturing_transition.take() {
+        self.set_scene(Some(scene));
+        self.scene_transition = Some(SceneTransition { started: Instant::now(), duration });
+      }
+
       window.progress_frame();
+      self.frame_limiter.wait_for_frame_end();
 
       if let Some(message) = self.message_box.check_messages() {
         self.process_message(message);
@@ -122,6 +781,12 @@ impl Threaded for Renderer {
     }
 
     self.vulkan.device_wait_idle();
+    self.particle_emitters.clear();
+    self.environment_map = None;
+    self.shadow_map = None;
+    self.material_descriptor_sets.clear();
+    self.texture_streaming = None;
+    self.allocator.cleanup();
     self.message_box.post_message(Message::Stop);
   }
 
@@ -129,3 +794,92 @@ impl Threaded for Renderer {
     "Renderer".to_owned()
   }
 }
+
+fn collect_node_transforms(scene: &Scene, node_index: usize, parent_transform: glm::Mat4, transforms: &mut [glm::Mat4]) {
+  let node = &scene.nodes()[node_index];
+  let transform = parent_transform * node.transform;
+
+  if let Some(slot) = transforms.get_mut(node_index) {
+    *slot = transform;
+  }
+
+  for child in &node.children {
+    collect_node_transforms(scene, *child, transform, transforms);
+  }
+}
+
+/// The bounding radius is scaled along with the node, using its largest axis so stretched nodes don't lose detail too early.
+fn lod_level(lod_distances: &[f32], bounds: Option<Aabb>, camera_pos: glm::Vec3, node_transform: &glm::Mat4) -> usize {
+  let scale = (0..3).map(|axis| glm::length(&node_transform.column(axis).xyz())).fold(0.0, f32::max);
+  let radius = bounds.map_or(1.0, |bounds| glm::length(&(bounds.max - bounds.min)) / 2.0);
+  let radius = (radius * scale).max(f32::EPSILON);
+  let distance = glm::distance(&camera_pos, &node_transform.column(3).xyz()) / radius;
+
+  lod_distances.iter().rposition(|lod_distance| distance >= *lod_distance).unwrap_or(0)
+}
+
+fn write_png(path: &Path, extent: vk::Extent2D, pixels: &[u8]) -> Result<()> {
+  let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+  let mut encoder = png::Encoder::new(file, extent.width, extent.height);
+  encoder.set_color(png::ColorType::Rgba);
+  encoder.set_depth(png::BitDepth::Eight);
+  // The color attachment is sRGB, so its bytes can be written as they are
+  encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+
+  let mut writer = encoder.write_header()?;
+  writer.write_image_data(pixels)?;
+  Ok(())
+}
+
+fn find_node(scene: &Scene, node_path: &str) -> Option<usize> {
+  let mut candidates = scene.parent_nodes();
+  let mut found = None;
+
+  for name in node_path.split('/') {
+    let node_index = *candidates.iter().find(|node| scene.nodes()[**node].name == name)?;
+    candidates = scene.nodes()[node_index].children.as_slice();
+    found = Some(node_index);
+  }
+
+  found
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const LOD_DISTANCES: [f32; 3] = [0.0, 10.0, 20.0];
+
+  #[test]
+  fn lod_switches_once_the_threshold_is_reached() {
+    let identity = glm::Mat4::identity();
+    assert_eq!(lod_level(&LOD_DISTANCES, None, glm::vec3(9.9, 0.0, 0.0), &identity), 0);
+    assert_eq!(lod_level(&LOD_DISTANCES, None, glm::vec3(10.0, 0.0, 0.0), &identity), 1);
+    assert_eq!(lod_level(&LOD_DISTANCES, None, glm::vec3(0.0, 25.0, 0.0), &identity), 2);
+  }
+
+  #[test]
+  fn lod_distance_is_measured_in_bounding_radii() {
+    // Diagonal of 4, so a radius of 2
+    let bounds = Aabb::from_points([glm::vec3(0.0, 0.0, 0.0), glm::vec3(4.0, 0.0, 0.0)]);
+    let identity = glm::Mat4::identity();
+    assert_eq!(lod_level(&LOD_DISTANCES, bounds, glm::vec3(19.9, 0.0, 0.0), &identity), 0);
+    assert_eq!(lod_level(&LOD_DISTANCES, bounds, glm::vec3(20.0, 0.0, 0.0), &identity), 1);
+  }
+
+  #[test]
+  fn scaled_nodes_keep_their_detail_longer() {
+    let camera_pos = glm::vec3(10.0, 0.0, 0.0);
+    assert_eq!(lod_level(&LOD_DISTANCES, None, camera_pos, &glm::Mat4::identity()), 1);
+    assert_eq!(lod_level(&LOD_DISTANCES, None, camera_pos, &glm::scaling(&glm::vec3(2.0, 2.0, 2.0))), 0);
+    // Only the largest axis counts
+    assert_eq!(lod_level(&LOD_DISTANCES, None, camera_pos, &glm::scaling(&glm::vec3(1.0, 2.0, 1.0))), 0);
+  }
+
+  #[test]
+  fn lod_distance_is_measured_from_the_node_position() {
+    let node_transform = glm::translation(&glm::vec3(100.0, 0.0, 0.0));
+    assert_eq!(lod_level(&LOD_DISTANCES, None, glm::vec3(95.0, 0.0, 0.0), &node_transform), 0);
+    assert_eq!(lod_level(&LOD_DISTANCES, None, glm::Vec3::zeros(), &node_transform), 2);
+  }
+}
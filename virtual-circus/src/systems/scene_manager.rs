@@ -1,16 +1,31 @@
-use crate::message_bus::{Message, MessageBox, MessageData};
+use crate::message_bus::{FilteredMessageBox, Message, MessageBox, MessageData, MessageFilter};
 use crate::utils::thread::Threaded;
 
 use asset_lib as ast;
+use nalgebra_glm as glm;
+
+#[derive(Clone, Debug)]
+pub(crate) struct NodeQueryResult {
+  pub(crate) node_index: usize,
+  pub(crate) node_name: String,
+  pub(crate) distance: f32,
+}
 
 pub(crate) struct SceneManager {
-  message_box: MessageBox,
+  message_box: FilteredMessageBox,
   scenes: Vec<ast::Scene>,
+  scene_transform: glm::Mat4,
 }
 
 impl SceneManager {
   pub(crate) fn new(message_box: MessageBox) -> Self {
-    Self { message_box, scenes: Vec::new() }
+    let filter = MessageFilter::SCENE_READY | MessageFilter::SET_SCENE_TRANSFORM | MessageFilter::QUERY_NODES_IN_SPHERE;
+
+    Self {
+      message_box: message_box.subscribe(filter),
+      scenes: Vec::new(),
+      scene_transform: glm::Mat4::identity(),
+    }
   }
 
   fn save_scene(&mut self, scene: MessageData<ast::Scene>) {
@@ -20,6 +35,33 @@ impl SceneManager {
       self.scenes.push(scene);
     }
   }
+
+  /// Finds all nodes of the current scene whose world space position lies within `radius` of `center`, closest first.
+  pub(crate) fn query_sphere(&self, center: glm::Vec3, radius: f32) -> Vec<NodeQueryResult> {
+    let Some(scene) = self.scenes.last() else {
+      return Vec::new();
+    };
+
+    let mut world_transforms = vec![glm::Mat4::identity(); scene.nodes().len()];
+    for node in scene.parent_nodes() {
+      collect_world_transforms(scene, *node, self.scene_transform, &mut world_transforms);
+    }
+
+    let mut results = Vec::new();
+    for (node_index, (node, transform)) in scene.nodes().iter().zip(world_transforms.iter()).enumerate() {
+      let distance = glm::distance(&center, &transform.column(3).xyz());
+      if distance <= radius {
+        results.push(NodeQueryResult {
+          node_index,
+          node_name: node.name.clone(),
+          distance,
+        });
+      }
+    }
+
+    results.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    results
+  }
 }
 
 impl Threaded for SceneManager {
@@ -28,6 +70,11 @@ impl Threaded for SceneManager {
       if let Some(message) = self.message_box.check_messages() {
         match message {
           Message::SceneReady(data) => self.save_scene(data),
+          Message::SetSceneTransform(transform) => self.scene_transform = transform,
+          Message::QueryNodesInSphere { center, radius } => {
+            let results = self.query_sphere(center, radius);
+            self.message_box.post_message(Message::NodeQueryResults(results));
+          }
           _ => (),
         }
       }
@@ -38,3 +85,13 @@ impl Threaded for SceneManager {
     "Scene Manager".to_owned()
   }
 }
+
+fn collect_world_transforms(scene: &ast::Scene, node_index: usize, parent_transform: glm::Mat4, transforms: &mut [glm::Mat4]) {
+  let node = &scene.nodes()[node_index];
+  let transform = parent_transform * node.transform;
+  transforms[node_index] = transform;
+
+  for child in &node.children {
+    collect_world_transforms(scene, *child, transform, transforms);
+  }
+}
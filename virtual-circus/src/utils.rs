@@ -1,3 +1,4 @@
 pub(crate) mod constants;
+pub(crate) mod frame_limiter;
 pub(crate) mod thread;
 pub(crate) mod tools;
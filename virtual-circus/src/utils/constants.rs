@@ -8,6 +8,17 @@ pub(crate) const WINDOW_HEIGHT: u32 = 900;
 pub(crate) const MAX_FRAMES_IN_FLIGHT: u32 = 2;
 pub(crate) const DESIRED_SWAPCHAIN_IMAGES: u32 = 3;
 pub(crate) const DEPTH_FORMAT: ash::vk::Format = ash::vk::Format::D32_SFLOAT;
-pub(crate) const DESCRIPTOR_SET_COUNT: usize = 2;
+pub(crate) const DESCRIPTOR_SET_COUNT: usize = 5;
 pub(crate) const GLOBAL_DESCRIPTOR_BINDING: usize = 0;
 pub(crate) const MATERIAL_DESCRIPTOR_BINDING: usize = 1;
+pub(crate) const SHADOW_DESCRIPTOR_BINDING: usize = 2;
+pub(crate) const TRANSFORM_DESCRIPTOR_BINDING: usize = 3;
+pub(crate) const SKYBOX_DESCRIPTOR_BINDING: usize = 4;
+// Post processing pipelines have their own layout, with the processed image as the only descriptor set
+pub(crate) const POST_PROCESS_DESCRIPTOR_BINDING: usize = 0;
+pub(crate) const MAX_SCENE_NODES: usize = 1024;
+// World space position the scene is viewed from, until the camera can be moved
+pub(crate) const CAMERA_POSITION: [f32; 3] = [1.0, 1.0, 1.5];
+pub(crate) const PARTICLE_WORKGROUP_SIZE: u32 = 64;
+// Converted from shaders/VTC_default, relative to the working directory like the other assets
+pub(crate) const MESH_PIPELINE_ASSET: &str = "config/VTC_default.pipl";
@@ -0,0 +1,69 @@
+use log::warn;
+use serde::Deserialize;
+
+use std::time::{Duration, Instant};
+
+#[derive(Deserialize, Default)]
+pub(crate) struct RenderConfig {
+  /// Frames per second the renderer should aim for, 0 disables the limit.
+  #[serde(default)]
+  pub(crate) target_fps: u32,
+}
+
+impl RenderConfig {
+  pub(crate) fn load() -> Self {
+    let mut config_file = std::env::current_exe().unwrap();
+    config_file.pop();
+    config_file.push("config/render.toml");
+
+    let config = match std::fs::read_to_string(&config_file) {
+      Ok(config) => config,
+      Err(e) => {
+        warn!("Failed to read render config, using defaults: {}", e);
+        return Self::default();
+      }
+    };
+
+    match toml::from_str(&config) {
+      Ok(config) => config,
+      Err(e) => {
+        warn!("Failed to parse render config, using defaults: {}", e);
+        Self::default()
+      }
+    }
+  }
+}
+
+pub(crate) struct FrameLimiter {
+  target_fps: u32,
+  frame_start: Instant,
+}
+
+impl FrameLimiter {
+  pub(crate) fn new(target_fps: u32) -> Self {
+    Self {
+      target_fps,
+      frame_start: Instant::now(),
+    }
+  }
+
+  pub(crate) fn set_target_fps(&mut self, target_fps: u32) {
+    self.target_fps = target_fps;
+  }
+
+  pub(crate) fn begin_frame(&mut self) {
+    self.frame_start = Instant::now();
+  }
+
+  /// Sleeps for whatever is left of the frame budget, does nothing if the limiter is disabled.
+  pub(crate) fn wait_for_frame_end(&self) {
+    if self.target_fps == 0 {
+      return;
+    }
+
+    let frame_budget = Duration::from_secs_f64(1.0 / self.target_fps as f64);
+    if let Some(remaining) = frame_budget.checked_sub(self.frame_start.elapsed()) {
+      std::thread::sleep(remaining);
+    }
+  }
+}
@@ -23,6 +23,22 @@ pub(crate) enum EngineError {
   AllocatorError(#[from] gpu_allocator::AllocationError),
   #[error("failed to process asset file: {0}")]
   AssetError(#[from] asset_lib::AssetError),
+  #[error("failed to process model: {0}")]
+  ModelError(#[from] ModelError),
+  #[error("failed to write file: {0}")]
+  FileError(#[from] std::io::Error),
+  #[error("failed to encode image: {0}")]
+  ImageEncodingError(#[from] png::EncodingError),
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum ModelError {
+  #[error("model is missing a required resource: {0}")]
+  NoResource(&'static str),
+  #[error("model contains an invalid field: {0}")]
+  InvalidField(&'static str),
+  #[error("failed to parse gltf data: {0}")]
+  GltfError(#[from] gltf::Error),
 }
 //---------------------------Macros------------------------
 
@@ -2,17 +2,30 @@ pub(crate) mod allocator;
 pub(crate) mod descriptors;
 mod device;
 pub(crate) mod elements;
+mod environment_map;
+pub(crate) mod frame_graph;
 pub(crate) mod rendering_context;
+mod offscreen;
+mod shadow_map;
 mod window;
 
-use self::descriptors::{GlobalDescriptorSetLayout, MaterialDescriptorSetLayout};
+use self::descriptors::{
+  GlobalDescriptorSetLayout, MaterialDescriptorSetLayout, PostProcessDescriptorSetLayout, ReflectedDescriptorSetLayout, ShadowDescriptorSetLayout, SkyboxDescriptorSetLayout,
+  TransformDescriptorSetLayout,
+};
+use self::device::DeviceConfig;
+use self::elements::{PipelineLayout, PipelineLayoutCache};
 use crate::utils::constants::*;
 use crate::utils::tools::Result;
 pub(crate) use allocator::Allocator;
 pub(crate) use device::Device;
+pub(crate) use environment_map::DynamicEnvironmentMap;
+pub(crate) use offscreen::OffscreenTarget;
+pub(crate) use shadow_map::ShadowMap;
 pub(crate) use window::{Window, WindowResources};
 
 use ash::vk;
+use asset_lib as ast;
 use glfw::{Glfw, WindowEvent};
 
 use std::sync::mpsc::Receiver;
@@ -23,20 +36,38 @@ pub(crate) struct Vulkan {
   device: Arc<Device>,
   global_descriptor_set_layout: Arc<GlobalDescriptorSetLayout>,
   material_descriptor_set_layout: Arc<MaterialDescriptorSetLayout>,
+  shadow_descriptor_set_layout: Arc<ShadowDescriptorSetLayout>,
+  transform_descriptor_set_layout: Arc<TransformDescriptorSetLayout>,
+  skybox_descriptor_set_layout: Arc<SkyboxDescriptorSetLayout>,
+  post_process_descriptor_set_layout: Arc<PostProcessDescriptorSetLayout>,
+  /// Sets the mesh pipeline asset declares after the engine's own, the engine never writes these itself.
+  mesh_descriptor_set_layouts: Vec<ReflectedDescriptorSetLayout>,
+  pipeline_layout_cache: PipelineLayoutCache,
 }
 
 impl Vulkan {
   pub(crate) fn init() -> Result<Self> {
     let glfw = glfw::init(glfw::FAIL_ON_ERRORS)?;
-    let device: Arc<Device> = Arc::new(Device::new(&glfw)?);
+    let device: Arc<Device> = Arc::new(Device::new(&glfw, &DeviceConfig::from_env())?);
     let global_descriptor_set_layout = Arc::new(GlobalDescriptorSetLayout::new(&device)?);
     let material_descriptor_set_layout = Arc::new(MaterialDescriptorSetLayout::new(&device)?);
+    let shadow_descriptor_set_layout = Arc::new(ShadowDescriptorSetLayout::new(&device)?);
+    let transform_descriptor_set_layout = Arc::new(TransformDescriptorSetLayout::new(&device)?);
+    let skybox_descriptor_set_layout = Arc::new(SkyboxDescriptorSetLayout::new(&device)?);
+    let post_process_descriptor_set_layout = Arc::new(PostProcessDescriptorSetLayout::new(&device)?);
+    let mesh_descriptor_set_layouts = reflect_mesh_descriptor_set_layouts(&device)?;
 
     Ok(Self {
       glfw,
       device,
       global_descriptor_set_layout,
       material_descriptor_set_layout,
+      shadow_descriptor_set_layout,
+      transform_descriptor_set_layout,
+      skybox_descriptor_set_layout,
+      post_process_descriptor_set_layout,
+      mesh_descriptor_set_layouts,
+      pipeline_layout_cache: PipelineLayoutCache::new(),
     })
   }
 
@@ -52,14 +83,56 @@ impl Vulkan {
     self.material_descriptor_set_layout.clone()
   }
 
+  #[allow(dead_code)]
+  pub(crate) fn get_shadow_descriptor_set_layout(&self) -> Arc<ShadowDescriptorSetLayout> {
+    self.shadow_descriptor_set_layout.clone()
+  }
+
+  pub(crate) fn get_transform_descriptor_set_layout(&self) -> Arc<TransformDescriptorSetLayout> {
+    self.transform_descriptor_set_layout.clone()
+  }
+
+  pub(crate) fn get_skybox_descriptor_set_layout(&self) -> Arc<SkyboxDescriptorSetLayout> {
+    self.skybox_descriptor_set_layout.clone()
+  }
+
+  /// Not part of `get_descriptor_set_layouts`, post processing pipelines use a layout of their own.
+  pub(crate) fn get_post_process_descriptor_set_layout(&self) -> Arc<PostProcessDescriptorSetLayout> {
+    self.post_process_descriptor_set_layout.clone()
+  }
+
+  pub(crate) fn pipeline_layout_cache(&self) -> &PipelineLayoutCache {
+    &self.pipeline_layout_cache
+  }
+
   pub(crate) fn get_descriptor_set_layouts(&self) -> [vk::DescriptorSetLayout; DESCRIPTOR_SET_COUNT] {
-    [**self.global_descriptor_set_layout, **self.material_descriptor_set_layout]
+    [
+      **self.global_descriptor_set_layout,
+      **self.material_descriptor_set_layout,
+      **self.shadow_descriptor_set_layout,
+      **self.transform_descriptor_set_layout,
+      **self.skybox_descriptor_set_layout,
+    ]
+  }
+
+  /// Layout of the pipelines drawing scene meshes, the engine's descriptor sets followed by the ones reflected from the mesh pipeline asset.
+  pub(crate) fn mesh_pipeline_layout(&self) -> Result<Arc<PipelineLayout>> {
+    let mut descriptor_set_layouts = self.get_descriptor_set_layouts().to_vec();
+    descriptor_set_layouts.extend(self.mesh_descriptor_set_layouts.iter().map(|layout| **layout));
+    self
+      .pipeline_layout_cache
+      .get_or_create(&self.device, &descriptor_set_layouts, &[PipelineLayout::node_push_constant_range()])
   }
 
   pub(crate) fn create_allocator(&self) -> Result<Allocator> {
     Allocator::new(self)
   }
 
+  #[allow(dead_code)]
+  pub(crate) fn is_ray_tracing_supported(&self) -> bool {
+    self.device.supports_ray_tracing()
+  }
+
   pub(crate) fn device_wait_idle(&self) {
     self.device.wait_idle()
   }
@@ -71,9 +144,19 @@ impl Vulkan {
   pub(crate) fn create_window(&mut self, resources: WindowResources) -> Result<(Window, Receiver<(f64, WindowEvent)>)> {
     self.glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
     self.glfw.window_hint(glfw::WindowHint::Resizable(true));
-    let (window, events) = self.glfw.create_window(WINDOW_WIDTH, WINDOW_HEIGHT, "Virtual Circus", glfw::WindowMode::Windowed).unwrap();
+    let (mut window, events) = self.glfw.create_window(WINDOW_WIDTH, WINDOW_HEIGHT, "Virtual Circus", glfw::WindowMode::Windowed).unwrap();
+    window.set_mouse_button_polling(true);
     let window = Window::new(self, window, resources)?;
 
     Ok((window, events))
   }
 }
+
+fn reflect_mesh_descriptor_set_layouts(device: &Arc<Device>) -> Result<Vec<ReflectedDescriptorSetLayout>> {
+  // Without the asset PipelineSettings::mesh falls back to the default shaders, which only use the engine's sets
+  let Ok(pipeline) = ast::AssetFile::load_from_file(MESH_PIPELINE_ASSET).and_then(ast::Pipeline::load_pipeline) else {
+    return Ok(Vec::new());
+  };
+
+  ReflectedDescriptorSetLayout::from_pipeline_sets(device, &pipeline, DESCRIPTOR_SET_COUNT as u32)
+}
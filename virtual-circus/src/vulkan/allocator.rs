@@ -12,6 +12,7 @@ use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc};
 use gpu_allocator::{vulkan, MemoryLocation};
 
 use log::{debug, error};
+use std::borrow::Cow;
 use std::mem::ManuallyDrop;
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 use std::sync::Arc;
@@ -19,6 +20,21 @@ use std::sync::Arc;
 pub(crate) enum BufferType {
   CpuVisible,
   GpuOnly,
+  /// Written by the GPU and read back on the host, like copies of rendered images.
+  GpuToCpu,
+}
+
+/// Usage of a single memory heap as reported by the driver, covering every allocation of the process and not just this allocator.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HeapStats {
+  pub(crate) used: u64,
+  pub(crate) budget: u64,
+  pub(crate) device_local: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct MemoryStats {
+  pub(crate) heaps: Vec<HeapStats>,
 }
 
 //-----------------------------------Allocators-----------------------------------------------
@@ -106,8 +122,7 @@ impl Allocator {
     unsafe {
       self.device.end_command_buffer(*command_buffer)?;
       self.device.queue_submit(transfer_queue, &[submit_info], *self.transfer_fence)?;
-      self.device.wait_for_fences(&[*self.transfer_fence], true, u64::MAX)?;
-      self.device.reset_fences(&[*self.transfer_fence])?;
+      self.transfer_fence.wait_and_reset()?;
       self.device.reset_command_buffer(*command_buffer, vk::CommandBufferResetFlags::empty())?;
       self.clear_staging_buffers();
       self.begin_recording()?;
@@ -136,10 +151,33 @@ impl Allocator {
     }
   }
 
+  /// Heap usage and budgets of the device, `None` when the device doesn't support memory budgets.
+  pub(crate) fn memory_stats(&self) -> Option<MemoryStats> {
+    if !self.device.supports_memory_budget() {
+      return None;
+    }
+
+    let (memory_properties, budget_properties) = unsafe { self.device.get_physical_device_memory_budget_properties() };
+    let heap_count = memory_properties.memory_heap_count as usize;
+    let heaps = memory_properties.memory_heaps[..heap_count]
+      .iter()
+      .enumerate()
+      .map(|(index, heap)| HeapStats {
+        used: budget_properties.heap_usage[index],
+        budget: budget_properties.heap_budget[index],
+        device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+      })
+      .collect();
+
+    Some(MemoryStats { heaps })
+  }
+
   pub(crate) fn create_buffer(&mut self, size: u64, usage: vk::BufferUsageFlags, buffer_type: BufferType) -> Result<Buffer> {
     match buffer_type {
       BufferType::CpuVisible => Buffer::new(self, size, usage, MemoryLocation::CpuToGpu),
-      BufferType::GpuOnly => Buffer::new(self, size, usage, MemoryLocation::GpuOnly),
+      // Data can only reach GPU only memory through transfers, see `upload_partial_buffer`
+      BufferType::GpuOnly => Buffer::new(self, size, usage | vk::BufferUsageFlags::TRANSFER_DST, MemoryLocation::GpuOnly),
+      BufferType::GpuToCpu => Buffer::new(self, size, usage, MemoryLocation::GpuToCpu),
     }
   }
 
@@ -152,6 +190,11 @@ impl Allocator {
         buffer.load_data(data)?;
         Ok(buffer)
       }
+      BufferType::GpuToCpu => {
+        let mut buffer = Buffer::new(self, size, usage, MemoryLocation::GpuToCpu)?;
+        buffer.load_data(data)?;
+        Ok(buffer)
+      }
       BufferType::GpuOnly => {
         let mut staging_buffer = Buffer::new(self, size, vk::BufferUsageFlags::TRANSFER_SRC, MemoryLocation::CpuToGpu)?;
         let final_buffer = Buffer::new(self, size, usage | vk::BufferUsageFlags::TRANSFER_DST, MemoryLocation::GpuOnly)?;
@@ -167,7 +210,44 @@ impl Allocator {
     }
   }
 
+  #[allow(dead_code)]
+  pub(crate) fn upload_partial_buffer(&mut self, dst: &mut Buffer, data: &[u8], dst_offset: u64) -> Result<()> {
+    let size = data.len() as u64;
+    if dst_offset + size > dst.size() {
+      return Err(EngineError::CreationError("attempted to upload data past the end of the buffer"));
+    }
+
+    match dst.location() {
+      MemoryLocation::GpuOnly => {
+        let mut staging_buffer = Buffer::new(self, size, vk::BufferUsageFlags::TRANSFER_SRC, MemoryLocation::CpuToGpu)?;
+        staging_buffer.load_data(data)?;
+
+        let command_buffer = self.get_command_buffer();
+        let transfer_stage = (vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_WRITE);
+        let any_stage = (vk::PipelineStageFlags::ALL_COMMANDS, vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE);
+
+        // Previous reads of the buffer need to finish before we overwrite it, and the new data needs to be visible to whoever reads it next
+        dst.cmd_buffer_barrier(command_buffer, any_stage, transfer_stage);
+        staging_buffer.copy_buffer_region_to_buffer(command_buffer, dst, dst_offset, size);
+        dst.cmd_buffer_barrier(command_buffer, transfer_stage, any_stage);
+
+        self.staging_buffers.push(staging_buffer);
+        Ok(())
+      }
+      _ => dst.load_partial_data(data, dst_offset),
+    }
+  }
+
   pub(crate) fn create_image(&mut self, data: &[u8], image_info: vk::ImageCreateInfo, purpose: ImagePurpose) -> Result<Image> {
+    self.create_image_with_transform(data, image_info, purpose, |data| Cow::Borrowed(data))
+  }
+
+  /// Same as `create_image`, but the source bytes are passed through `transform` before landing in the staging buffer.
+  /// Useful for formats that need swizzling or padding, see `rgb_to_rgba`. Transforms that leave the data as is can borrow it to skip the copy.
+  pub(crate) fn create_image_with_transform<F>(&mut self, data: &[u8], image_info: vk::ImageCreateInfo, purpose: ImagePurpose, transform: F) -> Result<Image>
+  where
+    F: Fn(&[u8]) -> Cow<[u8]>,
+  {
     let transfer_image_info = vk::ImageCreateInfo {
       initial_layout: vk::ImageLayout::UNDEFINED,
       usage: image_info.usage | vk::ImageUsageFlags::TRANSFER_DST,
@@ -180,7 +260,7 @@ impl Allocator {
     // Make sure we're dealing with an image type that should be filled with data.
     use ImagePurpose as IP;
     match purpose {
-      IP::Texture => self.fill_image(data, &final_image, image_info.extent)?,
+      IP::Texture => self.fill_image(&transform(data), &final_image, image_info.extent)?,
       IP::ColorAttachment => (),
       IP::DepthBuffer => (),
     };
@@ -219,3 +299,17 @@ impl Allocator {
     };
   }
 }
+
+//-----------------------------------Transforms-----------------------------------------------
+
+/// Pads tightly packed `R8G8B8` pixels to `R8G8B8A8` with an opaque alpha, 3 channel formats are rarely supported for sampling.
+#[allow(dead_code)]
+pub(crate) fn rgb_to_rgba(data: &[u8]) -> Vec<u8> {
+  let mut rgba = Vec::with_capacity(data.len() / 3 * 4);
+  for pixel in data.chunks_exact(3) {
+    rgba.extend_from_slice(pixel);
+    rgba.push(255);
+  }
+
+  rgba
+}
@@ -17,6 +17,7 @@ pub(crate) struct Buffer {
   allocation_release_channel: Sender<Allocation>,
   buffer: vk::Buffer,
   allocation: ManuallyDrop<Allocation>,
+  location: MemoryLocation,
 }
 
 impl Buffer {
@@ -33,6 +34,10 @@ impl Buffer {
     self.allocation.size()
   }
 
+  pub(super) fn location(&self) -> MemoryLocation {
+    self.location
+  }
+
   pub(crate) fn device_address(&self) -> u64 {
     let info = vk::BufferDeviceAddressInfo {
       buffer: self.buffer,
@@ -81,6 +86,7 @@ impl Buffer {
           return Err(EngineError::VulkanError(e));
         }
       };
+      device.set_debug_name(buffer, &format!("Buffer {:?} ({} bytes)", usage, size));
 
       // construct the final buffer object
       Ok(Self {
@@ -88,6 +94,7 @@ impl Buffer {
         device,
         buffer,
         allocation: ManuallyDrop::new(allocation),
+        location,
       })
     }
   }
@@ -107,11 +114,54 @@ impl Buffer {
     Ok(())
   }
 
+  pub(crate) fn load_partial_data(&mut self, data: &[u8], offset: u64) -> Result<()> {
+    let memory = match self.allocation.mapped_slice_mut() {
+      Some(memory) => memory,
+      None => return Err(EngineError::CreationError("failed to map the memory of the buffer")),
+    };
+
+    let offset = offset as usize;
+    if offset + data.len() > memory.len() {
+      return Err(EngineError::CreationError("attempted to write past the end of the buffer"));
+    }
+
+    memory[offset..offset + data.len()].clone_from_slice(data);
+    Ok(())
+  }
+
   pub(super) fn copy_buffer_to_buffer(&mut self, command_buffer: &vk::CommandBuffer, dst_buffer: &Buffer, size: u64) {
     let copy_command = vk::BufferCopy { size, ..Default::default() };
     unsafe { self.device.cmd_copy_buffer(*command_buffer, self.buffer, dst_buffer.buffer, &[copy_command]) };
   }
 
+  pub(super) fn copy_buffer_region_to_buffer(&mut self, command_buffer: &vk::CommandBuffer, dst_buffer: &Buffer, dst_offset: u64, size: u64) {
+    let copy_command = vk::BufferCopy {
+      src_offset: 0,
+      dst_offset,
+      size,
+    };
+    unsafe { self.device.cmd_copy_buffer(*command_buffer, self.buffer, dst_buffer.buffer, &[copy_command]) };
+  }
+
+  pub(super) fn cmd_buffer_barrier(&self, command_buffer: &vk::CommandBuffer, src: (vk::PipelineStageFlags, vk::AccessFlags), dst: (vk::PipelineStageFlags, vk::AccessFlags)) {
+    let barrier = vk::BufferMemoryBarrier {
+      src_access_mask: src.1,
+      dst_access_mask: dst.1,
+      src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      buffer: self.buffer,
+      offset: 0,
+      size: vk::WHOLE_SIZE,
+      ..Default::default()
+    };
+
+    unsafe {
+      self
+        .device
+        .cmd_pipeline_barrier(*command_buffer, src.0, dst.0, vk::DependencyFlags::empty(), &[], &[barrier], &[]);
+    }
+  }
+
   pub(super) fn copy_buffer_to_image(&mut self, command_buffer: &vk::CommandBuffer, dst_image: &Image, extent: vk::Extent3D) {
     let copy_command = vk::BufferImageCopy {
       buffer_offset: 0,
@@ -36,6 +36,7 @@ pub(crate) struct Image {
   allocation: ManuallyDrop<Allocation>,
   format: vk::Format,
   aspect_mask: vk::ImageAspectFlags,
+  mip_levels: u32,
 }
 
 impl Image {
@@ -68,6 +69,10 @@ impl Image {
           return Err(EngineError::VulkanError(e));
         }
       };
+      let extent = image_info.extent;
+      allocator
+        .device
+        .set_debug_name(image, &format!("Image {:?} {}x{}x{}", image_info.format, extent.width, extent.height, extent.depth));
 
       Ok(Self {
         device: allocator.device.clone(),
@@ -76,12 +81,19 @@ impl Image {
         allocation: ManuallyDrop::new(allocation),
         format: image_info.format,
         aspect_mask,
+        mip_levels: image_info.mip_levels,
       })
     }
   }
 
+  #[allow(dead_code)]
+  pub(crate) fn mip_level_count(&self) -> u32 {
+    self.mip_levels
+  }
+
+  /// View of every mip level of the image.
   pub(crate) fn make_image_view(&self) -> Result<ImageView> {
-    ImageView::new(&self.device, &self.image, &self.format, self.aspect_mask)
+    ImageView::with_mip_levels(&self.device, &self.image, &self.format, self.aspect_mask, self.mip_levels)
   }
 
   pub(super) fn prepare_image_for_transfer(&mut self, command_buffer: &vk::CommandBuffer, aspect_mask: vk::ImageAspectFlags) {
@@ -96,7 +108,7 @@ impl Image {
       subresource_range: vk::ImageSubresourceRange {
         aspect_mask,
         base_mip_level: 0,
-        level_count: 1,
+        level_count: self.mip_levels,
         base_array_layer: 0,
         layer_count: 1,
       },
@@ -134,7 +146,7 @@ impl Image {
       subresource_range: vk::ImageSubresourceRange {
         aspect_mask: purpose.aspect_mask(),
         base_mip_level: 0,
-        level_count: 1,
+        level_count: self.mip_levels,
         base_array_layer: 0,
         layer_count: 1,
       },
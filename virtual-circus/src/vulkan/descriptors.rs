@@ -1,16 +1,27 @@
 mod global_descriptor_set;
 mod material_descriptor_set;
+mod post_process_descriptor_set;
+mod reflected_descriptor_set;
+mod shadow_descriptor_set;
+mod skybox_descriptor_set;
+mod transform_descriptor_set;
 
 pub(crate) use global_descriptor_set::{GlobalDescriptorSetInfo, GlobalDescriptorSetLayout, GlobalDescriptorSets};
-pub(crate) use material_descriptor_set::{MaterialDescriptorSetInfo, MaterialDescriptorSetLayout, MaterialDescriptorSets, MaterialFlags, MaterialInfo, TextureInfo};
+pub(crate) use material_descriptor_set::{MaterialDescriptorSetInfo, MaterialDescriptorSetLayout, MaterialDescriptorSets, MaterialFlags, MaterialInfo, MaterialTextureSlot, TextureInfo};
+pub(crate) use post_process_descriptor_set::{PostProcessDescriptorSetInfo, PostProcessDescriptorSetLayout, PostProcessDescriptorSets};
+pub(crate) use reflected_descriptor_set::ReflectedDescriptorSetLayout;
+pub(crate) use shadow_descriptor_set::ShadowDescriptorSetLayout;
+pub(crate) use skybox_descriptor_set::{SkyboxDescriptorSetInfo, SkyboxDescriptorSetLayout, SkyboxDescriptorSets};
+pub(crate) use transform_descriptor_set::{TransformDescriptorSetLayout, TransformDescriptorSets};
 
 use super::allocator::{Buffer, BufferType};
 use super::Allocator;
 use super::Device;
-use crate::utils::tools::Result;
+use crate::utils::tools::{EngineError, Result};
 
 use ash::vk;
 use log::{error, trace};
+use spirv_reflect::types::ReflectDescriptorType;
 use std::sync::Arc;
 
 pub(crate) trait DescriptorSet {
@@ -38,7 +49,7 @@ impl DescriptorSetLayoutImpl {
     let mut buffer_usage = UF::SHADER_DEVICE_ADDRESS;
     for binding in bindings {
       match binding.descriptor_type {
-        DT::UNIFORM_BUFFER => buffer_usage |= UF::RESOURCE_DESCRIPTOR_BUFFER_EXT,
+        DT::UNIFORM_BUFFER | DT::STORAGE_BUFFER => buffer_usage |= UF::RESOURCE_DESCRIPTOR_BUFFER_EXT,
         DT::COMBINED_IMAGE_SAMPLER => buffer_usage |= UF::SAMPLER_DESCRIPTOR_BUFFER_EXT | UF::RESOURCE_DESCRIPTOR_BUFFER_EXT,
         _ => error!("Unsupported descriptor type used!"),
       }
@@ -72,6 +83,38 @@ impl DescriptorSetLayoutImpl {
     })
   }
 
+  /// Builds the layout of descriptor set `set` from the bindings the shaders declare for it, merging stage flags of bindings shared between shaders.
+  fn from_spirv_reflection(device: &Arc<Device>, shaders: &[&[u32]], set: u32) -> Result<Self> {
+    let mut bindings: Vec<vk::DescriptorSetLayoutBinding> = Vec::new();
+
+    for shader in shaders {
+      let module = spirv_reflect::ShaderModule::load_u32_data(shader).map_err(|_| EngineError::CreationError("failed to reflect shader module"))?;
+      let stage_flags = vk::ShaderStageFlags::from_raw(module.get_shader_stage().bits());
+      let reflected_bindings = module
+        .enumerate_descriptor_bindings(None)
+        .map_err(|_| EngineError::CreationError("failed to enumerate shader descriptor bindings"))?;
+
+      for reflected_binding in reflected_bindings.iter().filter(|binding| binding.set == set) {
+        if let Some(binding) = bindings.iter_mut().find(|binding| binding.binding == reflected_binding.binding) {
+          binding.stage_flags |= stage_flags;
+          continue;
+        }
+
+        bindings.push(vk::DescriptorSetLayoutBinding {
+          binding: reflected_binding.binding,
+          descriptor_type: reflected_to_vk_descriptor_type(reflected_binding.descriptor_type)?,
+          descriptor_count: reflected_binding.count,
+          stage_flags,
+          p_immutable_samplers: std::ptr::null(),
+        });
+      }
+    }
+
+    // Descriptor offsets are looked up by binding index, so keep the bindings in order
+    bindings.sort_by_key(|binding| binding.binding);
+    Self::new(device, &bindings)
+  }
+
   fn create_descriptor_sets(&self, allocator: &mut Allocator, count: usize) -> Result<(Buffer, Vec<DescriptorSetImpl>)> {
     let backing_buffer = allocator.create_buffer(self.layout_size * count as u64, self.buffer_usage, BufferType::CpuVisible)?;
 
@@ -102,6 +145,46 @@ impl std::ops::Deref for DescriptorSetLayoutImpl {
   }
 }
 
+/// Number of descriptor sets the shaders declare, counting up to the highest set number any of their bindings use.
+fn reflected_set_count(shaders: &[&[u32]]) -> Result<u32> {
+  let mut set_count = 0;
+
+  for shader in shaders {
+    let module = spirv_reflect::ShaderModule::load_u32_data(shader).map_err(|_| EngineError::CreationError("failed to reflect shader module"))?;
+    let reflected_bindings = module
+      .enumerate_descriptor_bindings(None)
+      .map_err(|_| EngineError::CreationError("failed to enumerate shader descriptor bindings"))?;
+
+    if let Some(highest_set) = reflected_bindings.iter().map(|binding| binding.set).max() {
+      set_count = set_count.max(highest_set + 1);
+    }
+  }
+
+  Ok(set_count)
+}
+
+fn reflected_to_vk_descriptor_type(descriptor_type: ReflectDescriptorType) -> Result<vk::DescriptorType> {
+  use vk::DescriptorType as DT;
+  use ReflectDescriptorType as RDT;
+  let descriptor_type = match descriptor_type {
+    RDT::Sampler => DT::SAMPLER,
+    RDT::CombinedImageSampler => DT::COMBINED_IMAGE_SAMPLER,
+    RDT::SampledImage => DT::SAMPLED_IMAGE,
+    RDT::StorageImage => DT::STORAGE_IMAGE,
+    RDT::UniformTexelBuffer => DT::UNIFORM_TEXEL_BUFFER,
+    RDT::StorageTexelBuffer => DT::STORAGE_TEXEL_BUFFER,
+    RDT::UniformBuffer => DT::UNIFORM_BUFFER,
+    RDT::StorageBuffer => DT::STORAGE_BUFFER,
+    RDT::UniformBufferDynamic => DT::UNIFORM_BUFFER_DYNAMIC,
+    RDT::StorageBufferDynamic => DT::STORAGE_BUFFER_DYNAMIC,
+    RDT::InputAttachment => DT::INPUT_ATTACHMENT,
+    RDT::AccelerationStructureNV => DT::ACCELERATION_STRUCTURE_NV,
+    RDT::Undefined => return Err(EngineError::CreationError("shader declares a descriptor of undefined type")),
+  };
+
+  Ok(descriptor_type)
+}
+
 //-----------------------------------Descriptor Set---------------------------------------------------
 
 struct DescriptorSetImpl {
@@ -120,25 +203,29 @@ impl DescriptorSetImpl {
   }
 
   fn write_descriptor(&self, descriptor_infos: &[vk::DescriptorGetInfoEXT], descriptor_buffer: &mut Buffer) {
-    let device_properties = unsafe { self.device.get_physical_device_descriptor_buffer_properties() };
+    for binding in 0..self.descriptor_offsets.len() {
+      let descriptor_info = descriptor_infos.get(binding).expect("Not enough provided writes for all descriptors in a set!");
+      self.write_single_descriptor(binding, descriptor_info, descriptor_buffer);
+    }
+  }
 
-    for (i, descriptor_offset) in self.descriptor_offsets.iter().enumerate() {
-      let descriptor_info = descriptor_infos.get(i).expect("Not enough provided writes for all descriptors in a set!");
+  fn write_single_descriptor(&self, binding: usize, descriptor_info: &vk::DescriptorGetInfoEXT, descriptor_buffer: &mut Buffer) {
+    let device_properties = unsafe { self.device.get_physical_device_descriptor_buffer_properties() };
 
-      use vk::DescriptorType as DT;
-      let descriptor_type_size = match descriptor_info.ty {
-        DT::UNIFORM_BUFFER => device_properties.uniform_buffer_descriptor_size,
-        DT::COMBINED_IMAGE_SAMPLER => device_properties.combined_image_sampler_descriptor_size,
-        _ => panic!("Unsuported descriptor type used in write!"),
-      };
+    use vk::DescriptorType as DT;
+    let descriptor_type_size = match descriptor_info.ty {
+      DT::UNIFORM_BUFFER => device_properties.uniform_buffer_descriptor_size,
+      DT::STORAGE_BUFFER => device_properties.storage_buffer_descriptor_size,
+      DT::COMBINED_IMAGE_SAMPLER => device_properties.combined_image_sampler_descriptor_size,
+      _ => panic!("Unsuported descriptor type used in write!"),
+    };
 
-      let descriptor_offset = (self.buffer_offset + descriptor_offset) as usize;
-      let descriptor_buffer_region = descriptor_buffer.data();
-      let descriptor_buffer_region = descriptor_buffer_region[descriptor_offset..descriptor_offset + descriptor_type_size].as_mut();
+    let descriptor_offset = (self.buffer_offset + self.descriptor_offsets[binding]) as usize;
+    let descriptor_buffer_region = descriptor_buffer.data();
+    let descriptor_buffer_region = descriptor_buffer_region[descriptor_offset..descriptor_offset + descriptor_type_size].as_mut();
 
-      unsafe { self.device.get_descriptor(descriptor_info, descriptor_buffer_region) }
-      trace!("Descriptor contents: {:?}", descriptor_buffer_region);
-    }
+    unsafe { self.device.get_descriptor(descriptor_info, descriptor_buffer_region) }
+    trace!("Descriptor contents: {:?}", descriptor_buffer_region);
   }
 
   fn get_descriptor_set_offset(&self) -> u64 {
@@ -6,6 +6,7 @@ use crate::utils::tools::Result;
 
 use ash::vk;
 use log::debug;
+use memoffset::offset_of;
 use nalgebra_glm::*;
 use serde::Serialize;
 
@@ -13,11 +14,15 @@ use std::mem::size_of;
 use std::ops::{Index, IndexMut};
 use std::sync::Arc;
 
+// Fields are tightly packed when serialized, repr(C) keeps offset_of! matching the serialized layout
 #[derive(Serialize, Default, Debug)]
+#[repr(C)]
 pub(crate) struct GlobalDescriptorSetInfo {
-  pub(crate) model: Mat4,
   pub(crate) view: Mat4,
   pub(crate) projection: Mat4,
+  pub(crate) environment_intensity: f32,
+  // Booleans serialize to a single byte, shaders expect 4
+  pub(crate) has_environment_map: u32,
 }
 
 //---------------------------------Layout--------------------------------------------------
@@ -32,7 +37,7 @@ impl GlobalDescriptorSetLayout {
       binding: 0,
       descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
       descriptor_count: 1,
-      stage_flags: vk::ShaderStageFlags::VERTEX,
+      stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
       p_immutable_samplers: std::ptr::null(),
     }];
 
@@ -132,6 +137,18 @@ impl GlobalDescriptorSet {
     let data = bincode::serialize(&info).unwrap();
     self.buffer.load_data(&data)
   }
+
+  #[allow(dead_code)]
+  pub(crate) fn update_view_only(&mut self, view: Mat4) -> Result<()> {
+    let data = bincode::serialize(&view).unwrap();
+    self.buffer.load_partial_data(&data, offset_of!(GlobalDescriptorSetInfo, view) as u64)
+  }
+
+  #[allow(dead_code)]
+  pub(crate) fn update_projection_only(&mut self, projection: Mat4) -> Result<()> {
+    let data = bincode::serialize(&projection).unwrap();
+    self.buffer.load_partial_data(&data, offset_of!(GlobalDescriptorSetInfo, projection) as u64)
+  }
 }
 
 impl DescriptorSet for GlobalDescriptorSet {
@@ -3,9 +3,10 @@ use super::super::elements::{ImageView, Sampler};
 use super::super::{Allocator, Device};
 use super::{DescriptorSet, DescriptorSetImpl, DescriptorSetLayoutImpl, DescriptorSets};
 use crate::utils::constants::*;
-use crate::utils::tools::Result;
+use crate::utils::tools::{EngineError, Result};
 
 use ash::vk;
+use asset_lib as ast;
 use bitmask_enum::bitmask;
 use nalgebra_glm::*;
 use serde::Serialize;
@@ -24,6 +25,7 @@ pub(crate) enum MaterialFlags {
   HasNormalTexture = 0b00010000,
   HasOcclusionTexture = 0b00100000,
   HasEmmisiveTexture = 0b01000000,
+  HasTransmission = 0b10000000,
 }
 
 #[derive(Serialize, Default)]
@@ -36,6 +38,52 @@ pub(crate) struct MaterialInfo {
   pub(crate) occlusion_strength_factor: f32,
   pub(crate) alpha_cutoff: f32,
   pub(crate) material_flags: MaterialFlags,
+  pub(crate) transmission_factor: f32,
+  pub(crate) thickness_factor: f32,
+  pub(crate) attenuation_color: Vec3,
+}
+
+impl From<&ast::Material> for MaterialInfo {
+  fn from(material: &ast::Material) -> Self {
+    let mut material_flags = match material.alpha_mode {
+      ast::AlphaMode::Opaque => MaterialFlags::AlphaModeOpaque,
+      ast::AlphaMode::Mask => MaterialFlags::AlphaModeMask,
+      ast::AlphaMode::Blend => MaterialFlags::AlphaModeBlend,
+    };
+
+    if material.double_sided {
+      material_flags |= MaterialFlags::DoubleSided;
+    }
+    if material.normal_texture.is_some() {
+      material_flags |= MaterialFlags::HasNormalTexture;
+    }
+    if material.transmission_factor.is_some() {
+      material_flags |= MaterialFlags::HasTransmission;
+    }
+
+    Self {
+      base_color_factor: material.base_color_factor,
+      emissive_factor: material.emissive_factor,
+      metallic_roughness_factor: Vec2::new(material.metallic_factor, material.roughness_factor),
+      normals_scale_factor: material.normal_scale,
+      occlusion_strength_factor: material.occlusion_strength,
+      alpha_cutoff: material.alpha_cutoff,
+      material_flags,
+      transmission_factor: material.transmission_factor.unwrap_or(0.0),
+      thickness_factor: material.thickness_factor,
+      attenuation_color: material.attenuation_color,
+    }
+  }
+}
+
+/// Bindings of the material textures within the material descriptor set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum MaterialTextureSlot {
+  BaseColor = 1,
+  MetallicRoughness = 2,
+  Normal = 3,
+  Occlusion = 4,
+  Emissive = 5,
 }
 
 pub(crate) struct TextureInfo<'a> {
@@ -141,6 +189,31 @@ impl MaterialDescriptorSets {
 
     Ok(Self { descriptor_buffer, descriptor_sets })
   }
+
+  /// Points a single texture of a material at a different image.
+  /// The descriptor buffer is read directly by frames in flight, so no frame using the material can still be executing.
+  pub(crate) fn update_texture(&mut self, material_index: usize, slot: MaterialTextureSlot, texture: &TextureInfo) -> Result<()> {
+    let Some(material) = self.descriptor_sets.get(material_index) else {
+      return Err(EngineError::CreationError("material to update the texture of doesn't exist"));
+    };
+
+    let image_infos = [vk::DescriptorImageInfo {
+      image_view: **texture.image_view,
+      sampler: **texture.sampler,
+      image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }];
+
+    let get_info = vk::DescriptorGetInfoEXT {
+      ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      data: vk::DescriptorDataEXT {
+        p_combined_image_sampler: image_infos.as_ptr(),
+      },
+      ..Default::default()
+    };
+
+    material.descriptor_set.write_single_descriptor(slot as usize, &get_info, &mut self.descriptor_buffer);
+    Ok(())
+  }
 }
 
 impl DescriptorSets for MaterialDescriptorSets {
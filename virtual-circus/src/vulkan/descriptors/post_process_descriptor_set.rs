@@ -0,0 +1,122 @@
+use super::super::allocator::Buffer;
+use super::super::elements::{ImageView, Sampler};
+use super::super::{Allocator, Device};
+use super::{DescriptorSet, DescriptorSetImpl, DescriptorSetLayoutImpl, DescriptorSets};
+use crate::utils::constants::*;
+use crate::utils::tools::Result;
+
+use ash::vk;
+
+use std::ops::Index;
+use std::sync::Arc;
+
+pub(crate) struct PostProcessDescriptorSetInfo<'a> {
+  /// Has to be in `SHADER_READ_ONLY_OPTIMAL` layout whenever the descriptor set gets used.
+  pub(crate) image: &'a ImageView,
+  pub(crate) sampler: &'a Sampler,
+}
+
+//---------------------------------Layout--------------------------------------------------
+
+pub(crate) struct PostProcessDescriptorSetLayout {
+  descriptor_set_layout: DescriptorSetLayoutImpl,
+}
+
+impl PostProcessDescriptorSetLayout {
+  pub(crate) fn new(device: &Arc<Device>) -> Result<Self> {
+    let bindings = [vk::DescriptorSetLayoutBinding {
+      binding: 0,
+      descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      descriptor_count: 1,
+      stage_flags: vk::ShaderStageFlags::FRAGMENT,
+      p_immutable_samplers: std::ptr::null(),
+    }];
+
+    let descriptor_set_layout = DescriptorSetLayoutImpl::new(device, &bindings)?;
+    Ok(Self { descriptor_set_layout })
+  }
+
+  pub(crate) fn create_descriptor_sets(&self, allocator: &mut Allocator, descriptor_infos: &[PostProcessDescriptorSetInfo]) -> Result<PostProcessDescriptorSets> {
+    let (descriptor_buffer, descriptor_sets) = self.descriptor_set_layout.create_descriptor_sets(allocator, descriptor_infos.len())?;
+    Ok(PostProcessDescriptorSets::new(descriptor_buffer, descriptor_sets, descriptor_infos))
+  }
+}
+
+impl std::ops::Deref for PostProcessDescriptorSetLayout {
+  type Target = vk::DescriptorSetLayout;
+
+  fn deref(&self) -> &Self::Target {
+    &self.descriptor_set_layout
+  }
+}
+
+//---------------------------------Descriptor Sets-------------------------------------------------
+
+pub(crate) struct PostProcessDescriptorSets {
+  descriptor_buffer: Buffer,
+  descriptor_sets: Vec<PostProcessDescriptorSet>,
+}
+
+impl PostProcessDescriptorSets {
+  fn new(mut descriptor_buffer: Buffer, descriptor_set_impls: Vec<DescriptorSetImpl>, descriptor_infos: &[PostProcessDescriptorSetInfo]) -> Self {
+    let mut descriptor_sets = Vec::with_capacity(descriptor_set_impls.len());
+    for (descriptor_set_impl, descriptor_info) in descriptor_set_impls.into_iter().zip(descriptor_infos) {
+      descriptor_sets.push(PostProcessDescriptorSet::new(&mut descriptor_buffer, descriptor_set_impl, descriptor_info));
+    }
+
+    Self { descriptor_buffer, descriptor_sets }
+  }
+}
+
+impl DescriptorSets for PostProcessDescriptorSets {
+  fn get_descriptor_buffer_info(&self) -> (vk::DescriptorBufferBindingInfoEXT, usize) {
+    let binding_info = vk::DescriptorBufferBindingInfoEXT {
+      address: self.descriptor_buffer.device_address(),
+      usage: vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT | vk::BufferUsageFlags::SAMPLER_DESCRIPTOR_BUFFER_EXT,
+      ..Default::default()
+    };
+
+    (binding_info, POST_PROCESS_DESCRIPTOR_BINDING)
+  }
+}
+
+impl Index<usize> for PostProcessDescriptorSets {
+  type Output = PostProcessDescriptorSet;
+
+  fn index(&self, index: usize) -> &Self::Output {
+    &self.descriptor_sets[index]
+  }
+}
+
+//---------------------------------Descriptor Set--------------------------------------------------
+pub(crate) struct PostProcessDescriptorSet {
+  descriptor_set: DescriptorSetImpl,
+}
+
+impl PostProcessDescriptorSet {
+  fn new(descriptor_buffer: &mut Buffer, descriptor_set: DescriptorSetImpl, descriptor_info: &PostProcessDescriptorSetInfo) -> Self {
+    let image_info = vk::DescriptorImageInfo {
+      image_view: **descriptor_info.image,
+      sampler: **descriptor_info.sampler,
+      image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+
+    let image_get_info = vk::DescriptorGetInfoEXT {
+      ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      data: vk::DescriptorDataEXT {
+        p_combined_image_sampler: &image_info,
+      },
+      ..Default::default()
+    };
+
+    descriptor_set.write_descriptor(&[image_get_info], descriptor_buffer);
+
+    Self { descriptor_set }
+  }
+}
+
+impl DescriptorSet for PostProcessDescriptorSet {
+  fn get_descriptor_set_info(&self) -> (u64, usize) {
+    (self.descriptor_set.get_descriptor_set_offset(), POST_PROCESS_DESCRIPTOR_BINDING)
+  }
+}
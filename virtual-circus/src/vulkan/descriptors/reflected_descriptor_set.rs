@@ -0,0 +1,45 @@
+use super::super::Device;
+use super::{reflected_set_count, DescriptorSetLayoutImpl};
+use crate::utils::tools::{EngineError, Result};
+
+use ash::vk;
+use asset_lib as ast;
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// Layout for descriptor sets of user pipelines, built from the shaders themselves instead of being written out by hand.
+pub(crate) struct ReflectedDescriptorSetLayout {
+  descriptor_set_layout: DescriptorSetLayoutImpl,
+}
+
+impl ReflectedDescriptorSetLayout {
+  pub(crate) fn from_pipeline(device: &Arc<Device>, pipeline: &ast::Pipeline, set: u32) -> Result<Self> {
+    let vertex_shader = read_spirv(&pipeline.vertex_shader)?;
+    let fragment_shader = read_spirv(&pipeline.fragment_shader)?;
+
+    let descriptor_set_layout = DescriptorSetLayoutImpl::from_spirv_reflection(device, &[&vertex_shader, &fragment_shader], set)?;
+    Ok(Self { descriptor_set_layout })
+  }
+
+  /// Layouts of every set the pipeline declares from `first_set` on, sets it skips get an empty layout so the set numbers stay in place.
+  pub(crate) fn from_pipeline_sets(device: &Arc<Device>, pipeline: &ast::Pipeline, first_set: u32) -> Result<Vec<Self>> {
+    let vertex_shader = read_spirv(&pipeline.vertex_shader)?;
+    let fragment_shader = read_spirv(&pipeline.fragment_shader)?;
+    let set_count = reflected_set_count(&[&vertex_shader, &fragment_shader])?;
+
+    (first_set..set_count).map(|set| Self::from_pipeline(device, pipeline, set)).collect()
+  }
+}
+
+impl std::ops::Deref for ReflectedDescriptorSetLayout {
+  type Target = vk::DescriptorSetLayout;
+
+  fn deref(&self) -> &Self::Target {
+    &self.descriptor_set_layout
+  }
+}
+
+fn read_spirv(code: &[u8]) -> Result<Vec<u32>> {
+  ash::util::read_spv(&mut Cursor::new(code)).map_err(|_| EngineError::CreationError("pipeline contains invalid spirv code"))
+}
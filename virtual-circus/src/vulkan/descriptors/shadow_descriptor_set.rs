@@ -0,0 +1,157 @@
+use super::super::allocator::{Buffer, BufferType};
+use super::super::elements::{ImageView, Sampler};
+use super::super::{Allocator, Device};
+use super::{DescriptorSet, DescriptorSetImpl, DescriptorSetLayoutImpl, DescriptorSets};
+use crate::utils::constants::*;
+use crate::utils::tools::Result;
+
+use ash::vk;
+use nalgebra_glm as glm;
+
+use std::mem::size_of;
+use std::ops::Index;
+use std::sync::Arc;
+
+pub(crate) struct ShadowDescriptorSetInfo<'a> {
+  pub(crate) shadow_map: &'a ImageView,
+  /// Has to be a comparison sampler created with `Sampler::new_shadow`.
+  pub(crate) sampler: &'a Sampler,
+  /// Projects world space positions into the shadow map, with depth in the `0..1` range.
+  pub(crate) light_view_projection: glm::Mat4,
+}
+
+//---------------------------------Layout--------------------------------------------------
+
+pub(crate) struct ShadowDescriptorSetLayout {
+  descriptor_set_layout: DescriptorSetLayoutImpl,
+}
+
+impl ShadowDescriptorSetLayout {
+  pub(crate) fn new(device: &Arc<Device>) -> Result<Self> {
+    let bindings = [
+      vk::DescriptorSetLayoutBinding {
+        binding: 0,
+        descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        descriptor_count: 1,
+        stage_flags: vk::ShaderStageFlags::FRAGMENT,
+        p_immutable_samplers: std::ptr::null(),
+      },
+      vk::DescriptorSetLayoutBinding {
+        binding: 1,
+        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+        descriptor_count: 1,
+        stage_flags: vk::ShaderStageFlags::FRAGMENT,
+        p_immutable_samplers: std::ptr::null(),
+      },
+    ];
+
+    let descriptor_set_layout = DescriptorSetLayoutImpl::new(device, &bindings)?;
+    Ok(Self { descriptor_set_layout })
+  }
+
+  pub(crate) fn create_descriptor_sets(&self, allocator: &mut Allocator, descriptor_infos: &[ShadowDescriptorSetInfo]) -> Result<ShadowDescriptorSets> {
+    let (descriptor_buffer, descriptor_sets) = self.descriptor_set_layout.create_descriptor_sets(allocator, descriptor_infos.len())?;
+    ShadowDescriptorSets::new(allocator, descriptor_buffer, descriptor_sets, descriptor_infos)
+  }
+}
+
+impl std::ops::Deref for ShadowDescriptorSetLayout {
+  type Target = vk::DescriptorSetLayout;
+
+  fn deref(&self) -> &Self::Target {
+    &self.descriptor_set_layout
+  }
+}
+
+//---------------------------------Descriptor Sets-------------------------------------------------
+
+pub(crate) struct ShadowDescriptorSets {
+  descriptor_buffer: Buffer,
+  descriptor_sets: Vec<ShadowDescriptorSet>,
+}
+
+impl ShadowDescriptorSets {
+  fn new(allocator: &mut Allocator, mut descriptor_buffer: Buffer, descriptor_set_impls: Vec<DescriptorSetImpl>, descriptor_infos: &[ShadowDescriptorSetInfo]) -> Result<Self> {
+    let mut descriptor_sets = Vec::with_capacity(descriptor_set_impls.len());
+    for (descriptor_set_impl, descriptor_info) in descriptor_set_impls.into_iter().zip(descriptor_infos) {
+      descriptor_sets.push(ShadowDescriptorSet::new(allocator, &mut descriptor_buffer, descriptor_set_impl, descriptor_info)?);
+    }
+
+    Ok(Self { descriptor_buffer, descriptor_sets })
+  }
+}
+
+impl DescriptorSets for ShadowDescriptorSets {
+  fn get_descriptor_buffer_info(&self) -> (vk::DescriptorBufferBindingInfoEXT, usize) {
+    let binding_info = vk::DescriptorBufferBindingInfoEXT {
+      address: self.descriptor_buffer.device_address(),
+      usage: vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT | vk::BufferUsageFlags::SAMPLER_DESCRIPTOR_BUFFER_EXT,
+      ..Default::default()
+    };
+
+    (binding_info, SHADOW_DESCRIPTOR_BINDING)
+  }
+}
+
+impl Index<usize> for ShadowDescriptorSets {
+  type Output = ShadowDescriptorSet;
+
+  fn index(&self, index: usize) -> &Self::Output {
+    &self.descriptor_sets[index]
+  }
+}
+
+//---------------------------------Descriptor Set--------------------------------------------------
+pub(crate) struct ShadowDescriptorSet {
+  descriptor_set: DescriptorSetImpl,
+  _light_buffer: Buffer,
+}
+
+impl ShadowDescriptorSet {
+  fn new(allocator: &mut Allocator, descriptor_buffer: &mut Buffer, descriptor_set: DescriptorSetImpl, descriptor_info: &ShadowDescriptorSetInfo) -> Result<Self> {
+    // The light doesn't move, so its matrix is written once along with the set
+    let usage = vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+    let mut light_buffer = allocator.create_buffer(size_of::<glm::Mat4>() as u64, usage, BufferType::CpuVisible)?;
+    light_buffer.load_data(&bincode::serialize(&descriptor_info.light_view_projection).unwrap())?;
+
+    let shadow_map_info = vk::DescriptorImageInfo {
+      image_view: **descriptor_info.shadow_map,
+      sampler: **descriptor_info.sampler,
+      image_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+    };
+
+    let shadow_map_get_info = vk::DescriptorGetInfoEXT {
+      ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      data: vk::DescriptorDataEXT {
+        p_combined_image_sampler: &shadow_map_info,
+      },
+      ..Default::default()
+    };
+
+    let light_data = vk::DescriptorAddressInfoEXT {
+      address: light_buffer.device_address(),
+      range: light_buffer.size(),
+      format: vk::Format::UNDEFINED,
+      ..Default::default()
+    };
+
+    let light_get_info = vk::DescriptorGetInfoEXT {
+      ty: vk::DescriptorType::UNIFORM_BUFFER,
+      data: vk::DescriptorDataEXT { p_uniform_buffer: &light_data },
+      ..Default::default()
+    };
+
+    descriptor_set.write_descriptor(&[shadow_map_get_info, light_get_info], descriptor_buffer);
+
+    Ok(Self {
+      descriptor_set,
+      _light_buffer: light_buffer,
+    })
+  }
+}
+
+impl DescriptorSet for ShadowDescriptorSet {
+  fn get_descriptor_set_info(&self) -> (u64, usize) {
+    (self.descriptor_set.get_descriptor_set_offset(), SHADOW_DESCRIPTOR_BINDING)
+  }
+}
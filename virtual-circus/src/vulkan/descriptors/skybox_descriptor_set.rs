@@ -0,0 +1,117 @@
+use super::super::allocator::Buffer;
+use super::super::elements::{ImageView, Sampler};
+use super::super::{Allocator, Device};
+use super::{DescriptorSet, DescriptorSetImpl, DescriptorSetLayoutImpl, DescriptorSets};
+use crate::utils::constants::*;
+use crate::utils::tools::{EngineError, Result};
+
+use ash::vk;
+
+use std::ops::Index;
+use std::sync::Arc;
+
+pub(crate) struct SkyboxDescriptorSetInfo<'a> {
+  /// Cube view of the environment map, has to be in `SHADER_READ_ONLY_OPTIMAL` layout whenever the descriptor set gets used.
+  pub(crate) image: &'a ImageView,
+  pub(crate) sampler: &'a Sampler,
+}
+
+//---------------------------------Layout--------------------------------------------------
+
+pub(crate) struct SkyboxDescriptorSetLayout {
+  descriptor_set_layout: DescriptorSetLayoutImpl,
+}
+
+impl SkyboxDescriptorSetLayout {
+  pub(crate) fn new(device: &Arc<Device>) -> Result<Self> {
+    let bindings = [vk::DescriptorSetLayoutBinding {
+      binding: 0,
+      descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      descriptor_count: 1,
+      stage_flags: vk::ShaderStageFlags::FRAGMENT,
+      p_immutable_samplers: std::ptr::null(),
+    }];
+
+    let descriptor_set_layout = DescriptorSetLayoutImpl::new(device, &bindings)?;
+    Ok(Self { descriptor_set_layout })
+  }
+
+  /// The sets start out without an environment map, until one gets written they can only be bound for pipelines that don't sample them.
+  pub(crate) fn create_descriptor_sets(&self, allocator: &mut Allocator, count: usize) -> Result<SkyboxDescriptorSets> {
+    let (descriptor_buffer, descriptor_sets) = self.descriptor_set_layout.create_descriptor_sets(allocator, count)?;
+    let descriptor_sets = descriptor_sets.into_iter().map(|descriptor_set| SkyboxDescriptorSet { descriptor_set }).collect();
+    Ok(SkyboxDescriptorSets { descriptor_buffer, descriptor_sets })
+  }
+}
+
+impl std::ops::Deref for SkyboxDescriptorSetLayout {
+  type Target = vk::DescriptorSetLayout;
+
+  fn deref(&self) -> &Self::Target {
+    &self.descriptor_set_layout
+  }
+}
+
+//---------------------------------Descriptor Sets-------------------------------------------------
+
+pub(crate) struct SkyboxDescriptorSets {
+  descriptor_buffer: Buffer,
+  descriptor_sets: Vec<SkyboxDescriptorSet>,
+}
+
+impl SkyboxDescriptorSets {
+  /// Points a set at another environment map, the set must not be in use by the GPU while it gets rewritten.
+  pub(crate) fn update_environment_map(&mut self, index: usize, descriptor_info: &SkyboxDescriptorSetInfo) -> Result<()> {
+    let Some(skybox) = self.descriptor_sets.get(index) else {
+      return Err(EngineError::CreationError("skybox descriptor set to update doesn't exist"));
+    };
+
+    let image_info = vk::DescriptorImageInfo {
+      image_view: **descriptor_info.image,
+      sampler: **descriptor_info.sampler,
+      image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+
+    let image_get_info = vk::DescriptorGetInfoEXT {
+      ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      data: vk::DescriptorDataEXT {
+        p_combined_image_sampler: &image_info,
+      },
+      ..Default::default()
+    };
+
+    skybox.descriptor_set.write_descriptor(&[image_get_info], &mut self.descriptor_buffer);
+    Ok(())
+  }
+}
+
+impl DescriptorSets for SkyboxDescriptorSets {
+  fn get_descriptor_buffer_info(&self) -> (vk::DescriptorBufferBindingInfoEXT, usize) {
+    let binding_info = vk::DescriptorBufferBindingInfoEXT {
+      address: self.descriptor_buffer.device_address(),
+      usage: vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT | vk::BufferUsageFlags::SAMPLER_DESCRIPTOR_BUFFER_EXT,
+      ..Default::default()
+    };
+
+    (binding_info, SKYBOX_DESCRIPTOR_BINDING)
+  }
+}
+
+impl Index<usize> for SkyboxDescriptorSets {
+  type Output = SkyboxDescriptorSet;
+
+  fn index(&self, index: usize) -> &Self::Output {
+    &self.descriptor_sets[index]
+  }
+}
+
+//---------------------------------Descriptor Set--------------------------------------------------
+pub(crate) struct SkyboxDescriptorSet {
+  descriptor_set: DescriptorSetImpl,
+}
+
+impl DescriptorSet for SkyboxDescriptorSet {
+  fn get_descriptor_set_info(&self) -> (u64, usize) {
+    (self.descriptor_set.get_descriptor_set_offset(), SKYBOX_DESCRIPTOR_BINDING)
+  }
+}
@@ -0,0 +1,141 @@
+use super::super::allocator::{Buffer, BufferType};
+use super::super::{Allocator, Device};
+use super::{DescriptorSet, DescriptorSetImpl, DescriptorSetLayoutImpl, DescriptorSets};
+use crate::utils::constants::{MAX_SCENE_NODES, TRANSFORM_DESCRIPTOR_BINDING};
+use crate::utils::tools::{EngineError, Result};
+
+use ash::vk;
+use nalgebra_glm::*;
+
+use std::mem::size_of;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+//---------------------------------Layout--------------------------------------------------
+
+pub(crate) struct TransformDescriptorSetLayout {
+  descriptor_set_layout: DescriptorSetLayoutImpl,
+}
+
+impl TransformDescriptorSetLayout {
+  pub(crate) fn new(device: &Arc<Device>) -> Result<Self> {
+    let bindings = [vk::DescriptorSetLayoutBinding {
+      binding: 0,
+      descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+      descriptor_count: 1,
+      stage_flags: vk::ShaderStageFlags::VERTEX,
+      p_immutable_samplers: std::ptr::null(),
+    }];
+
+    let descriptor_set_layout = DescriptorSetLayoutImpl::new(device, &bindings)?;
+    Ok(Self { descriptor_set_layout })
+  }
+
+  pub(crate) fn create_descriptor_sets(&self, allocator: &mut Allocator, count: usize) -> Result<TransformDescriptorSets> {
+    let (descriptor_buffer, descriptor_sets) = self.descriptor_set_layout.create_descriptor_sets(allocator, count)?;
+    TransformDescriptorSets::new(allocator, descriptor_buffer, descriptor_sets)
+  }
+}
+
+impl std::ops::Deref for TransformDescriptorSetLayout {
+  type Target = vk::DescriptorSetLayout;
+
+  fn deref(&self) -> &Self::Target {
+    &self.descriptor_set_layout
+  }
+}
+
+//---------------------------------Descriptor Sets-------------------------------------------------
+
+pub(crate) struct TransformDescriptorSets {
+  descriptor_buffer: Buffer,
+  descriptor_sets: Vec<TransformDescriptorSet>,
+}
+
+impl TransformDescriptorSets {
+  fn new(allocator: &mut Allocator, mut descriptor_buffer: Buffer, descriptor_set_impls: Vec<DescriptorSetImpl>) -> Result<Self> {
+    let mut descriptor_sets = Vec::with_capacity(descriptor_set_impls.len());
+    for descriptor_set_impl in descriptor_set_impls {
+      descriptor_sets.push(TransformDescriptorSet::new(allocator, &mut descriptor_buffer, descriptor_set_impl)?);
+    }
+
+    Ok(Self { descriptor_buffer, descriptor_sets })
+  }
+}
+
+impl DescriptorSets for TransformDescriptorSets {
+  fn get_descriptor_buffer_info(&self) -> (vk::DescriptorBufferBindingInfoEXT, usize) {
+    let binding_info = vk::DescriptorBufferBindingInfoEXT {
+      address: self.descriptor_buffer.device_address(),
+      usage: vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT,
+      ..Default::default()
+    };
+
+    (binding_info, TRANSFORM_DESCRIPTOR_BINDING)
+  }
+}
+
+impl Index<usize> for TransformDescriptorSets {
+  type Output = TransformDescriptorSet;
+
+  fn index(&self, index: usize) -> &Self::Output {
+    &self.descriptor_sets[index]
+  }
+}
+
+impl IndexMut<usize> for TransformDescriptorSets {
+  fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+    &mut self.descriptor_sets[index]
+  }
+}
+
+//---------------------------------Descriptor Set--------------------------------------------------
+pub(crate) struct TransformDescriptorSet {
+  descriptor_set: DescriptorSetImpl,
+  buffer: Buffer,
+}
+
+impl TransformDescriptorSet {
+  fn new(allocator: &mut Allocator, descriptor_buffer: &mut Buffer, descriptor_set: DescriptorSetImpl) -> Result<Self> {
+    let usage = vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+    let buffer = allocator.create_buffer((size_of::<Mat4>() * MAX_SCENE_NODES) as u64, usage, BufferType::CpuVisible)?;
+
+    let data = vk::DescriptorAddressInfoEXT {
+      address: buffer.device_address(),
+      range: buffer.size(),
+      format: vk::Format::UNDEFINED,
+      ..Default::default()
+    };
+
+    let get_info = vk::DescriptorGetInfoEXT {
+      ty: vk::DescriptorType::STORAGE_BUFFER,
+      data: vk::DescriptorDataEXT { p_storage_buffer: [data].as_ptr() },
+      ..Default::default()
+    };
+
+    descriptor_set.write_descriptor(&[get_info], descriptor_buffer);
+
+    Ok(Self { descriptor_set, buffer })
+  }
+
+  /// Uploads world space transforms of scene nodes, indexed by the node index pushed with each draw call.
+  pub(crate) fn update_transforms(&mut self, transforms: &[Mat4]) -> Result<()> {
+    if transforms.len() > MAX_SCENE_NODES {
+      return Err(EngineError::CreationError("scene has more nodes than the transform buffer can hold"));
+    }
+
+    // Serializing the slice directly would prefix it with its length, so the matrices get packed one by one
+    let mut data = Vec::with_capacity(size_of::<Mat4>() * transforms.len());
+    for transform in transforms {
+      data.extend(bincode::serialize(transform).unwrap());
+    }
+
+    self.buffer.load_data(&data)
+  }
+}
+
+impl DescriptorSet for TransformDescriptorSet {
+  fn get_descriptor_set_info(&self) -> (u64, usize) {
+    (self.descriptor_set.get_descriptor_set_offset(), TRANSFORM_DESCRIPTOR_BINDING)
+  }
+}
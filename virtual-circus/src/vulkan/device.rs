@@ -21,38 +21,64 @@ pub(crate) struct Device {
   device: ash::Device,
   transfer_queue_family_index: u32,
   graphics_queue_family_index: u32,
+  /// Usually the same family as the graphics queue, some implementations can only present from a separate one.
+  present_queue_family_index: u32,
+  supports_memory_budget: bool,
   surface_loader: Surface,
   swapchain_loader: Swapchain,
   vertex_input_dynamic_state: VertexInputDynamicState,
   descriptor_buffer: DescriptorBuffer,
 }
 
+#[derive(Default)]
+pub(crate) struct DeviceConfig {
+  /// Accept integrated GPUs when no discrete one is suitable.
+  pub(crate) allow_integrated_gpu: bool,
+  /// Extensions required on top of the ones the engine always needs.
+  pub(crate) required_extensions: Vec<CString>,
+}
+
+impl DeviceConfig {
+  pub(crate) fn from_env() -> Self {
+    let allow_integrated_gpu = std::env::var("VIRTUAL_CIRCUS_ALLOW_INTEGRATED_GPU").is_ok_and(|value| value == "1");
+
+    Self {
+      allow_integrated_gpu,
+      ..Default::default()
+    }
+  }
+}
+
 //------------------------Setup----------------------------------
 
-fn get_required_extensions() -> Vec<CString> {
-  vec![
+fn get_required_extensions(config: &DeviceConfig) -> Vec<CString> {
+  let mut extensions = vec![
     ash::extensions::khr::Swapchain::name().to_owned(),
     ash::extensions::ext::DescriptorBuffer::name().to_owned(),
     CString::new("VK_EXT_vertex_input_dynamic_state").unwrap(),
     CString::new("VK_EXT_robustness2").unwrap(),
     CString::new("VK_EXT_index_type_uint8").unwrap(),
-  ]
+  ];
+
+  extensions.extend(config.required_extensions.iter().cloned());
+  extensions
 }
 
 //------------------------Device----------------------------------
 
 impl Device {
-  pub(crate) fn new(glfw: &Glfw) -> Result<Self> {
+  pub(crate) fn new(glfw: &Glfw, config: &DeviceConfig) -> Result<Self> {
     let instance = Instance::new(glfw)?;
 
     debug!("Creating a logical device.");
     // let graphics_queue = device.get_device_queue(graphics_queue_family_index, 0);s
     //When searching for physical devices we check whether it supports both queue types so just unwrap
-    let physical_device = pick_physical_device(&instance, glfw)?;
+    let physical_device = pick_physical_device(&instance, glfw, config)?;
 
     let graphics_queue_family_index = find_graphics_queue_family(&instance, physical_device).unwrap();
     let transfer_queue_family_index = find_transfer_queue_family(&instance, physical_device).unwrap();
-    let queue_family_indices = [graphics_queue_family_index, transfer_queue_family_index];
+    let present_queue_family_index = find_present_queue_family(&instance, glfw, physical_device).unwrap();
+    let queue_family_indices = [graphics_queue_family_index, transfer_queue_family_index, present_queue_family_index];
     let queue_priorities = [1.0];
 
     let graphics_queue_ci = vk::DeviceQueueCreateInfo {
@@ -69,15 +95,33 @@ impl Device {
       ..Default::default()
     };
 
-    let queue_infos = [graphics_queue_ci, transfer_queue_ci];
+    let mut queue_infos = vec![graphics_queue_ci, transfer_queue_ci];
+
+    // Every queue family can only be requested once
+    if !queue_family_indices[..2].contains(&present_queue_family_index) {
+      queue_infos.push(vk::DeviceQueueCreateInfo {
+        queue_family_index: queue_family_indices[2],
+        p_queue_priorities: queue_priorities.as_ptr(),
+        queue_count: queue_priorities.len() as u32,
+        ..Default::default()
+      });
+    }
 
     // Extension compatibility is checked when the physical device is picked.
-    let extensions = get_required_extensions();
+    let mut extensions = get_required_extensions(config);
+
+    // Memory budgets are only used for debug statistics, so devices without them are still fine
+    let memory_budget_extension = CString::new("VK_EXT_memory_budget").unwrap();
+    let supports_memory_budget = device_supports_extension(&instance, physical_device, &memory_budget_extension);
+    if supports_memory_budget {
+      extensions.push(memory_budget_extension);
+    }
     trace!("Requested device extensions: {:?}", extensions);
     let extensions: Vec<*const i8> = extensions.iter().map(|item| item.as_ptr()).collect();
 
     let vulkan_10_features = vk::PhysicalDeviceFeatures {
       sampler_anisotropy: vk::TRUE,
+      pipeline_statistics_query: vk::TRUE,
       ..Default::default()
     };
 
@@ -85,6 +129,7 @@ impl Device {
 
     let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features {
       buffer_device_address: vk::TRUE,
+      timeline_semaphore: vk::TRUE,
       shader_uniform_buffer_array_non_uniform_indexing: vk::TRUE,
       shader_sampled_image_array_non_uniform_indexing: vk::TRUE,
       descriptor_binding_uniform_buffer_update_after_bind: vk::TRUE,
@@ -94,6 +139,7 @@ impl Device {
 
     let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features {
       dynamic_rendering: vk::TRUE,
+      synchronization2: vk::TRUE,
       ..Default::default()
     };
 
@@ -142,6 +188,8 @@ impl Device {
       device,
       transfer_queue_family_index,
       graphics_queue_family_index,
+      present_queue_family_index,
+      supports_memory_budget,
       surface_loader,
       swapchain_loader,
       vertex_input_dynamic_state,
@@ -155,6 +203,39 @@ impl Device {
     }
   }
 
+  /// Only reports whether the physical device could do ray tracing, none of the ray tracing extensions get enabled.
+  pub(crate) fn supports_ray_tracing(&self) -> bool {
+    let ray_tracing_extensions = [
+      ash::extensions::khr::AccelerationStructure::name().to_owned(),
+      ash::extensions::khr::RayTracingPipeline::name().to_owned(),
+      ash::extensions::khr::DeferredHostOperations::name().to_owned(),
+    ];
+
+    let device_extensions = match unsafe { self.instance.enumerate_device_extension_properties(self.physical_device) } {
+      Ok(extensions) => extensions,
+      Err(e) => {
+        error!("Failed to get device extension properties: {}", e);
+        return false;
+      }
+    };
+    let device_extensions: Vec<CString> = device_extensions.iter().map(|extension| vk_to_string(&extension.extension_name).to_owned()).collect();
+
+    trace!("Checking if device has the ray tracing extensions...");
+    if !required_match_available(&ray_tracing_extensions, &device_extensions) {
+      return false;
+    }
+
+    let mut acceleration_structure_features = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder().push_next(&mut acceleration_structure_features);
+    unsafe { self.instance.get_physical_device_features2(self.physical_device, &mut features) };
+
+    acceleration_structure_features.acceleration_structure == vk::TRUE
+  }
+
+  pub(crate) fn supports_memory_budget(&self) -> bool {
+    self.supports_memory_budget
+  }
+
   pub(crate) fn instance(&self) -> &Instance {
     &self.instance
   }
@@ -171,6 +252,10 @@ impl Device {
     unsafe { self.device.get_device_queue(self.transfer_queue_family_index, 0) }
   }
 
+  pub(crate) fn present_queue(&self) -> vk::Queue {
+    unsafe { self.device.get_device_queue(self.present_queue_family_index, 0) }
+  }
+
   pub(crate) fn transfer_queue_family_index(&self) -> u32 {
     self.transfer_queue_family_index
   }
@@ -179,6 +264,32 @@ impl Device {
     self.graphics_queue_family_index
   }
 
+  pub(crate) fn present_queue_family_index(&self) -> u32 {
+    self.present_queue_family_index
+  }
+
+  /// Labels a Vulkan object so debugging tools like RenderDoc show it by name instead of by handle.
+  #[cfg(debug_assertions)]
+  pub(crate) fn set_debug_name<T: vk::Handle>(&self, object: T, name: &str) {
+    let Ok(name) = CString::new(name) else {
+      error!("Debug name {} contains a nul byte, skipping", name);
+      return;
+    };
+
+    let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+      .object_type(T::TYPE)
+      .object_handle(object.as_raw())
+      .object_name(&name);
+
+    if let Err(e) = unsafe { self.instance.debug_utils_loader().set_debug_utils_object_name(self.device.handle(), &name_info) } {
+      error!("Failed to set debug name {:?}: {}", name, e);
+    }
+  }
+
+  // Object names only matter when debugging, release builds skip them entirely
+  #[cfg(not(debug_assertions))]
+  pub(crate) fn set_debug_name<T: vk::Handle>(&self, _object: T, _name: &str) {}
+
   // Delegates
   pub(crate) unsafe fn get_physical_device_properties(&self) -> vk::PhysicalDeviceProperties {
     self.instance.get_physical_device_properties(self.physical_device)
@@ -191,6 +302,14 @@ impl Device {
     properties
   }
 
+  /// Only valid when `supports_memory_budget` is true.
+  pub(crate) unsafe fn get_physical_device_memory_budget_properties(&self) -> (vk::PhysicalDeviceMemoryProperties, vk::PhysicalDeviceMemoryBudgetPropertiesEXT) {
+    let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let mut properties = vk::PhysicalDeviceMemoryProperties2::builder().push_next(&mut budget_properties);
+    self.instance.get_physical_device_memory_properties2(self.physical_device, &mut properties);
+    (properties.memory_properties, budget_properties)
+  }
+
   pub(crate) unsafe fn get_physical_device_descriptor_buffer_properties(&self) -> vk::PhysicalDeviceDescriptorBufferPropertiesEXT {
     let mut descriptor_buffer_properties = vk::PhysicalDeviceDescriptorBufferPropertiesEXT::default();
     let properties = vk::PhysicalDeviceProperties2::builder();
@@ -296,11 +415,14 @@ impl Deref for Device {
 
 //------------------------Helpers-------------------------------
 
-fn pick_physical_device(instance: &Instance, glfw: &Glfw) -> Result<vk::PhysicalDevice> {
+fn pick_physical_device(instance: &Instance, glfw: &Glfw, config: &DeviceConfig) -> Result<vk::PhysicalDevice> {
   debug!("Picking physical device.");
   let physical_devices = unsafe { instance.enumerate_physical_devices()? };
+  let suitable_devices: Vec<vk::PhysicalDevice> = physical_devices.into_iter().filter(|device| device_is_suitable(instance, glfw, config, *device)).collect();
 
-  let device = physical_devices.into_iter().find(|device| device_is_suitable(instance, glfw, *device)).ok_or_else(|| {
+  // Integrated GPUs are only a fallback, so prefer a discrete GPU whenever there is one
+  let is_discrete = |device: &vk::PhysicalDevice| unsafe { instance.get_physical_device_properties(*device).device_type == vk::PhysicalDeviceType::DISCRETE_GPU };
+  let device = suitable_devices.iter().copied().find(is_discrete).or(suitable_devices.first().copied()).ok_or_else(|| {
     error!("Couldn't find suitable physical device!");
     vk::Result::ERROR_INITIALIZATION_FAILED
   })?;
@@ -309,7 +431,7 @@ fn pick_physical_device(instance: &Instance, glfw: &Glfw) -> Result<vk::Physical
   Ok(device)
 }
 
-fn device_is_suitable(instance: &Instance, glfw: &Glfw, device: vk::PhysicalDevice) -> bool {
+fn device_is_suitable(instance: &Instance, glfw: &Glfw, config: &DeviceConfig, device: vk::PhysicalDevice) -> bool {
   // TODO: check whether the buffer_device_address feature is present
   let device_properties = unsafe { instance.get_physical_device_properties(device) };
   let device_extensions = unsafe { instance.enumerate_device_extension_properties(device).expect("Could not get device extension properties!") };
@@ -317,7 +439,7 @@ fn device_is_suitable(instance: &Instance, glfw: &Glfw, device: vk::PhysicalDevi
 
   let device_extensions: Vec<CString> = device_extensions.iter().map(|extension| vk_to_string(&extension.extension_name).to_owned()).collect();
 
-  let required_extensions = get_required_extensions();
+  let required_extensions = get_required_extensions(config);
 
   trace!("Checking if device has all the required extensions...");
   trace!("Device extensions: {:?}", device_extensions);
@@ -326,7 +448,8 @@ fn device_is_suitable(instance: &Instance, glfw: &Glfw, device: vk::PhysicalDevi
   }
 
   trace!("Checking if device is a discrete GPU...");
-  if device_properties.device_type != vk::PhysicalDeviceType::DISCRETE_GPU {
+  let integrated_allowed = config.allow_integrated_gpu && device_properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU;
+  if device_properties.device_type != vk::PhysicalDeviceType::DISCRETE_GPU && !integrated_allowed {
     return false;
   };
 
@@ -336,19 +459,26 @@ fn device_is_suitable(instance: &Instance, glfw: &Glfw, device: vk::PhysicalDevi
   }
 
   trace!("Checking if device supports a graphics queue...");
-  let graphics_queue = find_graphics_queue_family(instance, device);
-  if graphics_queue.is_none() {
+  if find_graphics_queue_family(instance, device).is_none() {
     return false;
   }
 
-  trace!("Checking if graphics queue also supports presentation...");
-  if !glfw.get_physical_device_presentation_support_raw(instance.handle().as_raw() as usize, device.as_raw() as usize, graphics_queue.unwrap()) {
+  trace!("Checking if device supports presentation...");
+  if find_present_queue_family(instance, glfw, device).is_none() {
     return false;
   }
 
   true
 }
 
+fn device_supports_extension(instance: &Instance, device: vk::PhysicalDevice, extension: &CString) -> bool {
+  let Ok(device_extensions) = (unsafe { instance.enumerate_device_extension_properties(device) }) else {
+    return false;
+  };
+
+  device_extensions.iter().any(|device_extension| vk_to_string(&device_extension.extension_name) == extension.as_c_str())
+}
+
 pub(crate) fn find_graphics_queue_family(instance: &Instance, device: vk::PhysicalDevice) -> Option<u32> {
   let families = unsafe { instance.get_physical_device_queue_family_properties(device) };
 
@@ -361,6 +491,25 @@ pub(crate) fn find_graphics_queue_family(instance: &Instance, device: vk::Physic
   None
 }
 
+/// Vulkan has no queue flag for presentation, so the support has to be queried from the windowing system for every family.
+/// The graphics family is preferred, presenting from it doesn't need a queue ownership transfer of the swapchain images.
+pub(crate) fn find_present_queue_family(instance: &Instance, glfw: &Glfw, device: vk::PhysicalDevice) -> Option<u32> {
+  let families = unsafe { instance.get_physical_device_queue_family_properties(device) };
+  let supports_presentation = |index: u32| glfw.get_physical_device_presentation_support_raw(instance.handle().as_raw() as usize, device.as_raw() as usize, index);
+
+  if let Some(graphics_family) = find_graphics_queue_family(instance, device).filter(|index| supports_presentation(*index)) {
+    return Some(graphics_family);
+  }
+
+  for (index, queue_family) in families.into_iter().enumerate() {
+    if queue_family.queue_count > 0 && supports_presentation(index as u32) {
+      return Some(index as u32);
+    }
+  }
+
+  None
+}
+
 pub(crate) fn find_transfer_queue_family(instance: &Instance, device: vk::PhysicalDevice) -> Option<u32> {
   let families = unsafe { instance.get_physical_device_queue_family_properties(device) };
 
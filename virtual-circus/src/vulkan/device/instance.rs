@@ -137,6 +137,11 @@ impl Instance {
   pub(super) fn get_surface_loader(&self) -> Surface {
     Surface::new(&self.entry, &self.instance)
   }
+
+  #[cfg(debug_assertions)]
+  pub(super) fn debug_utils_loader(&self) -> &DebugUtils {
+    &self.debug_utils_loader
+  }
 }
 
 impl Drop for Instance {
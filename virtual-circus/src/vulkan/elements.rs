@@ -1,19 +1,23 @@
 mod command_pool;
+mod compute_pipeline;
 mod fence;
 mod image_view;
 mod pipeline;
 mod pipeline_layout;
+mod query_pool;
 mod sampler;
 mod semaphore;
 mod surface;
 mod swapchain;
 
 pub(crate) use command_pool::CommandPool;
+pub(crate) use compute_pipeline::ComputePipeline;
 pub(crate) use fence::Fence;
 pub(crate) use image_view::ImageView;
-pub(crate) use pipeline::Pipeline;
-pub(crate) use pipeline_layout::PipelineLayout;
+pub(crate) use pipeline::{FullscreenPipeline, Pipeline, PipelineSettings};
+pub(crate) use pipeline_layout::{PipelineLayout, PipelineLayoutCache};
+pub(crate) use query_pool::{PipelineStatistics, StatisticsQueryPool};
 pub(crate) use sampler::Sampler;
-pub(crate) use semaphore::Semaphore;
+pub(crate) use semaphore::{Semaphore, TimelineSemaphore};
 pub(crate) use surface::Surface;
 pub(crate) use swapchain::Swapchain;
@@ -15,15 +15,32 @@ pub(crate) struct CommandPool {
 
 impl CommandPool {
   pub(crate) fn new(device: &Arc<Device>, queue_family_index: u32, count: u32) -> Result<Self> {
+    Self::create(device, queue_family_index, count, vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+  }
+
+  /// Creates a pool whose command buffers can only be reset all at once through `reset`, meant for buffers re-recorded every frame.
+  pub(crate) fn new_transient(device: &Arc<Device>, queue_family_index: u32, count: u32) -> Result<Self> {
+    Self::create(device, queue_family_index, count, vk::CommandPoolCreateFlags::TRANSIENT)
+  }
+
+  fn create(device: &Arc<Device>, queue_family_index: u32, count: u32, flags: vk::CommandPoolCreateFlags) -> Result<Self> {
     debug!("Creating command pool.");
     let create_info = vk::CommandPoolCreateInfo {
       queue_family_index,
-      flags: vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+      flags,
       ..Default::default()
     };
 
     let command_pool = unsafe { device.create_command_pool(&create_info, None)? };
 
+    let queue_family_name = match queue_family_index {
+      index if index == device.graphics_queue_family_index() => "graphics",
+      index if index == device.transfer_queue_family_index() => "transfer",
+      index if index == device.present_queue_family_index() => "present",
+      _ => "unknown",
+    };
+    device.set_debug_name(command_pool, &format!("Command pool ({} queue family)", queue_family_name));
+
     let command_buffers_create_info = vk::CommandBufferAllocateInfo {
       command_pool,
       level: vk::CommandBufferLevel::PRIMARY,
@@ -40,6 +57,18 @@ impl CommandPool {
     })
   }
 
+  /// Resets every command buffer of the pool at once, none of them can be in use by the GPU anymore.
+  /// Releasing the resources hands the pool's memory back to the driver instead of keeping it around for the next recording.
+  pub(crate) fn reset(&self, release_resources: bool) -> Result<()> {
+    let flags = match release_resources {
+      true => vk::CommandPoolResetFlags::RELEASE_RESOURCES,
+      false => vk::CommandPoolResetFlags::empty(),
+    };
+
+    unsafe { self.device.reset_command_pool(self.command_pool, flags)? };
+    Ok(())
+  }
+
   #[allow(dead_code)]
   pub(crate) fn get_device(&self) -> Arc<Device> {
     self.device.clone()
@@ -0,0 +1,71 @@
+use super::super::Device;
+use super::pipeline::read_shader;
+use crate::utils::tools::Result;
+
+use ash::vk;
+use log::debug;
+
+use std::ffi::CString;
+use std::sync::Arc;
+
+pub(crate) struct ComputePipeline {
+  device: Arc<Device>,
+  pipeline: vk::Pipeline,
+}
+
+impl ComputePipeline {
+  pub(crate) fn new(device: &Arc<Device>, pipeline_layout: &vk::PipelineLayout, shader_path: &str) -> Result<Self> {
+    debug!("Creating compute pipeline.");
+    let compute_shader = unsafe { read_shader(shader_path, device)? };
+
+    let main_function_name = CString::new("main").unwrap();
+
+    let shader_stage_info = vk::PipelineShaderStageCreateInfo {
+      module: compute_shader,
+      stage: vk::ShaderStageFlags::COMPUTE,
+      p_name: main_function_name.as_ptr(),
+      ..Default::default()
+    };
+
+    let pipeline_create_info = vk::ComputePipelineCreateInfo {
+      flags: vk::PipelineCreateFlags::DESCRIPTOR_BUFFER_EXT,
+      stage: shader_stage_info,
+      layout: *pipeline_layout,
+      ..Default::default()
+    };
+
+    let pipeline = unsafe {
+      match device.create_compute_pipelines(vk::PipelineCache::null(), &[pipeline_create_info], None) {
+        Ok(pipelines) => Ok(pipelines[0]),
+        Err((pipelines, err)) => err.result_with_success(pipelines[0]),
+      }?
+    };
+
+    unsafe { device.destroy_shader_module(compute_shader, None) };
+
+    device.set_debug_name(pipeline, &format!("Compute pipeline {}", shader_path));
+
+    debug!("Successfully created compute pipeline!");
+    Ok(Self { device: device.clone(), pipeline })
+  }
+
+  #[allow(dead_code)]
+  pub(crate) fn get_device(&self) -> Arc<Device> {
+    self.device.clone()
+  }
+}
+
+impl Drop for ComputePipeline {
+  fn drop(&mut self) {
+    debug!("Destroying compute pipeline.");
+    unsafe { self.device.destroy_pipeline(self.pipeline, None) };
+  }
+}
+
+impl std::ops::Deref for ComputePipeline {
+  type Target = vk::Pipeline;
+
+  fn deref(&self) -> &Self::Target {
+    &self.pipeline
+  }
+}
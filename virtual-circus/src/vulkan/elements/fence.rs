@@ -22,6 +22,34 @@ impl Fence {
     Ok(Self { device: device.clone(), fence })
   }
 
+  pub(crate) fn wait(&self) -> Result<()> {
+    unsafe { self.device.wait_for_fences(&[self.fence], true, u64::MAX)? };
+    Ok(())
+  }
+
+  pub(crate) fn reset(&self) -> Result<()> {
+    unsafe { self.device.reset_fences(&[self.fence])? };
+    Ok(())
+  }
+
+  pub(crate) fn wait_and_reset(&self) -> Result<()> {
+    self.wait()?;
+    self.reset()
+  }
+
+  /// Returns `false` without resetting the fence if it didn't get signaled within the timeout (in nanoseconds).
+  #[allow(dead_code)]
+  pub(crate) fn wait_and_reset_with_timeout(&self, timeout: u64) -> Result<bool> {
+    match unsafe { self.device.wait_for_fences(&[self.fence], true, timeout) } {
+      Ok(_) => (),
+      Err(vk::Result::TIMEOUT) => return Ok(false),
+      Err(e) => return Err(e.into()),
+    };
+
+    self.reset()?;
+    Ok(true)
+  }
+
   #[allow(dead_code)]
   pub(crate) fn get_device(&self) -> Arc<Device> {
     self.device.clone()
@@ -13,27 +13,57 @@ pub(crate) struct ImageView {
 
 impl ImageView {
   pub(crate) fn new(device: &Arc<Device>, image: &vk::Image, format: &vk::Format, aspect_mask: vk::ImageAspectFlags) -> Result<Self> {
-    let components = vk::ComponentMapping {
-      r: vk::ComponentSwizzle::IDENTITY,
-      g: vk::ComponentSwizzle::IDENTITY,
-      b: vk::ComponentSwizzle::IDENTITY,
-      a: vk::ComponentSwizzle::IDENTITY,
-    };
+    Self::with_mip_levels(device, image, format, aspect_mask, 1)
+  }
 
+  /// View of the first `level_count` mip levels, so sampling can pick between all of them.
+  pub(crate) fn with_mip_levels(device: &Arc<Device>, image: &vk::Image, format: &vk::Format, aspect_mask: vk::ImageAspectFlags, level_count: u32) -> Result<Self> {
     let subresource_range = vk::ImageSubresourceRange {
       aspect_mask,
       base_mip_level: 0,
-      level_count: 1,
+      level_count,
       base_array_layer: 0,
       layer_count: 1,
     };
 
+    Self::from_subresource_range(device, image, format, vk::ImageViewType::TYPE_2D, subresource_range)
+  }
+
+  /// View of `layer_count` array layers starting at `base_array_layer`, like a single face or all six faces of a cubemap.
+  pub(crate) fn with_layers(
+    device: &Arc<Device>,
+    image: &vk::Image,
+    format: &vk::Format,
+    aspect_mask: vk::ImageAspectFlags,
+    view_type: vk::ImageViewType,
+    base_array_layer: u32,
+    layer_count: u32,
+  ) -> Result<Self> {
+    let subresource_range = vk::ImageSubresourceRange {
+      aspect_mask,
+      base_mip_level: 0,
+      level_count: 1,
+      base_array_layer,
+      layer_count,
+    };
+
+    Self::from_subresource_range(device, image, format, view_type, subresource_range)
+  }
+
+  fn from_subresource_range(device: &Arc<Device>, image: &vk::Image, format: &vk::Format, view_type: vk::ImageViewType, subresource_range: vk::ImageSubresourceRange) -> Result<Self> {
+    let components = vk::ComponentMapping {
+      r: vk::ComponentSwizzle::IDENTITY,
+      g: vk::ComponentSwizzle::IDENTITY,
+      b: vk::ComponentSwizzle::IDENTITY,
+      a: vk::ComponentSwizzle::IDENTITY,
+    };
+
     let create_info = vk::ImageViewCreateInfo {
       format: *format,
       components,
       subresource_range,
       image: *image,
-      view_type: vk::ImageViewType::TYPE_2D,
+      view_type,
       ..Default::default()
     };
 
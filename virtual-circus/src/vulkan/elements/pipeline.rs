@@ -1,23 +1,154 @@
 use super::super::Device;
 use crate::utils::constants::*;
-use crate::utils::tools::Result;
+use crate::utils::tools::{EngineError, Result};
 
 use ash::vk;
-use log::debug;
+use asset_lib as ast;
+use log::{debug, warn};
 
 use std::ffi::CString;
+use std::io::Cursor;
 use std::sync::Arc;
 
+/// Where the SPIR-V of a shader stage comes from.
+pub(crate) enum ShaderSource {
+  /// Compiled shader file, relative to the executable.
+  File(&'static str),
+  /// SPIR-V embedded in a pipeline asset.
+  Embedded(Vec<u8>),
+}
+
+impl ShaderSource {
+  fn label(&self) -> &str {
+    match self {
+      ShaderSource::File(path) => path,
+      ShaderSource::Embedded(_) => "embedded shader",
+    }
+  }
+}
+
+pub(crate) struct PipelineSettings {
+  pub(crate) vertex_shader: ShaderSource,
+  /// Pipelines without a fragment shader only write depth and have no color attachment.
+  pub(crate) fragment_shader: Option<ShaderSource>,
+  /// Mesh pipelines get their vertex layout from the mesh being drawn, see `RenderingContext::draw_mesh`.
+  /// Pipelines without mesh vertex input generate their vertices from the vertex index in the shader.
+  pub(crate) mesh_vertex_input: bool,
+  pub(crate) alpha_blending: bool,
+  pub(crate) depth_test: bool,
+  pub(crate) depth_write: bool,
+  pub(crate) cull_mode: vk::CullModeFlags,
+  pub(crate) polygon_mode: vk::PolygonMode,
+}
+
+impl PipelineSettings {
+  /// Settings of the pipeline asset at `path`, relative to the working directory like every other asset.
+  pub(crate) fn load(path: &str) -> Result<Self> {
+    let pipeline = ast::Pipeline::load_pipeline(ast::AssetFile::load_from_file(path)?)?;
+    Ok(Self::from(&pipeline))
+  }
+
+  /// Pipeline the scene meshes get drawn with, falls back to the default shaders while its asset hasn't been converted.
+  pub(crate) fn mesh() -> Self {
+    match Self::load(MESH_PIPELINE_ASSET) {
+      Ok(settings) => settings,
+      Err(e) => {
+        warn!("Failed to load mesh pipeline {}, using the default shaders: {}", MESH_PIPELINE_ASSET, e);
+        Self::default()
+      }
+    }
+  }
+
+  /// Depth only variant of the mesh pipeline for rendering shadow maps.
+  pub(crate) fn shadow() -> Self {
+    Self {
+      fragment_shader: None,
+      ..Self::mesh()
+    }
+  }
+
+  pub(crate) fn particles() -> Self {
+    Self {
+      vertex_shader: ShaderSource::File("shaders/particles.vert.spv"),
+      fragment_shader: Some(ShaderSource::File("shaders/particles.frag.spv")),
+      mesh_vertex_input: false,
+      alpha_blending: true,
+      depth_write: false,
+      ..Default::default()
+    }
+  }
+
+  /// Single triangle covering the whole screen, see `RenderingContext::draw_fullscreen_quad`.
+  pub(crate) fn fullscreen(fragment_shader: &'static str, alpha_blending: bool) -> Self {
+    Self {
+      vertex_shader: ShaderSource::File(FULLSCREEN_VERTEX_SHADER),
+      fragment_shader: Some(ShaderSource::File(fragment_shader)),
+      mesh_vertex_input: false,
+      alpha_blending,
+      depth_test: false,
+      depth_write: false,
+      ..Default::default()
+    }
+  }
+}
+
+impl Default for PipelineSettings {
+  fn default() -> Self {
+    Self {
+      vertex_shader: ShaderSource::File("shaders/vertexShader.vert.spv"),
+      fragment_shader: Some(ShaderSource::File("shaders/fragmentShader.frag.spv")),
+      mesh_vertex_input: true,
+      alpha_blending: false,
+      depth_test: true,
+      depth_write: true,
+      cull_mode: vk::CullModeFlags::NONE,
+      polygon_mode: vk::PolygonMode::FILL,
+    }
+  }
+}
+
+impl From<&ast::Pipeline> for PipelineSettings {
+  fn from(pipeline: &ast::Pipeline) -> Self {
+    let cull_mode = match pipeline.cull_mode {
+      ast::CullMode::None => vk::CullModeFlags::NONE,
+      ast::CullMode::Front => vk::CullModeFlags::FRONT,
+      ast::CullMode::Back => vk::CullModeFlags::BACK,
+    };
+
+    let polygon_mode = match pipeline.polygon_mode {
+      ast::PolygonMode::Fill => vk::PolygonMode::FILL,
+      ast::PolygonMode::Line => vk::PolygonMode::LINE,
+      ast::PolygonMode::Point => vk::PolygonMode::POINT,
+    };
+
+    Self {
+      vertex_shader: ShaderSource::Embedded(pipeline.vertex_shader.clone()),
+      fragment_shader: Some(ShaderSource::Embedded(pipeline.fragment_shader.clone())),
+      alpha_blending: pipeline.blending.test,
+      depth_test: pipeline.depth_test,
+      depth_write: pipeline.depth_write,
+      cull_mode,
+      polygon_mode,
+      ..Default::default()
+    }
+  }
+}
+
+const FULLSCREEN_VERTEX_SHADER: &str = "shaders/fullscreen.vert.spv";
+
 pub(crate) struct Pipeline {
   device: Arc<Device>,
   pipeline: vk::Pipeline,
 }
 
 impl Pipeline {
-  pub(crate) fn new(device: &Arc<Device>, pipeline_layout: &vk::PipelineLayout) -> Result<Self> {
+  pub(crate) fn new(device: &Arc<Device>, pipeline_layout: &vk::PipelineLayout, settings: &PipelineSettings) -> Result<Self> {
     debug!("Creating graphics pipeline.");
-    let vertex_shader = unsafe { read_shader("shaders/vertexShader.vert.spv", device)? };
-    let fragment_shader = unsafe { read_shader("shaders/fragmentShader.frag.spv", device)? };
+    let vertex_shader = unsafe { create_shader_module(&settings.vertex_shader, device)? };
+    let fragment_shader = match &settings.fragment_shader {
+      Some(fragment_shader) => Some(unsafe { create_shader_module(fragment_shader, device)? }),
+      None => None,
+    };
 
     let main_function_name = CString::new("main").unwrap();
 
@@ -28,49 +159,21 @@ impl Pipeline {
       ..Default::default()
     };
 
-    let fragment_shader_stage_info = vk::PipelineShaderStageCreateInfo {
-      module: fragment_shader,
-      stage: vk::ShaderStageFlags::FRAGMENT,
-      p_name: main_function_name.as_ptr(),
-      ..Default::default()
-    };
+    let mut shader_stages = vec![vertex_shader_stage_info];
+    if let Some(fragment_shader) = fragment_shader {
+      shader_stages.push(vk::PipelineShaderStageCreateInfo {
+        module: fragment_shader,
+        stage: vk::ShaderStageFlags::FRAGMENT,
+        p_name: main_function_name.as_ptr(),
+        ..Default::default()
+      });
+    }
 
-    let shader_stages = [vertex_shader_stage_info, fragment_shader_stage_info];
-
-    let vertex_binding_desciptions = [vk::VertexInputBindingDescription {
-      binding: 0,
-      stride: 40,
-      input_rate: vk::VertexInputRate::VERTEX,
-    }];
-
-    let vertex_attribute_descriptions = [
-      vk::VertexInputAttributeDescription {
-        location: 0,
-        binding: 0,
-        format: vk::Format::R32G32B32_SFLOAT,
-        offset: 0,
-      },
-      vk::VertexInputAttributeDescription {
-        location: 1,
-        binding: 0,
-        format: vk::Format::R32G32B32_SFLOAT,
-        offset: 12,
-      },
-      vk::VertexInputAttributeDescription {
-        location: 2,
-        binding: 0,
-        format: vk::Format::R32G32B32A32_SFLOAT,
-        offset: 24,
-      },
-    ];
-
-    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo {
-      vertex_binding_description_count: vertex_binding_desciptions.len() as u32,
-      p_vertex_binding_descriptions: vertex_binding_desciptions.as_ptr(),
-      vertex_attribute_description_count: vertex_attribute_descriptions.len() as u32,
-      p_vertex_attribute_descriptions: vertex_attribute_descriptions.as_ptr(),
-      ..Default::default()
-    };
+    // Only the fragment shader writes color, depth only pipelines render without a color attachment
+    let color_attachment_count = fragment_shader.is_some() as u32;
+
+    // Ignored for mesh pipelines, their vertex input is set dynamically
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default();
 
     let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
       primitive_restart_enable: vk::FALSE,
@@ -78,7 +181,15 @@ impl Pipeline {
       ..Default::default()
     };
 
-    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_states = match settings.mesh_vertex_input {
+      true => vec![
+        vk::DynamicState::VIEWPORT,
+        vk::DynamicState::SCISSOR,
+        vk::DynamicState::VERTEX_INPUT_EXT,
+        vk::DynamicState::PRIMITIVE_TOPOLOGY,
+      ],
+      false => vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+    };
     let pipeline_dynamic_state = vk::PipelineDynamicStateCreateInfo {
       dynamic_state_count: dynamic_states.len() as u32,
       p_dynamic_states: dynamic_states.as_ptr(),
@@ -92,8 +203,8 @@ impl Pipeline {
     };
 
     let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo {
-      depth_test_enable: vk::TRUE,
-      depth_write_enable: vk::TRUE,
+      depth_test_enable: settings.depth_test.into(),
+      depth_write_enable: settings.depth_write.into(),
       depth_compare_op: vk::CompareOp::LESS,
       depth_bounds_test_enable: vk::FALSE,
       stencil_test_enable: vk::FALSE,
@@ -104,9 +215,9 @@ impl Pipeline {
       depth_clamp_enable: vk::FALSE,
       depth_bias_enable: vk::FALSE,
       rasterizer_discard_enable: vk::FALSE,
-      polygon_mode: vk::PolygonMode::FILL,
+      polygon_mode: settings.polygon_mode,
       line_width: 1.0,
-      cull_mode: vk::CullModeFlags::NONE,
+      cull_mode: settings.cull_mode,
       front_face: vk::FrontFace::CLOCKWISE,
       ..Default::default()
     };
@@ -118,7 +229,7 @@ impl Pipeline {
     };
 
     let color_blend_attachment = vk::PipelineColorBlendAttachmentState {
-      blend_enable: vk::FALSE,
+      blend_enable: settings.alpha_blending.into(),
       color_write_mask: vk::ColorComponentFlags::RGBA,
       src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
       dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
@@ -132,12 +243,12 @@ impl Pipeline {
       logic_op_enable: vk::FALSE,
       logic_op: vk::LogicOp::COPY,
       p_attachments: &color_blend_attachment,
-      attachment_count: 1,
+      attachment_count: color_attachment_count,
       ..Default::default()
     };
 
     let mut rendering_info = vk::PipelineRenderingCreateInfo {
-      color_attachment_count: 1,
+      color_attachment_count,
       p_color_attachment_formats: [vk::Format::R8G8B8A8_SRGB].as_ptr(),
       depth_attachment_format: DEPTH_FORMAT,
       stencil_attachment_format: vk::Format::UNDEFINED,
@@ -168,9 +279,14 @@ impl Pipeline {
 
     unsafe {
       device.destroy_shader_module(vertex_shader, None);
-      device.destroy_shader_module(fragment_shader, None);
+      if let Some(fragment_shader) = fragment_shader {
+        device.destroy_shader_module(fragment_shader, None);
+      }
     }
 
+    let fragment_label = settings.fragment_shader.as_ref().map_or("depth only", |fragment_shader| fragment_shader.label());
+    device.set_debug_name(pipeline, &format!("Graphics pipeline {} + {}", settings.vertex_shader.label(), fragment_label));
+
     debug!("Successfully created graphics pipeline!");
     Ok(Self { device: device.clone(), pipeline })
   }
@@ -181,13 +297,26 @@ impl Pipeline {
   }
 }
 
-unsafe fn read_shader(path: &str, device: &Device) -> Result<vk::ShaderModule> {
+pub(super) unsafe fn read_shader(path: &str, device: &Device) -> Result<vk::ShaderModule> {
   debug!("Loading shader: {}", path);
   let mut exe = std::env::current_exe().map_err(|_| vk::Result::ERROR_INITIALIZATION_FAILED)?;
   exe.pop();
   let mut file = std::fs::File::open(exe.join(path)).map_err(|_| vk::Result::ERROR_INITIALIZATION_FAILED)?;
   let code = ash::util::read_spv(&mut file).map_err(|_| vk::Result::ERROR_INITIALIZATION_FAILED)?;
+  create_shader_module_from_code(&code, device)
+}
 
+unsafe fn create_shader_module(source: &ShaderSource, device: &Device) -> Result<vk::ShaderModule> {
+  match source {
+    ShaderSource::File(path) => read_shader(path, device),
+    ShaderSource::Embedded(code) => {
+      let code = ash::util::read_spv(&mut Cursor::new(code.as_slice())).map_err(|_| EngineError::CreationError("pipeline contains invalid spirv code"))?;
+      create_shader_module_from_code(&code, device)
+    }
+  }
+}
+
+unsafe fn create_shader_module_from_code(code: &[u32], device: &Device) -> Result<vk::ShaderModule> {
   let create_info = vk::ShaderModuleCreateInfo {
     code_size: code.len() * 4,
     p_code: code.as_ptr(),
@@ -213,3 +342,25 @@ impl std::ops::Deref for Pipeline {
     &self.pipeline
   }
 }
+
+//-----------------------------Fullscreen Pipeline-----------------------------
+
+/// Pipeline for post process passes, its vertex shader covers the whole screen so only the fragment shader changes between passes.
+pub(crate) struct FullscreenPipeline {
+  pipeline: Pipeline,
+}
+
+impl FullscreenPipeline {
+  pub(crate) fn new(device: &Arc<Device>, pipeline_layout: &vk::PipelineLayout, fragment_shader: &'static str, alpha_blending: bool) -> Result<Self> {
+    let pipeline = Pipeline::new(device, pipeline_layout, &PipelineSettings::fullscreen(fragment_shader, alpha_blending))?;
+    Ok(Self { pipeline })
+  }
+}
+
+impl std::ops::Deref for FullscreenPipeline {
+  type Target = vk::Pipeline;
+
+  fn deref(&self) -> &Self::Target {
+    &self.pipeline
+  }
+}
@@ -5,7 +5,10 @@ use crate::utils::tools::Result;
 use ash::vk;
 use log::debug;
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+//-----------------------------------Pipeline Layout-----------------------------------------------
 
 pub(crate) struct PipelineLayout {
   device: Arc<Device>,
@@ -14,13 +17,15 @@ pub(crate) struct PipelineLayout {
 
 impl PipelineLayout {
   pub(crate) fn new(device: &Arc<Device>, descriptor_sets: &[vk::DescriptorSetLayout]) -> Result<Self> {
+    Self::with_push_constant_range(device, descriptor_sets, Self::node_push_constant_range())
+  }
+
+  pub(crate) fn with_push_constant_range(device: &Arc<Device>, descriptor_sets: &[vk::DescriptorSetLayout], range: vk::PushConstantRange) -> Result<Self> {
+    Self::with_push_constant_ranges(device, descriptor_sets, &[range])
+  }
+
+  pub(crate) fn with_push_constant_ranges(device: &Arc<Device>, descriptor_sets: &[vk::DescriptorSetLayout], push_constants: &[vk::PushConstantRange]) -> Result<Self> {
     debug!("Creating pipeline layout.");
-    let range = vk::PushConstantRange {
-      offset: 0,
-      size: std::mem::size_of::<PushConstant>() as u32,
-      stage_flags: vk::ShaderStageFlags::VERTEX,
-    };
-    let push_constants = [range];
 
     let pipeline_layout = vk::PipelineLayoutCreateInfo {
       set_layout_count: descriptor_sets.len() as u32,
@@ -35,6 +40,15 @@ impl PipelineLayout {
     Ok(Self { device: device.clone(), layout })
   }
 
+  /// Push constant range of the pipelines drawing scene nodes, which only push the index of the node.
+  pub(crate) fn node_push_constant_range() -> vk::PushConstantRange {
+    vk::PushConstantRange {
+      offset: 0,
+      size: std::mem::size_of::<PushConstant>() as u32,
+      stage_flags: vk::ShaderStageFlags::VERTEX,
+    }
+  }
+
   #[allow(dead_code)]
   pub(crate) fn get_device(&self) -> Arc<Device> {
     self.device.clone()
@@ -55,3 +69,50 @@ impl std::ops::Deref for PipelineLayout {
     &self.layout
   }
 }
+
+//-----------------------------------Pipeline Layout Cache-----------------------------------------------
+
+/// Identifies a pipeline layout by its descriptor set layouts and push constant ranges, in the order they were given.
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub(crate) struct PipelineLayoutKey {
+  descriptor_sets: Vec<vk::DescriptorSetLayout>,
+  // vk::PushConstantRange doesn't implement Hash, so its fields are stored on their own
+  push_constants: Vec<(vk::ShaderStageFlags, u32, u32)>,
+}
+
+impl PipelineLayoutKey {
+  pub(crate) fn new(descriptor_sets: &[vk::DescriptorSetLayout], push_constants: &[vk::PushConstantRange]) -> Self {
+    Self {
+      descriptor_sets: descriptor_sets.to_vec(),
+      push_constants: push_constants.iter().map(|range| (range.stage_flags, range.offset, range.size)).collect(),
+    }
+  }
+}
+
+/// Shares pipeline layouts between everything using the same descriptor set layouts and push constants.
+/// Layouts stay alive as long as the cache does, even once nothing uses them anymore.
+#[derive(Default)]
+pub(crate) struct PipelineLayoutCache {
+  // Windows only get a shared reference to Vulkan, so the cache has to be filled through one
+  layouts: Mutex<HashMap<PipelineLayoutKey, Arc<PipelineLayout>>>,
+}
+
+impl PipelineLayoutCache {
+  pub(crate) fn new() -> Self {
+    Self::default()
+  }
+
+  pub(crate) fn get_or_create(&self, device: &Arc<Device>, descriptor_sets: &[vk::DescriptorSetLayout], push_constants: &[vk::PushConstantRange]) -> Result<Arc<PipelineLayout>> {
+    let key = PipelineLayoutKey::new(descriptor_sets, push_constants);
+    let mut layouts = self.layouts.lock().unwrap();
+
+    if let Some(layout) = layouts.get(&key) {
+      debug!("Reusing cached pipeline layout.");
+      return Ok(layout.clone());
+    }
+
+    let layout = Arc::new(PipelineLayout::with_push_constant_ranges(device, descriptor_sets, push_constants)?);
+    layouts.insert(key, layout.clone());
+    Ok(layout)
+  }
+}
@@ -0,0 +1,93 @@
+use super::super::Device;
+use crate::utils::tools::Result;
+
+use ash::vk;
+use log::debug;
+
+use std::cell::Cell;
+use std::sync::Arc;
+
+/// Results are written in the order of the statistic flag bits, so the fields have to follow that order.
+#[repr(C)]
+#[derive(Default, Clone, Copy, Debug)]
+pub(crate) struct PipelineStatistics {
+  pub(crate) primitives_generated: u64,
+  pub(crate) vertex_invocations: u64,
+  pub(crate) fragment_invocations: u64,
+}
+
+pub(crate) struct StatisticsQueryPool {
+  device: Arc<Device>,
+  query_pool: vk::QueryPool,
+  has_results: Cell<bool>,
+}
+
+impl StatisticsQueryPool {
+  pub(crate) fn new(device: &Arc<Device>) -> Result<Self> {
+    debug!("Creating statistics query pool.");
+    let create_info = vk::QueryPoolCreateInfo {
+      query_type: vk::QueryType::PIPELINE_STATISTICS,
+      query_count: 1,
+      pipeline_statistics: vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES
+        | vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS
+        | vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+      ..Default::default()
+    };
+
+    let query_pool = unsafe { device.create_query_pool(&create_info, None)? };
+    debug!("Successfully created statistics query pool!");
+
+    Ok(Self {
+      device: device.clone(),
+      query_pool,
+      has_results: Cell::new(false),
+    })
+  }
+
+  /// Resets and begins the query, has to be recorded outside of a rendering pass.
+  pub(crate) fn cmd_begin(&self, command_buffer: &vk::CommandBuffer) {
+    unsafe {
+      self.device.cmd_reset_query_pool(*command_buffer, self.query_pool, 0, 1);
+      self.device.cmd_begin_query(*command_buffer, self.query_pool, 0, vk::QueryControlFlags::empty());
+    }
+    self.has_results.set(true);
+  }
+
+  pub(crate) fn cmd_end(&self, command_buffer: &vk::CommandBuffer) {
+    unsafe { self.device.cmd_end_query(*command_buffer, self.query_pool, 0) };
+  }
+
+  /// Returns the results of the last finished query without waiting for the GPU.
+  pub(crate) fn get_results(&self) -> Result<Option<PipelineStatistics>> {
+    if !self.has_results.get() {
+      return Ok(None);
+    }
+
+    let mut results = [PipelineStatistics::default()];
+    match unsafe { self.device.get_query_pool_results(self.query_pool, 0, 1, &mut results, vk::QueryResultFlags::TYPE_64) } {
+      Ok(_) => Ok(Some(results[0])),
+      Err(vk::Result::NOT_READY) => Ok(None),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  #[allow(dead_code)]
+  pub(crate) fn get_device(&self) -> Arc<Device> {
+    self.device.clone()
+  }
+}
+
+impl Drop for StatisticsQueryPool {
+  fn drop(&mut self) {
+    debug!("Destroying statistics query pool.");
+    unsafe { self.device.destroy_query_pool(self.query_pool, None) };
+  }
+}
+
+impl std::ops::Deref for StatisticsQueryPool {
+  type Target = vk::QueryPool;
+
+  fn deref(&self) -> &Self::Target {
+    &self.query_pool
+  }
+}
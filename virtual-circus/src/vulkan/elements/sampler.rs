@@ -34,7 +34,8 @@ impl Sampler {
       compare_op: vk::CompareOp::ALWAYS,
       mip_lod_bias: 0.0,
       min_lod: 0.0,
-      max_lod: 0.0,
+      // Samplers are shared between images, so the mip levels are only limited by the image view
+      max_lod: vk::LOD_CLAMP_NONE,
       border_color: vk::BorderColor::FLOAT_OPAQUE_BLACK,
       unnormalized_coordinates: vk::FALSE,
       ..Default::default()
@@ -46,6 +47,33 @@ impl Sampler {
     Ok(Self { device: device.clone(), sampler })
   }
 
+  /// Creates a comparison sampler for shadow maps, sampling it through a `sampler2DShadow` gives hardware PCF.
+  pub(crate) fn new_shadow(device: &Arc<Device>, filter: vk::Filter, address_mode: vk::SamplerAddressMode) -> Result<Self> {
+    debug!("Creating shadow sampler.");
+    let create_info = vk::SamplerCreateInfo {
+      mag_filter: filter,
+      min_filter: filter,
+      mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+      address_mode_u: address_mode,
+      address_mode_v: address_mode,
+      address_mode_w: address_mode,
+      anisotropy_enable: vk::FALSE,
+      compare_enable: vk::TRUE,
+      compare_op: vk::CompareOp::LESS_OR_EQUAL,
+      mip_lod_bias: 0.0,
+      min_lod: 0.0,
+      max_lod: 0.0,
+      border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
+      unnormalized_coordinates: vk::FALSE,
+      ..Default::default()
+    };
+
+    let sampler = unsafe { device.create_sampler(&create_info, None)? };
+    debug!("Successfully created shadow sampler!");
+
+    Ok(Self { device: device.clone(), sampler })
+  }
+
   #[allow(dead_code)]
   pub(crate) fn get_device(&self) -> Arc<Device> {
     self.device.clone()
@@ -42,3 +42,70 @@ impl std::ops::Deref for Semaphore {
     &self.semaphore
   }
 }
+
+//-----------------------------------Timeline Semaphore-----------------------------------------------
+
+pub(crate) struct TimelineSemaphore {
+  device: Arc<Device>,
+  semaphore: vk::Semaphore,
+}
+
+impl TimelineSemaphore {
+  pub(crate) fn new(device: &Arc<Device>, initial_value: u64) -> Result<Self> {
+    debug!("Creating timeline semaphore.");
+
+    let mut type_create_info = vk::SemaphoreTypeCreateInfo {
+      semaphore_type: vk::SemaphoreType::TIMELINE,
+      initial_value,
+      ..Default::default()
+    };
+    let create_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_create_info);
+    let semaphore = unsafe { device.create_semaphore(&create_info, None)? };
+
+    debug!("Timeline semaphore successfully created!");
+    Ok(Self { device: device.clone(), semaphore })
+  }
+
+  /// Signals the semaphore from the host, the value has to be greater than the current one.
+  #[allow(dead_code)]
+  pub(crate) fn signal_value(&self, value: u64) -> Result<()> {
+    let signal_info = vk::SemaphoreSignalInfo {
+      semaphore: self.semaphore,
+      value,
+      ..Default::default()
+    };
+
+    unsafe { self.device.signal_semaphore(&signal_info)? };
+    Ok(())
+  }
+
+  /// Blocks until the semaphore reaches at least `value`, timeout is in nanoseconds.
+  pub(crate) fn wait_value(&self, value: u64, timeout: u64) -> Result<()> {
+    let semaphores = [self.semaphore];
+    let values = [value];
+    let wait_info = vk::SemaphoreWaitInfo::builder().semaphores(&semaphores).values(&values);
+
+    unsafe { self.device.wait_semaphores(&wait_info, timeout)? };
+    Ok(())
+  }
+
+  #[allow(dead_code)]
+  pub(crate) fn get_device(&self) -> Arc<Device> {
+    self.device.clone()
+  }
+}
+
+impl Drop for TimelineSemaphore {
+  fn drop(&mut self) {
+    debug!("Destroying timeline semaphore.");
+    unsafe { self.device.destroy_semaphore(self.semaphore, None) };
+  }
+}
+
+impl std::ops::Deref for TimelineSemaphore {
+  type Target = vk::Semaphore;
+
+  fn deref(&self) -> &Self::Target {
+    &self.semaphore
+  }
+}
@@ -0,0 +1,363 @@
+use super::allocator::{Image, ImagePurpose};
+use super::descriptors::{GlobalDescriptorSetInfo, GlobalDescriptorSets, SkyboxDescriptorSetInfo, SkyboxDescriptorSets, TransformDescriptorSets};
+use super::elements::{CommandPool, Fence, ImageView, Pipeline, PipelineLayout, PipelineSettings, Sampler};
+use super::rendering_context::RenderingContext;
+use super::{Allocator, Device, Vulkan};
+use crate::utils::constants::*;
+use crate::utils::tools::Result;
+
+use ash::vk;
+use log::{debug, trace};
+use nalgebra_glm as glm;
+
+use std::sync::Arc;
+
+/// Width and height of every cubemap face in pixels.
+const ENVIRONMENT_MAP_SIZE: u32 = 128;
+const CUBE_FACE_COUNT: u32 = 6;
+const DEFAULT_UPDATE_INTERVAL: u32 = 30;
+const ENVIRONMENT_MAP_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Cubemap of the scene as seen from a single point, re-rendered every few frames so reflections can pick up moving objects.
+pub(crate) struct DynamicEnvironmentMap {
+  device: Arc<Device>,
+  position: glm::Vec3,
+  update_interval: u32,
+  frames_since_update: u32,
+  rendered: bool,
+  face_views: Vec<ImageView>,
+  cube_view: ImageView,
+  depth_image_view: ImageView,
+  cube_image: Image,
+  depth_image: Image,
+  sampler: Sampler,
+  pipeline_layout: Arc<PipelineLayout>,
+  pipeline: Pipeline,
+  command_pool: CommandPool,
+  fence: Fence,
+  // Every face is drawn with a view of its own within the same command buffer, so each needs its own set
+  global_descriptor_sets: GlobalDescriptorSets,
+  transform_descriptor_sets: TransformDescriptorSets,
+  // The faces are render targets while they're drawn, so this set never points at the map itself
+  skybox_descriptor_sets: SkyboxDescriptorSets,
+}
+
+impl DynamicEnvironmentMap {
+  pub(crate) fn new(vulkan: &Vulkan, allocator: &mut Allocator, position: glm::Vec3) -> Result<Self> {
+    debug!("Creating dynamic environment map.");
+    let device = vulkan.get_device();
+    let extent = vk::Extent3D {
+      width: ENVIRONMENT_MAP_SIZE,
+      height: ENVIRONMENT_MAP_SIZE,
+      depth: 1,
+    };
+
+    let cube_image_info = vk::ImageCreateInfo {
+      flags: vk::ImageCreateFlags::CUBE_COMPATIBLE,
+      format: ENVIRONMENT_MAP_FORMAT,
+      tiling: vk::ImageTiling::OPTIMAL,
+      usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+      image_type: vk::ImageType::TYPE_2D,
+      samples: vk::SampleCountFlags::TYPE_1,
+      mip_levels: 1,
+      array_layers: CUBE_FACE_COUNT,
+      extent,
+      ..Default::default()
+    };
+    let cube_image = allocator.create_image(&[], cube_image_info, ImagePurpose::ColorAttachment)?;
+
+    let depth_image_info = vk::ImageCreateInfo {
+      format: DEPTH_FORMAT,
+      usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+      flags: vk::ImageCreateFlags::empty(),
+      array_layers: 1,
+      ..cube_image_info
+    };
+    let depth_image = allocator.create_image(&[], depth_image_info, ImagePurpose::DepthBuffer)?;
+
+    let face_views = (0..CUBE_FACE_COUNT)
+      .map(|face| ImageView::with_layers(&device, &cube_image, &ENVIRONMENT_MAP_FORMAT, vk::ImageAspectFlags::COLOR, vk::ImageViewType::TYPE_2D, face, 1))
+      .collect::<Result<Vec<ImageView>>>()?;
+    let cube_view = ImageView::with_layers(&device, &cube_image, &ENVIRONMENT_MAP_FORMAT, vk::ImageAspectFlags::COLOR, vk::ImageViewType::CUBE, 0, CUBE_FACE_COUNT)?;
+    let depth_image_view = depth_image.make_image_view()?;
+
+    let sampler = Sampler::new(
+      &device,
+      vk::Filter::LINEAR,
+      vk::Filter::LINEAR,
+      vk::SamplerMipmapMode::NEAREST,
+      vk::SamplerAddressMode::CLAMP_TO_EDGE,
+      vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    )?;
+
+    let pipeline_layout = vulkan.mesh_pipeline_layout()?;
+    let pipeline = Pipeline::new(&device, &pipeline_layout, &PipelineSettings::mesh())?;
+
+    let command_pool = CommandPool::new(&device, device.graphics_queue_family_index(), 1)?;
+    let fence = Fence::new(&device, vk::FenceCreateFlags::empty())?;
+
+    let global_descriptor_sets = vulkan.get_global_descriptor_set_layout().create_descriptor_sets(allocator, CUBE_FACE_COUNT as usize)?;
+    let transform_descriptor_sets = vulkan.get_transform_descriptor_set_layout().create_descriptor_sets(allocator, 1)?;
+    let skybox_descriptor_sets = vulkan.get_skybox_descriptor_set_layout().create_descriptor_sets(allocator, 1)?;
+    allocator.flush();
+
+    debug!("Dynamic environment map successfully created!");
+    Ok(Self {
+      device,
+      position,
+      update_interval: DEFAULT_UPDATE_INTERVAL,
+      // Rendered on the first frame, there's nothing to sample before that
+      frames_since_update: DEFAULT_UPDATE_INTERVAL,
+      rendered: false,
+      face_views,
+      cube_view,
+      depth_image_view,
+      cube_image,
+      depth_image,
+      sampler,
+      pipeline_layout,
+      pipeline,
+      command_pool,
+      fence,
+      global_descriptor_sets,
+      transform_descriptor_sets,
+      skybox_descriptor_sets,
+    })
+  }
+
+  /// Renders the map only every `n`th frame, higher intervals trade reflection latency for performance.
+  #[allow(dead_code)]
+  pub(crate) fn update_interval(&mut self, n: u32) {
+    self.update_interval = n.max(1);
+  }
+
+  pub(crate) fn position(&self) -> glm::Vec3 {
+    self.position
+  }
+
+  pub(crate) fn set_position(&mut self, position: glm::Vec3) {
+    self.position = position;
+    self.frames_since_update = self.update_interval;
+  }
+
+  /// The cubemap still shows the old textures of the material, so it gets redrawn on the next update check.
+  pub(crate) fn notify_material_updated(&mut self) {
+    self.frames_since_update = self.update_interval;
+  }
+
+  /// Counts the frame, `true` once enough frames passed since the last update.
+  pub(crate) fn needs_update(&mut self) -> bool {
+    self.frames_since_update += 1;
+    self.frames_since_update >= self.update_interval
+  }
+
+  /// Whether the cubemap was drawn at least once, it can't be sampled before that.
+  pub(crate) fn is_rendered(&self) -> bool {
+    self.rendered
+  }
+
+  /// All six faces for sampling with a `samplerCube`, only valid once `is_rendered`.
+  pub(crate) fn skybox_descriptor_set_info(&self) -> SkyboxDescriptorSetInfo {
+    SkyboxDescriptorSetInfo {
+      image: &self.cube_view,
+      sampler: &self.sampler,
+    }
+  }
+
+  /// Draws the scene into all six faces and leaves the cubemap ready for sampling. Blocks until the GPU is done with it.
+  pub(crate) fn render(&mut self, transforms: &[glm::Mat4], draw: impl Fn(&RenderingContext)) -> Result<()> {
+    trace!("Rendering dynamic environment map at {:?}", self.position);
+    self.frames_since_update = 0;
+
+    let projection = glm::perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 10.0);
+    for (face, view) in face_views(self.position).into_iter().enumerate() {
+      self.global_descriptor_sets[face].update_descriptor(GlobalDescriptorSetInfo {
+        view,
+        projection,
+        environment_intensity: 1.0,
+        has_environment_map: 0,
+      })?;
+    }
+    self.transform_descriptor_sets[0].update_transforms(transforms)?;
+
+    let device = &self.device;
+    self.command_pool.reset(false)?;
+    let command_buffer = self.command_pool[0];
+
+    let render_area = vk::Rect2D {
+      offset: vk::Offset2D { x: 0, y: 0 },
+      extent: vk::Extent2D {
+        width: ENVIRONMENT_MAP_SIZE,
+        height: ENVIRONMENT_MAP_SIZE,
+      },
+    };
+
+    let viewport = vk::Viewport {
+      x: 0.0,
+      y: 0.0,
+      height: ENVIRONMENT_MAP_SIZE as f32,
+      width: ENVIRONMENT_MAP_SIZE as f32,
+      max_depth: 1.0,
+      min_depth: 0.0,
+    };
+
+    let mut rendering_context = RenderingContext::new(device, &self.command_pool[0], &self.pipeline_layout, 0.0);
+
+    unsafe {
+      device.begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())?;
+      // Every face gets cleared, so the old contents can be discarded
+      self.cmd_transition_faces(command_buffer, vk::ImageLayout::UNDEFINED, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+    }
+
+    rendering_context.bind_descriptor_buffer(&self.global_descriptor_sets);
+    rendering_context.bind_descriptor_buffer(&self.transform_descriptor_sets);
+    rendering_context.set_descriptor_set(&self.transform_descriptor_sets[0]);
+    rendering_context.bind_descriptor_buffer(&self.skybox_descriptor_sets);
+    rendering_context.set_descriptor_set(&self.skybox_descriptor_sets[0]);
+
+    for face in 0..CUBE_FACE_COUNT as usize {
+      let color_attachment = [vk::RenderingAttachmentInfo {
+        image_view: *self.face_views[face],
+        image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::STORE,
+        clear_value: vk::ClearValue {
+          color: vk::ClearColorValue { float32: [0.2, 0.0, 0.9, 1.0] },
+        },
+        ..Default::default()
+      }];
+
+      let depth_attachment = [vk::RenderingAttachmentInfo {
+        image_view: *self.depth_image_view,
+        image_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::DONT_CARE,
+        clear_value: vk::ClearValue {
+          depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+        },
+        ..Default::default()
+      }];
+
+      let rendering_info = vk::RenderingInfo {
+        render_area,
+        layer_count: 1,
+        color_attachment_count: 1,
+        p_color_attachments: color_attachment.as_ptr(),
+        p_depth_attachment: depth_attachment.as_ptr(),
+        ..Default::default()
+      };
+
+      unsafe {
+        // All faces share the depth image, the previous face has to be done with it before it gets cleared again
+        if face > 0 {
+          self.cmd_depth_barrier(command_buffer);
+        }
+
+        device.cmd_begin_rendering(command_buffer, &rendering_info);
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, *self.pipeline);
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+      }
+
+      rendering_context.set_descriptor_set(&self.global_descriptor_sets[face]);
+      draw(&rendering_context);
+      rendering_context.complete_rendering_command();
+    }
+
+    unsafe { self.cmd_transition_faces(command_buffer, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL) };
+    rendering_context.end_command_buffer()?;
+
+    let submit_info = vk::SubmitInfo {
+      command_buffer_count: 1,
+      p_command_buffers: &command_buffer,
+      ..Default::default()
+    };
+
+    unsafe { device.queue_submit(device.graphics_queue(), &[submit_info], *self.fence)? };
+    self.fence.wait_and_reset()?;
+    self.rendered = true;
+    Ok(())
+  }
+
+  unsafe fn cmd_transition_faces(&self, command_buffer: vk::CommandBuffer, old_layout: vk::ImageLayout, new_layout: vk::ImageLayout) {
+    let (src_stage_mask, src_access_mask, dst_stage_mask, dst_access_mask) = match new_layout {
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => (
+        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        vk::AccessFlags2::SHADER_SAMPLED_READ,
+      ),
+      _ => (
+        vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        vk::AccessFlags2::NONE,
+        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+      ),
+    };
+
+    let barrier = vk::ImageMemoryBarrier2 {
+      src_stage_mask,
+      src_access_mask,
+      dst_stage_mask,
+      dst_access_mask,
+      old_layout,
+      new_layout,
+      src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      image: *self.cube_image,
+      subresource_range: vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: CUBE_FACE_COUNT,
+      },
+      ..Default::default()
+    };
+
+    let dependency_info = vk::DependencyInfo::builder().image_memory_barriers(std::slice::from_ref(&barrier));
+    self.device.cmd_pipeline_barrier2(command_buffer, &dependency_info);
+  }
+
+  unsafe fn cmd_depth_barrier(&self, command_buffer: vk::CommandBuffer) {
+    let depth_stages = vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS;
+    let depth_access = vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE;
+
+    let barrier = vk::ImageMemoryBarrier2 {
+      src_stage_mask: depth_stages,
+      src_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+      dst_stage_mask: depth_stages,
+      dst_access_mask: depth_access,
+      old_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+      new_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+      src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      image: *self.depth_image,
+      subresource_range: vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::DEPTH,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+      },
+      ..Default::default()
+    };
+
+    let dependency_info = vk::DependencyInfo::builder().image_memory_barriers(std::slice::from_ref(&barrier));
+    self.device.cmd_pipeline_barrier2(command_buffer, &dependency_info);
+  }
+}
+
+/// View matrices looking down +X, -X, +Y, -Y, +Z and -Z, in the face order and orientation Vulkan samples cubemaps with.
+fn face_views(position: glm::Vec3) -> [glm::Mat4; CUBE_FACE_COUNT as usize] {
+  let directions = [
+    (glm::Vec3::x(), -glm::Vec3::y()),
+    (-glm::Vec3::x(), -glm::Vec3::y()),
+    (glm::Vec3::y(), glm::Vec3::z()),
+    (-glm::Vec3::y(), -glm::Vec3::z()),
+    (glm::Vec3::z(), -glm::Vec3::y()),
+    (-glm::Vec3::z(), -glm::Vec3::y()),
+  ];
+
+  directions.map(|(direction, up)| glm::look_at(&position, &(position + direction), &up))
+}
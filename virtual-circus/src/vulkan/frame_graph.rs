@@ -0,0 +1,637 @@
+use super::Device;
+use crate::utils::tools::{EngineError, Result};
+
+use ash::vk;
+use log::trace;
+
+//-----------------------------------Resources-----------------------------------------------
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct ResourceHandle(usize);
+
+/// Layout of an image together with the last stages and accesses that used it, outside of the passes of the graph.
+#[derive(Clone, Copy)]
+pub(crate) struct ImageState {
+  pub(crate) layout: vk::ImageLayout,
+  pub(crate) stage_mask: vk::PipelineStageFlags2,
+  pub(crate) access_mask: vk::AccessFlags2,
+}
+
+impl ImageState {
+  pub(crate) fn new(layout: vk::ImageLayout, stage_mask: vk::PipelineStageFlags2, access_mask: vk::AccessFlags2) -> Self {
+    Self { layout, stage_mask, access_mask }
+  }
+
+  /// Image whose contents can be discarded, nothing has to be waited on before using it.
+  pub(crate) fn undefined() -> Self {
+    Self::new(vk::ImageLayout::UNDEFINED, vk::PipelineStageFlags2::TOP_OF_PIPE, vk::AccessFlags2::NONE)
+  }
+}
+
+struct Resource {
+  name: &'static str,
+  image: vk::Image,
+  aspect_mask: vk::ImageAspectFlags,
+  initial_state: ImageState,
+  /// State the image has to be left in once all passes are done, for example `PRESENT_SRC_KHR` for swapchain images.
+  final_state: Option<ImageState>,
+  /// Source and destination queue family of a release recorded with the final barrier.
+  queue_family_release: Option<(u32, u32)>,
+}
+
+/// How a pass accesses one of the graph's images.
+#[derive(Clone, Copy)]
+pub(crate) struct ResourceUsage {
+  pub(crate) resource: ResourceHandle,
+  pub(crate) layout: vk::ImageLayout,
+  pub(crate) stage_mask: vk::PipelineStageFlags2,
+  pub(crate) access_mask: vk::AccessFlags2,
+}
+
+impl ResourceUsage {
+  pub(crate) fn color_attachment(resource: ResourceHandle) -> Self {
+    Self {
+      resource,
+      layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+      stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+      access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+    }
+  }
+
+  pub(crate) fn depth_attachment(resource: ResourceHandle) -> Self {
+    Self {
+      resource,
+      layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+      stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+      access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+    }
+  }
+
+  /// Depth image sampled by fragment shaders, like the shadow map.
+  pub(crate) fn sampled_depth(resource: ResourceHandle) -> Self {
+    Self {
+      resource,
+      layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+      stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+      access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+    }
+  }
+
+  pub(crate) fn transfer_source(resource: ResourceHandle) -> Self {
+    Self {
+      resource,
+      layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      stage_mask: vk::PipelineStageFlags2::BLIT,
+      access_mask: vk::AccessFlags2::TRANSFER_READ,
+    }
+  }
+
+  pub(crate) fn transfer_destination(resource: ResourceHandle) -> Self {
+    Self {
+      resource,
+      layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+      stage_mask: vk::PipelineStageFlags2::BLIT,
+      access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+    }
+  }
+}
+
+//-----------------------------------Passes-----------------------------------------------
+
+type RecordFunction<'a> = Box<dyn Fn(&Device, vk::CommandBuffer) + 'a>;
+
+pub(crate) struct Pass<'a> {
+  name: &'static str,
+  reads: Vec<ResourceUsage>,
+  writes: Vec<ResourceUsage>,
+  record: RecordFunction<'a>,
+}
+
+impl<'a> Pass<'a> {
+  pub(crate) fn new(name: &'static str, reads: Vec<ResourceUsage>, writes: Vec<ResourceUsage>, record: impl Fn(&Device, vk::CommandBuffer) + 'a) -> Self {
+    Self {
+      name,
+      reads,
+      writes,
+      record: Box::new(record),
+    }
+  }
+
+  fn usages(&self) -> impl Iterator<Item = (&ResourceUsage, bool)> {
+    self.reads.iter().map(|usage| (usage, false)).chain(self.writes.iter().map(|usage| (usage, true)))
+  }
+}
+
+/// Draws the depth of the shadow casters as seen from the light.
+pub(crate) struct ShadowPass;
+
+impl ShadowPass {
+  pub(crate) fn pass<'a>(shadow_map: ResourceHandle, record: impl Fn(&Device, vk::CommandBuffer) + 'a) -> Pass<'a> {
+    Pass::new("Shadow", Vec::new(), vec![ResourceUsage::depth_attachment(shadow_map)], record)
+  }
+}
+
+/// Draws the scene into the color and depth attachments, sampling the shadow map if there is one.
+pub(crate) struct MainPass;
+
+impl MainPass {
+  pub(crate) fn pass<'a>(color: ResourceHandle, depth: ResourceHandle, shadow_map: Option<ResourceHandle>, record: impl Fn(&Device, vk::CommandBuffer) + 'a) -> Pass<'a> {
+    let reads = shadow_map.map(ResourceUsage::sampled_depth).into_iter().collect();
+    Pass::new("Main", reads, vec![ResourceUsage::color_attachment(color), ResourceUsage::depth_attachment(depth)], record)
+  }
+}
+
+/// Draws on top of the finished main pass, like the debug HUDs.
+/// The depth attachment is only there because the fullscreen pipelines are created with its format, nothing tests against it.
+pub(crate) struct OverlayPass;
+
+impl OverlayPass {
+  pub(crate) fn pass<'a>(color: ResourceHandle, depth: ResourceHandle, record: impl Fn(&Device, vk::CommandBuffer) + 'a) -> Pass<'a> {
+    Pass::new("Overlay", Vec::new(), vec![ResourceUsage::color_attachment(color), ResourceUsage::depth_attachment(depth)], record)
+  }
+}
+
+/// Copies one image into another, like the finished color attachment into the swapchain image.
+pub(crate) struct BlitPass;
+
+impl BlitPass {
+  pub(crate) fn pass<'a>(source: ResourceHandle, destination: ResourceHandle, record: impl Fn(&Device, vk::CommandBuffer) + 'a) -> Pass<'a> {
+    Pass::new("Blit", vec![ResourceUsage::transfer_source(source)], vec![ResourceUsage::transfer_destination(destination)], record)
+  }
+}
+
+//-----------------------------------Frame Graph-----------------------------------------------
+
+/// Passes of a frame together with the images they use, the order of the passes and the barriers between them are inferred when compiling.
+#[derive(Default)]
+pub(crate) struct FrameGraph<'a> {
+  resources: Vec<Resource>,
+  passes: Vec<Pass<'a>>,
+}
+
+impl<'a> FrameGraph<'a> {
+  pub(crate) fn new() -> Self {
+    Self {
+      resources: Vec::new(),
+      passes: Vec::new(),
+    }
+  }
+
+  /// `initial_state` is the state the image is in when the frame graph starts, `ImageState::undefined` if its contents can be discarded.
+  pub(crate) fn add_image(&mut self, name: &'static str, image: vk::Image, aspect_mask: vk::ImageAspectFlags, initial_state: ImageState) -> ResourceHandle {
+    self.resources.push(Resource {
+      name,
+      image,
+      aspect_mask,
+      initial_state,
+      final_state: None,
+      queue_family_release: None,
+    });
+
+    ResourceHandle(self.resources.len() - 1)
+  }
+
+  pub(crate) fn set_final_state(&mut self, resource: ResourceHandle, state: ImageState) {
+    self.resources[resource.0].final_state = Some(state);
+  }
+
+  /// Releases the image to another queue family with its final barrier, the matching acquire has to be recorded on that queue.
+  pub(crate) fn release_to_queue_family(&mut self, resource: ResourceHandle, src_queue_family_index: u32, dst_queue_family_index: u32) {
+    self.resources[resource.0].queue_family_release = Some((src_queue_family_index, dst_queue_family_index));
+  }
+
+  pub(crate) fn add_pass(&mut self, pass: Pass<'a>) {
+    self.passes.push(pass);
+  }
+
+  /// Orders the passes by their dependencies and works out the barriers needed before each of them.
+  /// Passes without a dependency between them keep the order they were added in.
+  pub(crate) fn compile(self) -> Result<CompiledFrameGraph<'a>> {
+    let order = self.sort_passes()?;
+
+    let mut states = self.resources.iter().map(ResourceState::new).collect::<Vec<ResourceState>>();
+    let mut passes = self.passes.into_iter().map(Some).collect::<Vec<Option<Pass>>>();
+    let mut compiled_passes = Vec::with_capacity(order.len());
+
+    for pass_index in order {
+      let pass = passes[pass_index].take().unwrap();
+      let mut barriers = Vec::new();
+
+      for (usage, is_write) in pass.usages() {
+        let resource = &self.resources[usage.resource.0];
+        if let Some(barrier) = states[usage.resource.0].transition(resource, usage, is_write) {
+          barriers.push(barrier);
+        }
+      }
+
+      trace!("Frame graph pass {} needs {} barriers", pass.name, barriers.len());
+      compiled_passes.push(CompiledPass { pass, barriers });
+    }
+
+    let mut final_barriers = Vec::new();
+    for (resource_index, (resource, state)) in self.resources.iter().zip(states.iter_mut()).enumerate() {
+      let Some(final_state) = resource.final_state else {
+        continue;
+      };
+
+      let usage = ResourceUsage {
+        resource: ResourceHandle(resource_index),
+        layout: final_state.layout,
+        stage_mask: final_state.stage_mask,
+        access_mask: final_state.access_mask,
+      };
+
+      // A release always needs a barrier, even if the layout stays the same
+      let barrier = match resource.queue_family_release {
+        Some(_) => Some(state.barrier(resource, &usage)),
+        None => state.transition(resource, &usage, false),
+      };
+
+      if let Some(mut barrier) = barrier {
+        if let Some((src_queue_family_index, dst_queue_family_index)) = resource.queue_family_release {
+          barrier.src_queue_family_index = src_queue_family_index;
+          barrier.dst_queue_family_index = dst_queue_family_index;
+        }
+        final_barriers.push(barrier);
+      }
+    }
+
+    Ok(CompiledFrameGraph {
+      passes: compiled_passes,
+      final_barriers,
+    })
+  }
+
+  /// Kahn's algorithm over the producer and consumer pairs of every image. A pass reading an image depends on every pass writing it,
+  /// while passes writing the same image keep the order they were added in.
+  fn sort_passes(&self) -> Result<Vec<usize>> {
+    let pass_count = self.passes.len();
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); pass_count];
+    let mut dependency_counts = vec![0; pass_count];
+
+    let mut writers = vec![Vec::new(); self.resources.len()];
+    for (pass_index, pass) in self.passes.iter().enumerate() {
+      for usage in &pass.writes {
+        writers[usage.resource.0].push(pass_index);
+      }
+    }
+
+    let mut add_dependency = |producer: usize, consumer: usize| {
+      // Passes reading what they write themselves don't depend on each other
+      if producer != consumer && !dependents[producer].contains(&consumer) {
+        dependents[producer].push(consumer);
+        dependency_counts[consumer] += 1;
+      }
+    };
+
+    for resource_writers in &writers {
+      for pair in resource_writers.windows(2) {
+        add_dependency(pair[0], pair[1]);
+      }
+    }
+
+    for (pass_index, pass) in self.passes.iter().enumerate() {
+      for usage in &pass.reads {
+        for writer in &writers[usage.resource.0] {
+          add_dependency(*writer, pass_index);
+        }
+      }
+    }
+
+    let mut ready = (0..pass_count).filter(|pass| dependency_counts[*pass] == 0).collect::<Vec<usize>>();
+    let mut order = Vec::with_capacity(pass_count);
+
+    while !ready.is_empty() {
+      // Taking the earliest ready pass keeps the order of independent passes stable
+      let pass = ready.remove(0);
+      order.push(pass);
+
+      for dependent in &dependents[pass] {
+        dependency_counts[*dependent] -= 1;
+        if dependency_counts[*dependent] == 0 {
+          ready.push(*dependent);
+          ready.sort_unstable();
+        }
+      }
+    }
+
+    if order.len() != pass_count {
+      return Err(EngineError::CreationError("frame graph passes have a circular dependency"));
+    }
+
+    Ok(order)
+  }
+}
+
+/// Last known state of an image while the barriers are worked out.
+struct ResourceState {
+  layout: vk::ImageLayout,
+  stage_mask: vk::PipelineStageFlags2,
+  access_mask: vk::AccessFlags2,
+  written: bool,
+}
+
+impl ResourceState {
+  fn new(resource: &Resource) -> Self {
+    let state = resource.initial_state;
+    Self {
+      layout: state.layout,
+      stage_mask: state.stage_mask,
+      access_mask: state.access_mask,
+      // Accesses from before the graph could be writes, so they have to be waited on
+      written: state.access_mask != vk::AccessFlags2::NONE,
+    }
+  }
+
+  /// Barrier needed before `usage`, reads following reads in the same layout don't need one.
+  fn transition(&mut self, resource: &Resource, usage: &ResourceUsage, is_write: bool) -> Option<vk::ImageMemoryBarrier2> {
+    if self.layout == usage.layout {
+      // Nothing accessed the image yet, so there's nothing to wait for
+      if self.access_mask == vk::AccessFlags2::NONE {
+        self.stage_mask = usage.stage_mask;
+        self.access_mask = usage.access_mask;
+        self.written = is_write;
+        return None;
+      }
+
+      if !self.written && !is_write {
+        // Later barriers have to wait for every reader, not just the first one
+        self.stage_mask |= usage.stage_mask;
+        self.access_mask |= usage.access_mask;
+        return None;
+      }
+    }
+
+    let barrier = self.barrier(resource, usage);
+    self.written = is_write;
+    Some(barrier)
+  }
+
+  /// Barrier from the current state into `usage`, which then becomes the current state.
+  fn barrier(&mut self, resource: &Resource, usage: &ResourceUsage) -> vk::ImageMemoryBarrier2 {
+    let barrier = vk::ImageMemoryBarrier2 {
+      src_stage_mask: self.stage_mask,
+      src_access_mask: self.access_mask,
+      dst_stage_mask: usage.stage_mask,
+      dst_access_mask: usage.access_mask,
+      old_layout: self.layout,
+      new_layout: usage.layout,
+      src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+      image: resource.image,
+      subresource_range: vk::ImageSubresourceRange {
+        aspect_mask: resource.aspect_mask,
+        base_mip_level: 0,
+        level_count: vk::REMAINING_MIP_LEVELS,
+        base_array_layer: 0,
+        layer_count: vk::REMAINING_ARRAY_LAYERS,
+      },
+      ..Default::default()
+    };
+
+    trace!("Frame graph barrier for {}: {:?} -> {:?}", resource.name, self.layout, usage.layout);
+    self.layout = usage.layout;
+    self.stage_mask = usage.stage_mask;
+    self.access_mask = usage.access_mask;
+
+    barrier
+  }
+}
+
+//-----------------------------------Compiled Frame Graph-----------------------------------------------
+
+struct CompiledPass<'a> {
+  pass: Pass<'a>,
+  barriers: Vec<vk::ImageMemoryBarrier2>,
+}
+
+pub(crate) struct CompiledFrameGraph<'a> {
+  passes: Vec<CompiledPass<'a>>,
+  final_barriers: Vec<vk::ImageMemoryBarrier2>,
+}
+
+impl<'a> CompiledFrameGraph<'a> {
+  /// Records every pass in order, each preceded by the barriers it needs.
+  pub(crate) fn record(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+    for compiled_pass in &self.passes {
+      record_barriers(device, command_buffer, &compiled_pass.barriers);
+      (compiled_pass.pass.record)(device, command_buffer);
+    }
+
+    record_barriers(device, command_buffer, &self.final_barriers);
+  }
+}
+
+fn record_barriers(device: &Device, command_buffer: vk::CommandBuffer, barriers: &[vk::ImageMemoryBarrier2]) {
+  if barriers.is_empty() {
+    return;
+  }
+
+  let dependency_info = vk::DependencyInfo::builder().image_memory_barriers(barriers);
+  unsafe { device.cmd_pipeline_barrier2(command_buffer, &dependency_info) };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn image(graph: &mut FrameGraph, name: &'static str, initial_state: ImageState) -> ResourceHandle {
+    graph.add_image(name, vk::Image::null(), vk::ImageAspectFlags::COLOR, initial_state)
+  }
+
+  fn sampled(resource: ResourceHandle) -> ResourceUsage {
+    ResourceUsage {
+      resource,
+      layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+      access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+    }
+  }
+
+  fn pass_names(compiled: &CompiledFrameGraph) -> Vec<&'static str> {
+    compiled.passes.iter().map(|compiled_pass| compiled_pass.pass.name).collect()
+  }
+
+  #[test]
+  fn passes_are_ordered_by_producers_not_insertion() {
+    let mut graph = FrameGraph::new();
+    let shadow_map = image(&mut graph, "Shadow map", ImageState::undefined());
+    let color = image(&mut graph, "Color", ImageState::undefined());
+    let depth = image(&mut graph, "Depth", ImageState::undefined());
+    let swapchain_image = image(&mut graph, "Swapchain image", ImageState::undefined());
+
+    graph.add_pass(BlitPass::pass(color, swapchain_image, |_, _| ()));
+    graph.add_pass(MainPass::pass(color, depth, Some(shadow_map), |_, _| ()));
+    graph.add_pass(ShadowPass::pass(shadow_map, |_, _| ()));
+
+    let compiled = graph.compile().unwrap();
+    assert_eq!(pass_names(&compiled), vec!["Shadow", "Main", "Blit"]);
+  }
+
+  #[test]
+  fn independent_passes_keep_insertion_order() {
+    let mut graph = FrameGraph::new();
+    let first = image(&mut graph, "First", ImageState::undefined());
+    let second = image(&mut graph, "Second", ImageState::undefined());
+
+    graph.add_pass(Pass::new("Second", Vec::new(), vec![ResourceUsage::color_attachment(second)], |_, _| ()));
+    graph.add_pass(Pass::new("First", Vec::new(), vec![ResourceUsage::color_attachment(first)], |_, _| ()));
+
+    let compiled = graph.compile().unwrap();
+    assert_eq!(pass_names(&compiled), vec!["Second", "First"]);
+  }
+
+  #[test]
+  fn circular_dependencies_fail_to_compile() {
+    let mut graph = FrameGraph::new();
+    let first = image(&mut graph, "First", ImageState::undefined());
+    let second = image(&mut graph, "Second", ImageState::undefined());
+
+    graph.add_pass(Pass::new("A", vec![sampled(first)], vec![ResourceUsage::color_attachment(second)], |_, _| ()));
+    graph.add_pass(Pass::new("B", vec![sampled(second)], vec![ResourceUsage::color_attachment(first)], |_, _| ()));
+
+    assert!(matches!(graph.compile(), Err(EngineError::CreationError(_))));
+  }
+
+  #[test]
+  fn barriers_follow_the_last_write() {
+    let attachment_state = ImageState::new(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::NONE);
+    let present_state = ImageState::new(vk::ImageLayout::PRESENT_SRC_KHR, vk::PipelineStageFlags2::BOTTOM_OF_PIPE, vk::AccessFlags2::NONE);
+
+    let mut graph = FrameGraph::new();
+    let color = image(&mut graph, "Color", attachment_state);
+    let depth = graph.add_image(
+      "Depth",
+      vk::Image::null(),
+      vk::ImageAspectFlags::DEPTH,
+      ImageState::new(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS, vk::AccessFlags2::NONE),
+    );
+    let swapchain_image = image(&mut graph, "Swapchain image", ImageState::undefined());
+    graph.set_final_state(color, attachment_state);
+    graph.set_final_state(swapchain_image, present_state);
+    graph.release_to_queue_family(swapchain_image, 0, 1);
+
+    graph.add_pass(MainPass::pass(color, depth, None, |_, _| ()));
+    graph.add_pass(BlitPass::pass(color, swapchain_image, |_, _| ()));
+    let compiled = graph.compile().unwrap();
+
+    // The attachments are already in their layouts and weren't accessed before, so the main pass needs no barriers
+    assert!(compiled.passes[0].barriers.is_empty());
+
+    let blit_barriers = &compiled.passes[1].barriers;
+    assert_eq!(blit_barriers.len(), 2);
+    let color_barrier = blit_barriers[0];
+    assert_eq!(color_barrier.old_layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+    assert_eq!(color_barrier.new_layout, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+    assert_eq!(color_barrier.src_stage_mask, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT);
+    assert_eq!(color_barrier.src_access_mask, vk::AccessFlags2::COLOR_ATTACHMENT_WRITE);
+    assert_eq!(color_barrier.dst_access_mask, vk::AccessFlags2::TRANSFER_READ);
+    let swapchain_barrier = blit_barriers[1];
+    assert_eq!(swapchain_barrier.old_layout, vk::ImageLayout::UNDEFINED);
+    assert_eq!(swapchain_barrier.new_layout, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+
+    assert_eq!(compiled.final_barriers.len(), 2);
+    assert_eq!(compiled.final_barriers[0].old_layout, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+    assert_eq!(compiled.final_barriers[0].new_layout, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+    let present_barrier = compiled.final_barriers[1];
+    assert_eq!(present_barrier.old_layout, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+    assert_eq!(present_barrier.new_layout, vk::ImageLayout::PRESENT_SRC_KHR);
+    assert_eq!(present_barrier.src_access_mask, vk::AccessFlags2::TRANSFER_WRITE);
+    assert_eq!((present_barrier.src_queue_family_index, present_barrier.dst_queue_family_index), (0, 1));
+  }
+
+  #[test]
+  fn main_pass_waits_for_the_shadow_pass() {
+    let depth_state = ImageState::new(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS, vk::AccessFlags2::NONE);
+    // Frames from before might still be sampling the shadow map, but its contents can be discarded
+    let sampled_before = ImageState::new(vk::ImageLayout::UNDEFINED, vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::NONE);
+
+    let mut graph = FrameGraph::new();
+    let color = image(&mut graph, "Color", ImageState::undefined());
+    let depth = graph.add_image("Depth", vk::Image::null(), vk::ImageAspectFlags::DEPTH, depth_state);
+    let shadow_map = graph.add_image("Shadow map", vk::Image::null(), vk::ImageAspectFlags::DEPTH, sampled_before);
+
+    graph.add_pass(ShadowPass::pass(shadow_map, |_, _| ()));
+    graph.add_pass(MainPass::pass(color, depth, Some(shadow_map), |_, _| ()));
+    let compiled = graph.compile().unwrap();
+
+    let shadow_barriers = &compiled.passes[0].barriers;
+    assert_eq!(shadow_barriers.len(), 1);
+    assert_eq!(shadow_barriers[0].old_layout, vk::ImageLayout::UNDEFINED);
+    assert_eq!(shadow_barriers[0].new_layout, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+    assert_eq!(shadow_barriers[0].src_stage_mask, vk::PipelineStageFlags2::FRAGMENT_SHADER);
+    assert_eq!(shadow_barriers[0].src_access_mask, vk::AccessFlags2::NONE);
+
+    let shadow_read = compiled.passes[1]
+      .barriers
+      .iter()
+      .find(|barrier| barrier.new_layout == vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+      .unwrap();
+    assert_eq!(shadow_read.old_layout, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+    assert_eq!(
+      shadow_read.src_access_mask,
+      vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+    );
+    assert_eq!(shadow_read.dst_stage_mask, vk::PipelineStageFlags2::FRAGMENT_SHADER);
+    assert_eq!(shadow_read.dst_access_mask, vk::AccessFlags2::SHADER_SAMPLED_READ);
+  }
+
+  #[test]
+  fn overlay_pass_draws_after_the_main_pass_and_before_the_blit() {
+    let mut graph = FrameGraph::new();
+    let color = image(&mut graph, "Color", ImageState::undefined());
+    let depth = image(&mut graph, "Depth", ImageState::undefined());
+    let swapchain_image = image(&mut graph, "Swapchain image", ImageState::undefined());
+
+    graph.add_pass(BlitPass::pass(color, swapchain_image, |_, _| ()));
+    graph.add_pass(MainPass::pass(color, depth, None, |_, _| ()));
+    graph.add_pass(OverlayPass::pass(color, depth, |_, _| ()));
+    let compiled = graph.compile().unwrap();
+    assert_eq!(pass_names(&compiled), vec!["Main", "Overlay", "Blit"]);
+
+    // The overlay draws over what the main pass wrote, so it has to wait for those writes
+    let color_barrier = compiled.passes[1]
+      .barriers
+      .iter()
+      .find(|barrier| barrier.new_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+      .unwrap();
+    assert_eq!(color_barrier.src_access_mask, vk::AccessFlags2::COLOR_ATTACHMENT_WRITE);
+  }
+
+  #[test]
+  fn reads_after_reads_share_a_barrier() {
+    let mut graph = FrameGraph::new();
+    let color = image(&mut graph, "Color", ImageState::undefined());
+    let first_target = image(&mut graph, "First target", ImageState::undefined());
+    let second_target = image(&mut graph, "Second target", ImageState::undefined());
+    graph.set_final_state(
+      color,
+      ImageState::new(
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+      ),
+    );
+
+    graph.add_pass(Pass::new("Draw", Vec::new(), vec![ResourceUsage::color_attachment(color)], |_, _| ()));
+    graph.add_pass(Pass::new("First read", vec![sampled(color)], vec![ResourceUsage::color_attachment(first_target)], |_, _| ()));
+    let compute_read = ResourceUsage {
+      stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+      ..sampled(color)
+    };
+    graph.add_pass(Pass::new("Second read", vec![compute_read], vec![ResourceUsage::transfer_destination(second_target)], |_, _| ()));
+    let compiled = graph.compile().unwrap();
+
+    let color_barriers = |pass: usize| compiled.passes[pass].barriers.iter().filter(|barrier| barrier.old_layout != vk::ImageLayout::UNDEFINED).count();
+    assert_eq!(color_barriers(1), 1);
+    assert_eq!(color_barriers(2), 0);
+
+    // Going back to an attachment has to wait for both readers
+    assert_eq!(compiled.final_barriers.len(), 1);
+    assert_eq!(
+      compiled.final_barriers[0].src_stage_mask,
+      vk::PipelineStageFlags2::FRAGMENT_SHADER | vk::PipelineStageFlags2::COMPUTE_SHADER
+    );
+    assert_eq!(compiled.final_barriers[0].src_access_mask, vk::AccessFlags2::SHADER_SAMPLED_READ);
+  }
+}
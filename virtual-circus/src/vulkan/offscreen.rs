@@ -0,0 +1,293 @@
+use super::allocator::{Buffer, BufferType, Image, ImagePurpose};
+use super::descriptors::{GlobalDescriptorSetInfo, GlobalDescriptorSets, SkyboxDescriptorSets, TransformDescriptorSets};
+use super::elements::{CommandPool, Fence, ImageView, Pipeline, PipelineLayout, PipelineSettings};
+use super::rendering_context::RenderingContext;
+use super::window::projection_matrix;
+use super::{Allocator, Device, Vulkan};
+use crate::utils::constants::*;
+use crate::utils::tools::Result;
+
+use ash::vk;
+use log::{debug, trace};
+use nalgebra_glm as glm;
+
+use std::sync::Arc;
+
+/// Bytes per pixel of the offscreen color image.
+const PIXEL_SIZE: u64 = 4;
+
+/// Fixed size color target for rendering without a window, every frame gets copied into a buffer the host can read.
+/// Frames are rendered one at a time, so all resources exist only once.
+pub(crate) struct OffscreenTarget {
+  device: Arc<Device>,
+  extent: vk::Extent2D,
+  color_image_view: ImageView,
+  depth_image_view: ImageView,
+  color_image: Image,
+  _depth_image: Image,
+  readback_buffer: Buffer,
+  pipeline_layout: Arc<PipelineLayout>,
+  offscreen_pipeline: Pipeline,
+  command_pool: CommandPool,
+  frame_fence: Fence,
+  global_descriptor_sets: GlobalDescriptorSets,
+  transform_descriptor_sets: TransformDescriptorSets,
+  // Never gets an environment map, it only has to be bound since the mesh shaders declare it
+  skybox_descriptor_sets: SkyboxDescriptorSets,
+  environment_intensity: f32,
+}
+
+impl OffscreenTarget {
+  pub(crate) fn new(vulkan: &Vulkan, allocator: &mut Allocator, extent: vk::Extent2D) -> Result<Self> {
+    debug!("Creating offscreen target of {}x{}.", extent.width, extent.height);
+    let device = vulkan.get_device();
+
+    let color_image = create_target_image(
+      allocator,
+      extent,
+      vk::Format::R8G8B8A8_SRGB,
+      vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+      ImagePurpose::ColorAttachment,
+    )?;
+    let depth_image = create_target_image(allocator, extent, DEPTH_FORMAT, vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, ImagePurpose::DepthBuffer)?;
+    let color_image_view = color_image.make_image_view()?;
+    let depth_image_view = depth_image.make_image_view()?;
+
+    let readback_size = extent.width as u64 * extent.height as u64 * PIXEL_SIZE;
+    let readback_buffer = allocator.create_buffer(readback_size, vk::BufferUsageFlags::TRANSFER_DST, BufferType::GpuToCpu)?;
+
+    // Same layout as the windowed graphics pipeline, so it comes straight out of the cache
+    let pipeline_layout = vulkan.mesh_pipeline_layout()?;
+    let offscreen_pipeline = Pipeline::new(&device, &pipeline_layout, &PipelineSettings::mesh())?;
+
+    let command_pool = CommandPool::new(&device, device.graphics_queue_family_index(), 1)?;
+    let frame_fence = Fence::new(&device, vk::FenceCreateFlags::empty())?;
+
+    let global_descriptor_sets = vulkan.get_global_descriptor_set_layout().create_descriptor_sets(allocator, 1)?;
+    let transform_descriptor_sets = vulkan.get_transform_descriptor_set_layout().create_descriptor_sets(allocator, 1)?;
+    let skybox_descriptor_sets = vulkan.get_skybox_descriptor_set_layout().create_descriptor_sets(allocator, 1)?;
+
+    // Transitions the new images into their attachment layouts
+    allocator.flush();
+
+    debug!("Offscreen target successfully created!");
+    Ok(Self {
+      device,
+      extent,
+      color_image_view,
+      depth_image_view,
+      color_image,
+      _depth_image: depth_image,
+      readback_buffer,
+      pipeline_layout,
+      offscreen_pipeline,
+      command_pool,
+      frame_fence,
+      global_descriptor_sets,
+      transform_descriptor_sets,
+      skybox_descriptor_sets,
+      environment_intensity: 1.0,
+    })
+  }
+
+  pub(crate) fn extent(&self) -> vk::Extent2D {
+    self.extent
+  }
+
+  /// Renders a single frame viewed through `view`, `draw` records the draw calls of the scene.
+  /// Blocks until the GPU is done, afterwards the frame can be read with `capture_frame`.
+  pub(crate) fn render_frame(&mut self, view: glm::Mat4, transforms: &[glm::Mat4], time: f32, draw: impl FnOnce(&RenderingContext)) -> Result<()> {
+    trace!("Rendering offscreen frame at {}s", time);
+
+    self.global_descriptor_sets[0].update_descriptor(GlobalDescriptorSetInfo {
+      view,
+      projection: projection_matrix(&self.extent),
+      environment_intensity: self.environment_intensity,
+      has_environment_map: 0,
+    })?;
+    self.transform_descriptor_sets[0].update_transforms(transforms)?;
+
+    let device = &self.device;
+    self.command_pool.reset(false)?;
+    let command_buffer = self.command_pool[0];
+
+    let color_attachment = [vk::RenderingAttachmentInfo {
+      image_view: *self.color_image_view,
+      image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+      load_op: vk::AttachmentLoadOp::CLEAR,
+      store_op: vk::AttachmentStoreOp::STORE,
+      clear_value: vk::ClearValue {
+        color: vk::ClearColorValue { float32: [0.2, 0.0, 0.9, 1.0] },
+      },
+      ..Default::default()
+    }];
+
+    let depth_attachment = [vk::RenderingAttachmentInfo {
+      image_view: *self.depth_image_view,
+      image_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+      load_op: vk::AttachmentLoadOp::CLEAR,
+      store_op: vk::AttachmentStoreOp::DONT_CARE,
+      clear_value: vk::ClearValue {
+        depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+      },
+      ..Default::default()
+    }];
+
+    let render_area = vk::Rect2D {
+      offset: vk::Offset2D { x: 0, y: 0 },
+      extent: self.extent,
+    };
+
+    let rendering_info = vk::RenderingInfo {
+      render_area,
+      layer_count: 1,
+      color_attachment_count: 1,
+      p_color_attachments: color_attachment.as_ptr(),
+      p_depth_attachment: depth_attachment.as_ptr(),
+      ..Default::default()
+    };
+
+    let viewport = vk::Viewport {
+      x: 0.0,
+      y: 0.0,
+      height: self.extent.height as f32,
+      width: self.extent.width as f32,
+      max_depth: 1.0,
+      min_depth: 0.0,
+    };
+
+    // Time is passed to the shaders in milliseconds, the same as for windowed frames
+    let mut rendering_context = RenderingContext::new(device, &self.command_pool[0], &self.pipeline_layout, time * 1000.0);
+
+    unsafe {
+      device.begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())?;
+      device.cmd_begin_rendering(command_buffer, &rendering_info);
+      device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, *self.offscreen_pipeline);
+      device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+      device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+    }
+
+    rendering_context.bind_descriptor_buffer(&self.global_descriptor_sets);
+    rendering_context.set_descriptor_set(&self.global_descriptor_sets[0]);
+    rendering_context.bind_descriptor_buffer(&self.transform_descriptor_sets);
+    rendering_context.set_descriptor_set(&self.transform_descriptor_sets[0]);
+    rendering_context.bind_descriptor_buffer(&self.skybox_descriptor_sets);
+    rendering_context.set_descriptor_set(&self.skybox_descriptor_sets[0]);
+
+    draw(&rendering_context);
+
+    rendering_context.complete_rendering_command();
+    self.record_readback(command_buffer);
+    rendering_context.end_command_buffer()?;
+
+    let submit_info = vk::SubmitInfo {
+      command_buffer_count: 1,
+      p_command_buffers: &command_buffer,
+      ..Default::default()
+    };
+
+    unsafe { device.queue_submit(device.graphics_queue(), &[submit_info], *self.frame_fence)? };
+    self.frame_fence.wait_and_reset()
+  }
+
+  /// Tightly packed RGBA pixels of the last rendered frame, row by row from the top left corner.
+  pub(crate) fn capture_frame(&mut self) -> Vec<u8> {
+    self.readback_buffer.data().to_vec()
+  }
+
+  fn record_readback(&self, command_buffer: vk::CommandBuffer) {
+    let subresource_range = vk::ImageSubresourceRange {
+      aspect_mask: vk::ImageAspectFlags::COLOR,
+      base_mip_level: 0,
+      level_count: 1,
+      base_array_layer: 0,
+      layer_count: 1,
+    };
+
+    let before_copy = vk::ImageMemoryBarrier2 {
+      src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+      src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+      dst_stage_mask: vk::PipelineStageFlags2::COPY,
+      dst_access_mask: vk::AccessFlags2::TRANSFER_READ,
+      old_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+      new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      image: *self.color_image,
+      subresource_range,
+      ..Default::default()
+    };
+
+    // The next frame clears the image anyway, only the layout has to be restored
+    let after_copy = vk::ImageMemoryBarrier2 {
+      src_stage_mask: vk::PipelineStageFlags2::COPY,
+      src_access_mask: vk::AccessFlags2::NONE,
+      dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+      dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+      old_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      new_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+      image: *self.color_image,
+      subresource_range,
+      ..Default::default()
+    };
+
+    // Makes the copied pixels visible to the host once the frame fence is signaled
+    let readback_barrier = vk::BufferMemoryBarrier2 {
+      src_stage_mask: vk::PipelineStageFlags2::COPY,
+      src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+      dst_stage_mask: vk::PipelineStageFlags2::HOST,
+      dst_access_mask: vk::AccessFlags2::HOST_READ,
+      buffer: *self.readback_buffer,
+      offset: 0,
+      size: vk::WHOLE_SIZE,
+      ..Default::default()
+    };
+
+    let region = vk::BufferImageCopy {
+      buffer_offset: 0,
+      buffer_row_length: 0,
+      buffer_image_height: 0,
+      image_subresource: vk::ImageSubresourceLayers {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        mip_level: 0,
+        base_array_layer: 0,
+        layer_count: 1,
+      },
+      image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+      image_extent: vk::Extent3D {
+        width: self.extent.width,
+        height: self.extent.height,
+        depth: 1,
+      },
+    };
+
+    let device = &self.device;
+    unsafe {
+      let before_copy_info = vk::DependencyInfo::builder().image_memory_barriers(std::slice::from_ref(&before_copy));
+      device.cmd_pipeline_barrier2(command_buffer, &before_copy_info);
+      device.cmd_copy_image_to_buffer(command_buffer, *self.color_image, vk::ImageLayout::TRANSFER_SRC_OPTIMAL, *self.readback_buffer, &[region]);
+      let after_copy_info = vk::DependencyInfo::builder()
+        .image_memory_barriers(std::slice::from_ref(&after_copy))
+        .buffer_memory_barriers(std::slice::from_ref(&readback_barrier));
+      device.cmd_pipeline_barrier2(command_buffer, &after_copy_info);
+    }
+  }
+}
+
+fn create_target_image(allocator: &mut Allocator, extent: vk::Extent2D, format: vk::Format, usage: vk::ImageUsageFlags, purpose: ImagePurpose) -> Result<Image> {
+  let image_create_info = vk::ImageCreateInfo {
+    format,
+    tiling: vk::ImageTiling::OPTIMAL,
+    usage,
+    image_type: vk::ImageType::TYPE_2D,
+    samples: vk::SampleCountFlags::TYPE_1,
+    mip_levels: 1,
+    array_layers: 1,
+    extent: vk::Extent3D {
+      width: extent.width,
+      height: extent.height,
+      depth: 1,
+    },
+    ..Default::default()
+  };
+
+  allocator.create_image(&[], image_create_info, purpose)
+}
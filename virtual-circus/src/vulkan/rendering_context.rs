@@ -6,21 +6,158 @@ use crate::utils::constants::*;
 use crate::utils::tools::Result;
 
 use ash::vk;
+use asset_lib as ast;
 use nalgebra_glm::*;
 use serde::Serialize;
 
+use std::cell::Cell;
+
+// Transforms live in the transform storage buffer, the node index selects the one used by the draw call
 #[derive(Serialize)]
 pub(crate) struct PushConstant {
+  pub(crate) node_index: u32,
   pub(crate) time: f32,
-  pub(crate) matrix: Mat4,
 }
 
+// Ordered so the tightly packed serialized data matches the std430 layout of the particle shader block
+#[derive(Serialize)]
+pub(crate) struct ParticlePushConstant {
+  pub(crate) view_projection: Mat4,
+  pub(crate) particle_buffer: u64,
+  pub(crate) delta_time: f32,
+  pub(crate) lifetime: f32,
+  pub(crate) emitter_position: Vec3,
+  pub(crate) max_particles: u32,
+}
+
+#[derive(Serialize)]
+pub(crate) struct FadePushConstant {
+  pub(crate) alpha: f32,
+}
+
+/// One row for the VRAM summary and one per memory heap, the memory HUD shader declares the same number of rows.
+pub(crate) const MEMORY_HUD_MAX_ROWS: usize = 1 + vk::MAX_MEMORY_HEAPS;
+
+// Bit n of `warning_rows` draws row n in red, the rows past `row_count` are left out
+#[derive(Serialize)]
+pub(crate) struct MemoryHudPushConstant {
+  pub(crate) usages: [f32; MEMORY_HUD_MAX_ROWS],
+  pub(crate) row_count: u32,
+  pub(crate) warning_rows: u32,
+}
+
+//-----------------------------------Mesh Input-----------------------------------------------
+
+pub(crate) enum AttributeType {
+  Position,
+  Normal,
+  Tangent,
+}
+
+impl AttributeType {
+  fn get_location(&self) -> u32 {
+    match self {
+      AttributeType::Position => 0,
+      AttributeType::Normal => 1,
+      AttributeType::Tangent => 2,
+    }
+  }
+}
+
+pub(crate) struct Attribute {
+  pub(crate) buffer: vk::Buffer,
+  pub(crate) buffer_offset: vk::DeviceSize,
+  pub(crate) attribute_format: vk::Format,
+  pub(crate) attribute_offset: u32,
+  pub(crate) attribute_stride: u32,
+  pub(crate) count: u32,
+}
+
+impl Attribute {
+  fn get_binding_description(&self, binding: u32) -> vk::VertexInputBindingDescription2EXT {
+    vk::VertexInputBindingDescription2EXT {
+      binding,
+      stride: self.attribute_stride,
+      input_rate: vk::VertexInputRate::VERTEX,
+      divisor: 1,
+      ..Default::default()
+    }
+  }
+
+  fn get_attribute_description(&self, binding: u32, location: u32) -> vk::VertexInputAttributeDescription2EXT {
+    vk::VertexInputAttributeDescription2EXT {
+      location,
+      binding,
+      format: self.attribute_format,
+      offset: self.attribute_offset,
+      ..Default::default()
+    }
+  }
+}
+
+/// Vertex buffers of a mesh together with the vertex input state to read them, every attribute gets a binding of its own.
+#[derive(Default)]
+pub(crate) struct VertexInfo {
+  buffers: Vec<vk::Buffer>,
+  bindings: Vec<vk::VertexInputBindingDescription2EXT>,
+  attributes: Vec<vk::VertexInputAttributeDescription2EXT>,
+  offsets: Vec<vk::DeviceSize>,
+  count: u32,
+}
+
+impl VertexInfo {
+  /// Interleaved `ast::Vertex` data, all attributes read the same buffer at the offset of their field within a vertex.
+  pub(crate) fn from_asset_mesh(mesh: &ast::Mesh, buffer: vk::Buffer) -> Self {
+    let attribute = |attribute_format, attribute_offset| Attribute {
+      buffer,
+      buffer_offset: mesh.vertex_offset as vk::DeviceSize,
+      attribute_format,
+      attribute_offset,
+      attribute_stride: mesh.vertex_stride,
+      count: mesh.vertex_count,
+    };
+
+    let mut vertex_info = Self::default();
+    vertex_info.add_attribute(attribute(vk::Format::R32G32B32_SFLOAT, 0), AttributeType::Position);
+    vertex_info.add_attribute(attribute(vk::Format::R32G32B32_SFLOAT, 12), AttributeType::Normal);
+    vertex_info.add_attribute(attribute(vk::Format::R32G32B32A32_SFLOAT, 24), AttributeType::Tangent);
+    vertex_info
+  }
+
+  pub(crate) fn add_attribute(&mut self, attribute: Attribute, attribute_type: AttributeType) {
+    // Vertex buffers get bound to consecutive bindings, so the binding is just the index of the attribute
+    let binding = self.bindings.len() as u32;
+
+    self.buffers.push(attribute.buffer);
+    self.bindings.push(attribute.get_binding_description(binding));
+    self.attributes.push(attribute.get_attribute_description(binding, attribute_type.get_location()));
+    self.offsets.push(attribute.buffer_offset);
+    self.count = attribute.count;
+  }
+}
+
+pub(crate) struct IndexInfo {
+  pub(crate) buffer: vk::Buffer,
+  pub(crate) count: u32,
+  pub(crate) offset: vk::DeviceSize,
+  pub(crate) index_type: vk::IndexType,
+}
+
+pub(crate) struct MeshContext {
+  pub(crate) vertex_info: VertexInfo,
+  pub(crate) index_info: Option<IndexInfo>,
+  pub(crate) topology: vk::PrimitiveTopology,
+}
+
+//-----------------------------------Rendering Context-----------------------------------------------
+
 pub(crate) struct RenderingContext<'a> {
   device: &'a Device,
   command_buffer: &'a vk::CommandBuffer,
   pipeline_layout: &'a PipelineLayout,
-  descriptor_buffer_bindings: [Option<vk::DescriptorBufferBindingInfoEXT>; DESCRIPTOR_SET_COUNT],
-  descriptor_buffer_offsets: [Option<u64>; DESCRIPTOR_SET_COUNT],
+  // Materials change between draw calls, which only get a shared reference to the context
+  descriptor_buffer_bindings: Cell<[Option<vk::DescriptorBufferBindingInfoEXT>; DESCRIPTOR_SET_COUNT]>,
+  descriptor_buffer_offsets: Cell<[Option<u64>; DESCRIPTOR_SET_COUNT]>,
   time: f32,
 }
 
@@ -30,25 +167,55 @@ impl<'a> RenderingContext<'a> {
       device,
       command_buffer,
       pipeline_layout,
-      descriptor_buffer_bindings: [None; DESCRIPTOR_SET_COUNT],
-      descriptor_buffer_offsets: [None; DESCRIPTOR_SET_COUNT],
+      descriptor_buffer_bindings: Cell::new([None; DESCRIPTOR_SET_COUNT]),
+      descriptor_buffer_offsets: Cell::new([None; DESCRIPTOR_SET_COUNT]),
       time,
     }
   }
 
-  pub(crate) fn draw_model(&self, model: &Model) {
+  /// Draws a single mesh of the model, the material of the mesh has to be set beforehand.
+  pub(crate) fn draw_model_mesh(&self, model: &Model, mesh_index: usize) {
+    let mesh = &model.meshes[mesh_index];
+    let mesh_context = MeshContext {
+      vertex_info: VertexInfo::from_asset_mesh(mesh, *model.buffer),
+      index_info: Some(IndexInfo {
+        buffer: *model.buffer,
+        count: mesh.index_count,
+        offset: mesh.index_offset as vk::DeviceSize,
+        index_type: model.index_types[mesh_index],
+      }),
+      topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+    };
+
+    self.draw_mesh(&mesh_context);
+  }
+
+  pub(crate) fn draw_mesh(&self, mesh: &MeshContext) {
+    let vertex_info = &mesh.vertex_info;
+
     unsafe {
-      for mesh in &model.meshes {
-        self.device.cmd_bind_vertex_buffers(*self.command_buffer, 0, &[*model.buffer], &[mesh.vertex_offset as u64]);
+      self.device.cmd_set_primitive_topology(*self.command_buffer, mesh.topology);
+      self.device.cmd_set_vertex_input(*self.command_buffer, &vertex_info.bindings, &vertex_info.attributes);
+      self.device.cmd_bind_vertex_buffers(*self.command_buffer, 0, &vertex_info.buffers, &vertex_info.offsets);
 
-        self.device.cmd_bind_index_buffer(*self.command_buffer, *model.buffer, mesh.index_offset as u64, vk::IndexType::UINT32);
-        self.device.cmd_draw_indexed(*self.command_buffer, mesh.index_count, 1, 0, 0, 0);
+      match &mesh.index_info {
+        Some(index_info) => {
+          self.device.cmd_bind_index_buffer(*self.command_buffer, index_info.buffer, index_info.offset, index_info.index_type);
+          self.device.cmd_draw_indexed(*self.command_buffer, index_info.count, 1, 0, 0, 0);
+        }
+        None => self.device.cmd_draw(*self.command_buffer, vertex_info.count, 1, 0, 0),
       }
     }
   }
 
-  pub(crate) fn cmd_push_constants(&self, matrix: &Mat4) {
-    let push_constant = PushConstant { time: self.time, matrix: *matrix };
+  /// Draws a single triangle covering the whole screen, the bound pipeline has to generate its vertices from `gl_VertexIndex`.
+  /// Meant for `FullscreenPipeline`s, no vertex buffers are needed.
+  pub(crate) fn draw_fullscreen_quad(&self) {
+    unsafe { self.device.cmd_draw(*self.command_buffer, 3, 1, 0, 0) };
+  }
+
+  pub(crate) fn cmd_push_constants(&self, node_index: u32) {
+    let push_constant = PushConstant { node_index, time: self.time };
     let constant_data = bincode::serialize(&push_constant).unwrap();
 
     unsafe {
@@ -58,31 +225,37 @@ impl<'a> RenderingContext<'a> {
     }
   }
 
-  pub(crate) fn bind_descriptor_buffer(&mut self, descriptor_sets: &impl DescriptorSets) {
+  pub(crate) fn bind_descriptor_buffer(&self, descriptor_sets: &impl DescriptorSets) {
     let (buffer_info, binding_slot) = descriptor_sets.get_descriptor_buffer_info();
-    self.descriptor_buffer_bindings[binding_slot] = Some(buffer_info);
+    let mut bindings = self.descriptor_buffer_bindings.get();
+    bindings[binding_slot] = Some(buffer_info);
+    self.descriptor_buffer_bindings.set(bindings);
     self.bind_descriptor_buffers();
   }
 
   fn bind_descriptor_buffers(&self) {
-    let bindings = self.descriptor_buffer_bindings.iter().copied().flatten().collect::<Vec<vk::DescriptorBufferBindingInfoEXT>>();
+    let bindings = self.descriptor_buffer_bindings.get().into_iter().flatten().collect::<Vec<vk::DescriptorBufferBindingInfoEXT>>();
 
     unsafe { self.device.cmd_bind_descriptor_buffers(*self.command_buffer, &bindings) };
     self.set_descriptor_sets();
   }
 
-  pub(crate) fn set_descriptor_set(&mut self, descriptor_set: &impl DescriptorSet) {
+  pub(crate) fn set_descriptor_set(&self, descriptor_set: &impl DescriptorSet) {
     let (offset, binding_slot) = descriptor_set.get_descriptor_set_info();
-    self.descriptor_buffer_offsets[binding_slot] = Some(offset);
+    let mut offsets = self.descriptor_buffer_offsets.get();
+    offsets[binding_slot] = Some(offset);
+    self.descriptor_buffer_offsets.set(offsets);
     self.set_descriptor_sets();
   }
 
   fn set_descriptor_sets(&self) {
+    let bindings = self.descriptor_buffer_bindings.get();
+    let offsets = self.descriptor_buffer_offsets.get();
     let mut buffer_index = 0;
 
     let binding_slot = GLOBAL_DESCRIPTOR_BINDING;
-    if self.descriptor_buffer_bindings[binding_slot].is_some() {
-      if let Some(offset) = self.descriptor_buffer_offsets[binding_slot] {
+    if bindings[binding_slot].is_some() {
+      if let Some(offset) = offsets[binding_slot] {
         self.set_descriptor_offset(binding_slot as u32, buffer_index, offset);
       }
       // Even if there was no offset to configure for this descriptor set, we still found a binding so we need to progress the buffer index
@@ -90,13 +263,38 @@ impl<'a> RenderingContext<'a> {
     }
 
     let binding_slot = MATERIAL_DESCRIPTOR_BINDING;
-    if self.descriptor_buffer_bindings[binding_slot].is_some() {
-      if let Some(offset) = self.descriptor_buffer_offsets[binding_slot] {
+    if bindings[binding_slot].is_some() {
+      if let Some(offset) = offsets[binding_slot] {
         self.set_descriptor_offset(binding_slot as u32, buffer_index, offset);
       }
       // Even if there was no offset to configure for this descriptor set, we still found a binding so we need to progress the buffer index
       buffer_index += 1;
     }
+
+    let binding_slot = SHADOW_DESCRIPTOR_BINDING;
+    if bindings[binding_slot].is_some() {
+      if let Some(offset) = offsets[binding_slot] {
+        self.set_descriptor_offset(binding_slot as u32, buffer_index, offset);
+      }
+      // Even if there was no offset to configure for this descriptor set, we still found a binding so we need to progress the buffer index
+      buffer_index += 1;
+    }
+
+    let binding_slot = TRANSFORM_DESCRIPTOR_BINDING;
+    if bindings[binding_slot].is_some() {
+      if let Some(offset) = offsets[binding_slot] {
+        self.set_descriptor_offset(binding_slot as u32, buffer_index, offset);
+      }
+      // Even if there was no offset to configure for this descriptor set, we still found a binding so we need to progress the buffer index
+      buffer_index += 1;
+    }
+
+    let binding_slot = SKYBOX_DESCRIPTOR_BINDING;
+    if bindings[binding_slot].is_some() {
+      if let Some(offset) = offsets[binding_slot] {
+        self.set_descriptor_offset(binding_slot as u32, buffer_index, offset);
+      }
+    }
   }
 
   fn set_descriptor_offset(&self, descriptor_binding_slot: u32, buffer_index: u32, offset: u64) {
@@ -0,0 +1,232 @@
+use super::allocator::{Image, ImagePurpose};
+use super::descriptors::{GlobalDescriptorSetInfo, GlobalDescriptorSets, ShadowDescriptorSetInfo, ShadowDescriptorSets, TransformDescriptorSets};
+use super::elements::{CommandPool, Fence, ImageView, Pipeline, PipelineLayout, PipelineSettings, Sampler};
+use super::frame_graph::{FrameGraph, ImageState, ResourceHandle, ShadowPass};
+use super::rendering_context::RenderingContext;
+use super::{Allocator, Device, Vulkan};
+use crate::utils::constants::*;
+use crate::utils::tools::Result;
+
+use ash::vk;
+use log::{debug, trace};
+use nalgebra_glm as glm;
+
+use std::sync::Arc;
+
+/// Width and height of the shadow map in pixels.
+const SHADOW_MAP_SIZE: u32 = 2048;
+/// Half the width of the area around the origin that gets covered by the shadow map.
+const SHADOW_AREA_EXTENT: f32 = 5.0;
+
+/// Depth of the scene as seen from the directional light, the mesh shaders compare against it to find the fragments the light can't reach.
+/// Window frames redraw it with a pass of their frame graph, everything else renders it in a submission of its own first.
+pub(crate) struct ShadowMap {
+  device: Arc<Device>,
+  depth_image_view: ImageView,
+  depth_image: Image,
+  _sampler: Sampler,
+  pipeline_layout: Arc<PipelineLayout>,
+  pipeline: Pipeline,
+  command_pool: CommandPool,
+  fence: Fence,
+  global_descriptor_sets: GlobalDescriptorSets,
+  transform_descriptor_sets: TransformDescriptorSets,
+  shadow_descriptor_sets: ShadowDescriptorSets,
+}
+
+impl ShadowMap {
+  pub(crate) fn new(vulkan: &Vulkan, allocator: &mut Allocator) -> Result<Self> {
+    debug!("Creating shadow map.");
+    let device = vulkan.get_device();
+
+    let depth_image_info = vk::ImageCreateInfo {
+      format: DEPTH_FORMAT,
+      tiling: vk::ImageTiling::OPTIMAL,
+      usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+      image_type: vk::ImageType::TYPE_2D,
+      samples: vk::SampleCountFlags::TYPE_1,
+      mip_levels: 1,
+      array_layers: 1,
+      extent: vk::Extent3D {
+        width: SHADOW_MAP_SIZE,
+        height: SHADOW_MAP_SIZE,
+        depth: 1,
+      },
+      ..Default::default()
+    };
+    let depth_image = allocator.create_image(&[], depth_image_info, ImagePurpose::DepthBuffer)?;
+    let depth_image_view = depth_image.make_image_view()?;
+
+    // Fragments outside of the shadow map sample the white border, so they count as lit
+    let sampler = Sampler::new_shadow(&device, vk::Filter::LINEAR, vk::SamplerAddressMode::CLAMP_TO_BORDER)?;
+
+    let pipeline_layout = vulkan.mesh_pipeline_layout()?;
+    let pipeline = Pipeline::new(&device, &pipeline_layout, &PipelineSettings::shadow())?;
+
+    let command_pool = CommandPool::new(&device, device.graphics_queue_family_index(), 1)?;
+    // Every render waits for the previous one, which doesn't exist for the first
+    let fence = Fence::new(&device, vk::FenceCreateFlags::SIGNALED)?;
+
+    // The light never moves, so the sets describing it only get written once
+    let (view, projection) = light_matrices();
+    let mut global_descriptor_sets = vulkan.get_global_descriptor_set_layout().create_descriptor_sets(allocator, 1)?;
+    global_descriptor_sets[0].update_descriptor(GlobalDescriptorSetInfo {
+      view,
+      projection,
+      environment_intensity: 1.0,
+      has_environment_map: 0,
+    })?;
+    let transform_descriptor_sets = vulkan.get_transform_descriptor_set_layout().create_descriptor_sets(allocator, 1)?;
+
+    let shadow_info = ShadowDescriptorSetInfo {
+      shadow_map: &depth_image_view,
+      sampler: &sampler,
+      light_view_projection: projection * view,
+    };
+    let shadow_descriptor_sets = vulkan.get_shadow_descriptor_set_layout().create_descriptor_sets(allocator, &[shadow_info])?;
+    allocator.flush();
+
+    debug!("Shadow map successfully created!");
+    Ok(Self {
+      device,
+      depth_image_view,
+      depth_image,
+      _sampler: sampler,
+      pipeline_layout,
+      pipeline,
+      command_pool,
+      fence,
+      global_descriptor_sets,
+      transform_descriptor_sets,
+      shadow_descriptor_sets,
+    })
+  }
+
+  /// Set the mesh shaders sample the shadow map through, only valid once the map got rendered.
+  pub(crate) fn descriptor_sets(&self) -> &ShadowDescriptorSets {
+    &self.shadow_descriptor_sets
+  }
+
+  /// Adds the pass drawing the depth of the shadow casters, passes sampling the map have to read the returned image.
+  /// The casters get the transforms of `transform_descriptor_sets[transform_index]`, and `time` has to match the passes sampling the map
+  /// so animated meshes line up with their shadows.
+  pub(crate) fn add_pass<'a>(
+    &'a self,
+    graph: &mut FrameGraph<'a>,
+    transform_descriptor_sets: &'a TransformDescriptorSets,
+    transform_index: usize,
+    time: f32,
+    draw: impl Fn(&RenderingContext) + 'a,
+  ) -> ResourceHandle {
+    // The map gets cleared, but passes submitted before might still be sampling it
+    let sampled_state = ImageState::new(vk::ImageLayout::UNDEFINED, vk::PipelineStageFlags2::FRAGMENT_SHADER, vk::AccessFlags2::NONE);
+    let shadow_map = graph.add_image("Shadow map", *self.depth_image, vk::ImageAspectFlags::DEPTH, sampled_state);
+
+    graph.add_pass(ShadowPass::pass(shadow_map, move |device, command_buffer| {
+      let render_area = vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+          width: SHADOW_MAP_SIZE,
+          height: SHADOW_MAP_SIZE,
+        },
+      };
+
+      let viewport = vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        height: SHADOW_MAP_SIZE as f32,
+        width: SHADOW_MAP_SIZE as f32,
+        max_depth: 1.0,
+        min_depth: 0.0,
+      };
+
+      let depth_attachment = [vk::RenderingAttachmentInfo {
+        image_view: *self.depth_image_view,
+        image_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::STORE,
+        clear_value: vk::ClearValue {
+          depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+        },
+        ..Default::default()
+      }];
+
+      let rendering_info = vk::RenderingInfo {
+        render_area,
+        layer_count: 1,
+        p_depth_attachment: depth_attachment.as_ptr(),
+        ..Default::default()
+      };
+
+      let mut rendering_context = RenderingContext::new(device, &command_buffer, &self.pipeline_layout, time);
+
+      unsafe {
+        device.cmd_begin_rendering(command_buffer, &rendering_info);
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, *self.pipeline);
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+      }
+
+      rendering_context.bind_descriptor_buffer(&self.global_descriptor_sets);
+      rendering_context.set_descriptor_set(&self.global_descriptor_sets[0]);
+      rendering_context.bind_descriptor_buffer(transform_descriptor_sets);
+      rendering_context.set_descriptor_set(&transform_descriptor_sets[transform_index]);
+
+      draw(&rendering_context);
+
+      rendering_context.complete_rendering_command();
+    }));
+
+    shadow_map
+  }
+
+  /// Draws the depth of the shadow casters in a submission of its own, for rendering the scene outside of the frames of a window.
+  pub(crate) fn render(&mut self, transforms: &[glm::Mat4], time: f32, draw: impl Fn(&RenderingContext)) -> Result<()> {
+    trace!("Rendering shadow map");
+    // The previous render might still be reading the command buffer and transforms
+    self.fence.wait()?;
+    self.transform_descriptor_sets[0].update_transforms(transforms)?;
+
+    let device = &self.device;
+    self.command_pool.reset(false)?;
+    let command_buffer = self.command_pool[0];
+
+    let mut graph = FrameGraph::new();
+    let shadow_map = self.add_pass(&mut graph, &self.transform_descriptor_sets, 0, time, draw);
+    // Nothing in this submission samples the map, so it has to be handed over in the layout the mesh shaders read it in
+    graph.set_final_state(
+      shadow_map,
+      ImageState::new(
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        vk::AccessFlags2::SHADER_SAMPLED_READ,
+      ),
+    );
+
+    unsafe { device.begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())? };
+    graph.compile()?.record(device, command_buffer);
+    unsafe { device.end_command_buffer(command_buffer)? };
+
+    let submit_info = vk::SubmitInfo {
+      command_buffer_count: 1,
+      p_command_buffers: &command_buffer,
+      ..Default::default()
+    };
+
+    // Only reset once the submission can't fail anymore, otherwise the next render would wait forever
+    self.fence.reset()?;
+    unsafe { device.queue_submit(device.graphics_queue(), &[submit_info], *self.fence)? };
+    Ok(())
+  }
+}
+
+/// View and projection of the directional light, which shines from the same direction the mesh shaders light the scene from.
+fn light_matrices() -> (glm::Mat4, glm::Mat4) {
+  let light_direction = glm::normalize(&glm::vec3(1.0, 1.0, 1.0));
+  let view = glm::look_at(&(light_direction * SHADOW_AREA_EXTENT), &glm::Vec3::zeros(), &glm::Vec3::z());
+
+  // Depth is compared in the 0..1 range it gets stored in
+  let extent = SHADOW_AREA_EXTENT;
+  let projection = glm::ortho_rh_zo(-extent, extent, -extent, extent, 0.0, 2.0 * extent);
+  (view, projection)
+}
@@ -1,8 +1,15 @@
-use super::allocator::Image;
-use super::descriptors::{GlobalDescriptorSetInfo, GlobalDescriptorSets};
-use super::elements::{CommandPool, Fence, ImageView, Pipeline, PipelineLayout, Semaphore, Surface, Swapchain};
-use super::rendering_context::RenderingContext;
-use super::{Device, Vulkan};
+use super::allocator::{Image, ImagePurpose};
+use super::descriptors::{
+  DescriptorSet, DescriptorSets, GlobalDescriptorSetInfo, GlobalDescriptorSets, PostProcessDescriptorSetInfo, PostProcessDescriptorSetLayout, PostProcessDescriptorSets, SkyboxDescriptorSetInfo,
+  SkyboxDescriptorSets, TransformDescriptorSets,
+};
+use super::elements::{
+  CommandPool, ComputePipeline, FullscreenPipeline, ImageView, Pipeline, PipelineLayout, PipelineSettings, PipelineStatistics, Sampler, Semaphore, StatisticsQueryPool, Surface, Swapchain, TimelineSemaphore,
+};
+use super::frame_graph::{BlitPass, FrameGraph, ImageState, MainPass, OverlayPass};
+use super::rendering_context::{FadePushConstant, MemoryHudPushConstant, ParticlePushConstant, RenderingContext};
+use super::{Allocator, Device, ShadowMap, Vulkan};
+use crate::framework::{ParticleEmitter, Ray};
 use crate::utils::constants::*;
 use crate::utils::tools::{EngineError, Result};
 
@@ -23,19 +30,40 @@ pub(crate) struct Window {
   _color_images: Vec<Image>,
   depth_image_views: Vec<ImageView>,
   color_image_views: Vec<ImageView>,
-  graphics_pipeline_layout: PipelineLayout,
+  graphics_pipeline_layout: Arc<PipelineLayout>,
   graphics_pipeline: Pipeline,
-  command_pool: CommandPool,
+  particle_pipeline_layout: Arc<PipelineLayout>,
+  particle_simulation_pipeline: ComputePipeline,
+  particle_pipeline: Pipeline,
+  fade_pipeline_layout: Arc<PipelineLayout>,
+  fade_pipeline: FullscreenPipeline,
+  post_process_descriptor_set_layout: Arc<PostProcessDescriptorSetLayout>,
+  fade_overlay: Option<FadeOverlay>,
+  memory_hud_pipeline_layout: Arc<PipelineLayout>,
+  memory_hud_pipeline: FullscreenPipeline,
+  memory_hud: Option<MemoryHudPushConstant>,
+  // Only needed when the present queue belongs to a different family than the graphics queue
+  queue_ownership_transfer: Option<QueueOwnershipTransfer>,
+  command_pools: Vec<CommandPool>,
   image_available_semaphores: Vec<Semaphore>,
   render_complete_semaphores: Vec<Semaphore>,
-  frame_fences: Vec<Fence>,
+  // Binary semaphores are still needed for acquiring and presenting, timeline semaphores can't be used with swapchains
+  frame_semaphores: Vec<TimelineSemaphore>,
+  frame_number: u64,
+  statistics_query_pools: Vec<StatisticsQueryPool>,
   frame_index: usize,
   time: std::time::SystemTime,
+  // One per frame in flight, a slot's set gets rewritten as soon as its last frame is done with it
   global_descriptor_sets: GlobalDescriptorSets,
+  outdated_global_descriptor_sets: [bool; MAX_FRAMES_IN_FLIGHT as usize],
+  transform_descriptor_sets: TransformDescriptorSets,
+  skybox_descriptor_sets: SkyboxDescriptorSets,
+  environment_intensity: f32,
+  has_environment_map: bool,
 }
 
 impl Window {
-  pub(crate) fn new(vulkan: &Vulkan, glfw_window: glfw::Window, mut resources: WindowResources) -> Result<Self> {
+  pub(crate) fn new(vulkan: &Vulkan, glfw_window: glfw::Window, resources: WindowResources) -> Result<Self> {
     debug!("Beginning creation of window elements.");
 
     let device = vulkan.get_device();
@@ -50,17 +78,45 @@ impl Window {
     let depth_image_views = create_depth_image_views(&device, &resources.depth_images)?;
     let color_image_views = create_color_image_views(&device, &resources.color_images)?;
 
-    let graphics_pipeline_layout = PipelineLayout::new(&device, &vulkan.get_descriptor_set_layouts())?;
+    let pipeline_layout_cache = vulkan.pipeline_layout_cache();
+    let graphics_pipeline_layout = vulkan.mesh_pipeline_layout()?;
 
-    let graphics_pipeline = Pipeline::new(&device, &graphics_pipeline_layout)?;
+    let graphics_pipeline = Pipeline::new(&device, &graphics_pipeline_layout, &PipelineSettings::mesh())?;
 
-    let command_pool = CommandPool::new(&device, device.graphics_queue_family_index(), MAX_FRAMES_IN_FLIGHT)?;
+    // Particles are read straight from their buffer device address, so their pipelines only need push constants
+    let particle_push_constant_range = vk::PushConstantRange {
+      offset: 0,
+      size: std::mem::size_of::<ParticlePushConstant>() as u32,
+      stage_flags: vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX,
+    };
+    let particle_pipeline_layout = pipeline_layout_cache.get_or_create(&device, &[], &[particle_push_constant_range])?;
+    let particle_simulation_pipeline = ComputePipeline::new(&device, &particle_pipeline_layout, "shaders/particles.comp.spv")?;
+    let particle_pipeline = Pipeline::new(&device, &particle_pipeline_layout, &PipelineSettings::particles())?;
+
+    let fade_push_constant_range = vk::PushConstantRange {
+      offset: 0,
+      size: std::mem::size_of::<FadePushConstant>() as u32,
+      stage_flags: vk::ShaderStageFlags::FRAGMENT,
+    };
+    let post_process_descriptor_set_layout = vulkan.get_post_process_descriptor_set_layout();
+    let fade_pipeline_layout = pipeline_layout_cache.get_or_create(&device, &[**post_process_descriptor_set_layout], &[fade_push_constant_range])?;
+    let fade_pipeline = FullscreenPipeline::new(&device, &fade_pipeline_layout, "shaders/fade.frag.spv", true)?;
+
+    let memory_hud_push_constant_range = vk::PushConstantRange {
+      offset: 0,
+      size: std::mem::size_of::<MemoryHudPushConstant>() as u32,
+      stage_flags: vk::ShaderStageFlags::FRAGMENT,
+    };
+    let memory_hud_pipeline_layout = pipeline_layout_cache.get_or_create(&device, &[], &[memory_hud_push_constant_range])?;
+    let memory_hud_pipeline = FullscreenPipeline::new(&device, &memory_hud_pipeline_layout, "shaders/memory_hud.frag.spv", true)?;
+
+    let command_pools = create_command_pools(&device, MAX_FRAMES_IN_FLIGHT as usize)?;
+    let queue_ownership_transfer = QueueOwnershipTransfer::new(&device, &swapchain_images)?;
 
     let image_available_semaphores = create_semaphores(&device, MAX_FRAMES_IN_FLIGHT as usize)?;
     let render_complete_semaphores = create_semaphores(&device, MAX_FRAMES_IN_FLIGHT as usize)?;
-    let frame_fences = create_fences(&device, MAX_FRAMES_IN_FLIGHT as usize)?;
-
-    resources.global_descriptor_sets[0].update_descriptor(create_global_descriptor_set_info(&swapchain.extent))?;
+    let frame_semaphores = create_timeline_semaphores(&device, MAX_FRAMES_IN_FLIGHT as usize)?;
+    let statistics_query_pools = create_statistics_query_pools(&device, MAX_FRAMES_IN_FLIGHT as usize)?;
 
     debug!("All window elements succesfully created!");
 
@@ -76,30 +132,107 @@ impl Window {
       color_image_views,
       graphics_pipeline_layout,
       graphics_pipeline,
-      command_pool,
+      particle_pipeline_layout,
+      particle_simulation_pipeline,
+      particle_pipeline,
+      fade_pipeline_layout,
+      fade_pipeline,
+      post_process_descriptor_set_layout,
+      fade_overlay: None,
+      memory_hud_pipeline_layout,
+      memory_hud_pipeline,
+      memory_hud: None,
+      queue_ownership_transfer,
+      command_pools,
       image_available_semaphores,
       render_complete_semaphores,
-      frame_fences,
+      frame_semaphores,
+      frame_number: 0,
+      statistics_query_pools,
       global_descriptor_sets: resources.global_descriptor_sets,
+      // Written along with the first transforms of each slot
+      outdated_global_descriptor_sets: [true; MAX_FRAMES_IN_FLIGHT as usize],
+      transform_descriptor_sets: resources.transform_descriptor_sets,
+      skybox_descriptor_sets: resources.skybox_descriptor_sets,
       frame_index: 0,
       time: std::time::SystemTime::now(),
+      environment_intensity: 1.0,
+      has_environment_map: false,
     })
   }
 
-  pub(crate) fn get_rendering_context(&self) -> Result<RenderingContext> {
+  /// Starts recording a new frame, simulating the given particle emitters before any rendering happens.
+  /// The passes drawing the frame get recorded by `end_frame`.
+  pub(crate) fn begin_frame(&self, particle_emitters: &[&ParticleEmitter], delta_time: f32) -> Result<FrameContext> {
+    trace!("Beginning frame: {}", self.frame_index);
     let device = &self.device;
-    let fence = &self.frame_fences[self.frame_index];
-    unsafe { device.wait_for_fences(&[**fence], true, u64::MAX) }?;
+    let image_available = &self.image_available_semaphores[self.frame_index];
+    self.wait_for_frame_slot()?;
 
-    let command_buffer = self.command_pool[self.frame_index];
-
-    unsafe {
-      device.reset_fences(&[**fence])?;
-      device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+    let (image_index, suboptimal) = match unsafe { device.acquire_next_image(*self.swapchain, u64::MAX, **image_available, vk::Fence::null()) } {
+      Ok(result) => result,
+      Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return Err(EngineError::OldSwapchain),
+      Err(e) => return Err(e.into()),
     };
 
-    let begin_info = vk::CommandBufferBeginInfo::default();
+    self.begin_command_buffer(particle_emitters, delta_time)?;
+
+    Ok(FrameContext { image_index, suboptimal })
+  }
+
+  /// Uploads the world space transforms of all scene nodes for the upcoming frame, has to be called before `begin_frame`.
+  /// Changes to the global descriptor set since the last frame of this slot get written along with them.
+  pub(crate) fn upload_transforms(&mut self, transforms: &[glm::Mat4]) -> Result<()> {
+    // The buffers of this slot might still be read by the last frame that used it
+    self.wait_for_frame_slot()?;
+
+    if self.outdated_global_descriptor_sets[self.frame_index] {
+      let info = create_global_descriptor_set_info(&self.swapchain.extent, self.environment_intensity, self.has_environment_map);
+      self.global_descriptor_sets[self.frame_index].update_descriptor(info)?;
+      self.outdated_global_descriptor_sets[self.frame_index] = false;
+    }
+
+    self.transform_descriptor_sets[self.frame_index].update_transforms(transforms)
+  }
+
+  /// Blocks until the GPU finished every submitted frame, for resources that are shared between all frame slots.
+  pub(crate) fn wait_for_frames_in_flight(&self) -> Result<()> {
+    // The last frame of every slot is one of the frames right before the current frame number
+    for frame_number in self.frame_number.saturating_sub(MAX_FRAMES_IN_FLIGHT as u64)..self.frame_number {
+      let frame_index = (frame_number % MAX_FRAMES_IN_FLIGHT as u64) as usize;
+      self.frame_semaphores[frame_index].wait_value(frame_number + 1, u64::MAX)?;
+    }
+
+    Ok(())
+  }
+
+  /// Milliseconds since the window got created, the mesh shaders animate with it.
+  pub(crate) fn elapsed_time(&self) -> f32 {
+    std::time::SystemTime::now().duration_since(self.time).unwrap().as_millis() as f32
+  }
+
+  fn wait_for_frame_slot(&self) -> Result<()> {
+    // Each frame signals its slot's semaphore with its frame number + 1, wait for the last frame that used this slot
+    let previous_frame_value = (self.frame_number + 1).saturating_sub(MAX_FRAMES_IN_FLIGHT as u64);
+    self.frame_semaphores[self.frame_index].wait_value(previous_frame_value, u64::MAX)
+  }
 
+  fn begin_command_buffer(&self, particle_emitters: &[&ParticleEmitter], delta_time: f32) -> Result<()> {
+    // Every frame slot has its own pool, so resetting it can't touch the command buffer of a frame still in flight
+    let command_pool = &self.command_pools[self.frame_index];
+    command_pool.reset(false)?;
+    let command_buffer = command_pool[0];
+
+    unsafe { self.device.begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())? };
+
+    // Compute dispatches aren't allowed inside of a render pass, so the simulation has to be recorded first
+    self.record_particle_simulation(&command_buffer, particle_emitters, delta_time);
+    Ok(())
+  }
+
+  /// Records the main pass into the attachments of the current frame slot, `draw` gets the context with the mesh pipeline bound.
+  fn record_main_pass(&self, command_buffer: vk::CommandBuffer, time: f32, draw: impl Fn(&RenderingContext)) {
+    let device = &self.device;
     let render_area = vk::Rect2D {
       offset: vk::Offset2D { x: 0, y: 0 },
       extent: self.swapchain.extent,
@@ -154,11 +287,13 @@ impl Window {
       extent: self.swapchain.extent,
     };
 
-    let time = std::time::SystemTime::now().duration_since(self.time).unwrap().as_millis() as f32;
-    let mut rendering_context = RenderingContext::new(device, &self.command_pool[self.frame_index], &self.graphics_pipeline_layout, time);
+    let mut rendering_context = RenderingContext::new(device, &command_buffer, &self.graphics_pipeline_layout, time);
+    let statistics_query_pool = &self.statistics_query_pools[self.frame_index];
+
+    // Measures the main pass only, the shadow pass draws the scene a second time
+    statistics_query_pool.cmd_begin(&command_buffer);
 
     unsafe {
-      device.begin_command_buffer(command_buffer, &begin_info)?;
       device.cmd_begin_rendering(command_buffer, &rendering_info);
       device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, *self.graphics_pipeline);
       device.cmd_set_viewport(command_buffer, 0, &[viewport]);
@@ -166,132 +301,277 @@ impl Window {
     }
 
     rendering_context.bind_descriptor_buffer(&self.global_descriptor_sets);
-    rendering_context.set_descriptor_set(&self.global_descriptor_sets[0]);
-
-    Ok(rendering_context)
-  }
-
-  fn transition_color_image(&self, command_buffer: &vk::CommandBuffer, image: &vk::Image, stage: RenderingStage) {
-    let old_layout;
-    let new_layout;
-    let src_stage_mask;
-    let dst_stage_mask;
-    let src_access_mask;
-    let dst_access_mask;
-
-    match stage {
-      RenderingStage::BeforeCopy => {
-        old_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
-        new_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
-        src_stage_mask = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
-        dst_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
-        src_access_mask = vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
-        dst_access_mask = vk::AccessFlags::NONE;
-      }
-      RenderingStage::AfterCopy => {
-        old_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
-        new_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
-        src_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
-        dst_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
-        src_access_mask = vk::AccessFlags::NONE;
-        dst_access_mask = vk::AccessFlags::NONE;
-      }
-    }
+    rendering_context.set_descriptor_set(&self.global_descriptor_sets[self.frame_index]);
+    rendering_context.bind_descriptor_buffer(&self.transform_descriptor_sets);
+    rendering_context.set_descriptor_set(&self.transform_descriptor_sets[self.frame_index]);
+    // Bound even without an environment map, the shaders only sample it when the global set says there is one
+    rendering_context.bind_descriptor_buffer(&self.skybox_descriptor_sets);
+    rendering_context.set_descriptor_set(&self.skybox_descriptor_sets[0]);
 
-    let image_barrier = vk::ImageMemoryBarrier {
-      src_access_mask,
-      dst_access_mask,
-      old_layout,
-      new_layout,
-      src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-      dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-      image: *image,
-      subresource_range: vk::ImageSubresourceRange {
-        aspect_mask: vk::ImageAspectFlags::COLOR,
-        base_mip_level: 0,
-        level_count: 1,
-        base_array_layer: 0,
-        layer_count: 1,
-      },
+    draw(&rendering_context);
+
+    rendering_context.complete_rendering_command();
+    statistics_query_pool.cmd_end(&command_buffer);
+  }
+
+  /// Draws the bars of the memory HUD over the finished main pass.
+  fn record_memory_hud_pass(&self, command_buffer: vk::CommandBuffer, memory_hud: &MemoryHudPushConstant) {
+    let device = &self.device;
+    let render_area = vk::Rect2D {
+      offset: vk::Offset2D { x: 0, y: 0 },
+      extent: self.swapchain.extent,
+    };
+
+    let color_attachment = [vk::RenderingAttachmentInfo {
+      image_view: *self.color_image_views[self.frame_index],
+      image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+      load_op: vk::AttachmentLoadOp::LOAD,
+      store_op: vk::AttachmentStoreOp::STORE,
+      ..Default::default()
+    }];
+
+    // The main pass doesn't store its depth, the HUD doesn't test against it either
+    let depth_attachment = [vk::RenderingAttachmentInfo {
+      image_view: *self.depth_image_views[self.frame_index],
+      image_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+      load_op: vk::AttachmentLoadOp::DONT_CARE,
+      store_op: vk::AttachmentStoreOp::DONT_CARE,
+      ..Default::default()
+    }];
+
+    let rendering_info = vk::RenderingInfo {
+      render_area,
+      layer_count: 1,
+      color_attachment_count: 1,
+      p_color_attachments: color_attachment.as_ptr(),
+      p_depth_attachment: depth_attachment.as_ptr(),
       ..Default::default()
     };
 
+    let viewport = vk::Viewport {
+      x: 0.0,
+      y: 0.0,
+      height: self.swapchain.extent.height as f32,
+      width: self.swapchain.extent.width as f32,
+      max_depth: 1.0,
+      min_depth: 0.0,
+    };
+
+    let constant_data = bincode::serialize(memory_hud).unwrap();
+
     unsafe {
-      self
-        .device
-        .cmd_pipeline_barrier(*command_buffer, src_stage_mask, dst_stage_mask, vk::DependencyFlags::empty(), &[], &[], &[image_barrier]);
+      device.cmd_begin_rendering(command_buffer, &rendering_info);
+      device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, *self.memory_hud_pipeline);
+      device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+      device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+      device.cmd_push_constants(command_buffer, **self.memory_hud_pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, &constant_data);
+      device.cmd_draw(command_buffer, 3, 1, 0, 0);
+      device.cmd_end_rendering(command_buffer);
     }
   }
 
-  fn transition_swapchain_image(&self, command_buffer: &vk::CommandBuffer, image: &vk::Image, stage: RenderingStage) {
-    let old_layout;
-    let new_layout;
-    let src_stage_mask;
-    let dst_stage_mask;
-    let src_access_mask;
-    let dst_access_mask;
-
-    match stage {
-      RenderingStage::BeforeCopy => {
-        old_layout = vk::ImageLayout::UNDEFINED;
-        new_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-        src_stage_mask = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
-        dst_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
-        src_access_mask = vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
-        dst_access_mask = vk::AccessFlags::NONE;
+  fn record_particle_simulation(&self, command_buffer: &vk::CommandBuffer, particle_emitters: &[&ParticleEmitter], delta_time: f32) {
+    if particle_emitters.is_empty() {
+      return;
+    }
+
+    let device = &self.device;
+    let view_projection = self.view_projection();
+
+    // The previous frame may still be drawing the particles we are about to overwrite
+    let draw_to_simulation = vk::MemoryBarrier {
+      src_access_mask: vk::AccessFlags::SHADER_READ,
+      dst_access_mask: vk::AccessFlags::SHADER_WRITE,
+      ..Default::default()
+    };
+
+    let simulation_to_draw = vk::MemoryBarrier {
+      src_access_mask: vk::AccessFlags::SHADER_WRITE,
+      dst_access_mask: vk::AccessFlags::SHADER_READ,
+      ..Default::default()
+    };
+
+    unsafe {
+      device.cmd_pipeline_barrier(
+        *command_buffer,
+        vk::PipelineStageFlags::VERTEX_SHADER,
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::DependencyFlags::empty(),
+        &[draw_to_simulation],
+        &[],
+        &[],
+      );
+      device.cmd_bind_pipeline(*command_buffer, vk::PipelineBindPoint::COMPUTE, *self.particle_simulation_pipeline);
+
+      for emitter in particle_emitters {
+        self.cmd_push_particle_constants(command_buffer, emitter, view_projection, delta_time);
+        device.cmd_dispatch(*command_buffer, emitter.system.max_particles.div_ceil(PARTICLE_WORKGROUP_SIZE), 1, 1);
       }
-      RenderingStage::AfterCopy => {
-        old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-        new_layout = vk::ImageLayout::PRESENT_SRC_KHR;
-        src_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
-        dst_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
-        src_access_mask = vk::AccessFlags::NONE;
-        dst_access_mask = vk::AccessFlags::NONE;
+
+      device.cmd_pipeline_barrier(
+        *command_buffer,
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::PipelineStageFlags::VERTEX_SHADER,
+        vk::DependencyFlags::empty(),
+        &[simulation_to_draw],
+        &[],
+        &[],
+      );
+    }
+  }
+
+  /// Draws a billboard per particle of every emitter, has to be called after the scene since it replaces the bound pipeline.
+  pub(crate) fn draw_particles(&self, rendering_context: &RenderingContext, particle_emitters: &[&ParticleEmitter]) {
+    if particle_emitters.is_empty() {
+      return;
+    }
+
+    let device = &self.device;
+    let command_buffer = rendering_context.command_buffer();
+    let view_projection = self.view_projection();
+
+    unsafe {
+      device.cmd_bind_pipeline(*command_buffer, vk::PipelineBindPoint::GRAPHICS, *self.particle_pipeline);
+
+      for emitter in particle_emitters {
+        self.cmd_push_particle_constants(command_buffer, emitter, view_projection, 0.0);
+        // Each instance is a single particle, the 6 vertices of its quad are generated in the vertex shader
+        device.cmd_draw(*command_buffer, 6, emitter.system.max_particles, 0, 0);
       }
     }
+  }
 
-    let image_barrier = vk::ImageMemoryBarrier {
-      src_access_mask,
-      dst_access_mask,
-      old_layout,
-      new_layout,
-      src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-      dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-      image: *image,
-      subresource_range: vk::ImageSubresourceRange {
-        aspect_mask: vk::ImageAspectFlags::COLOR,
-        base_mip_level: 0,
-        level_count: 1,
-        base_array_layer: 0,
-        layer_count: 1,
+  /// Bars drawn over the following frames by the memory HUD pass, `None` leaves the pass out.
+  pub(crate) fn set_memory_hud(&mut self, memory_hud: Option<MemoryHudPushConstant>) {
+    self.memory_hud = memory_hud;
+  }
+
+  /// Copies the next recorded frame, so `draw_fade_overlay` can fade it out over the frames after it.
+  pub(crate) fn capture_next_frame(&mut self, allocator: &mut Allocator) -> Result<()> {
+    // The previous overlay might still be sampled by frames in flight
+    self.device.wait_idle();
+    self.fade_overlay = None;
+
+    let extent = self.swapchain.extent;
+    let image_create_info = vk::ImageCreateInfo {
+      // Same format as the color attachments, so the frame can be blitted over as is
+      format: vk::Format::R8G8B8A8_SRGB,
+      tiling: vk::ImageTiling::OPTIMAL,
+      usage: vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+      image_type: vk::ImageType::TYPE_2D,
+      samples: vk::SampleCountFlags::TYPE_1,
+      mip_levels: 1,
+      array_layers: 1,
+      extent: vk::Extent3D {
+        width: extent.width,
+        height: extent.height,
+        depth: 1,
       },
       ..Default::default()
     };
 
+    let image = allocator.create_image(&[], image_create_info, ImagePurpose::ColorAttachment)?;
+    let image_view = image.make_image_view()?;
+    let sampler = Sampler::new(
+      &self.device,
+      vk::Filter::NEAREST,
+      vk::Filter::NEAREST,
+      vk::SamplerMipmapMode::NEAREST,
+      vk::SamplerAddressMode::CLAMP_TO_EDGE,
+      vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    )?;
+
+    let descriptor_info = PostProcessDescriptorSetInfo {
+      image: &image_view,
+      sampler: &sampler,
+    };
+    let descriptor_sets = self.post_process_descriptor_set_layout.create_descriptor_sets(allocator, &[descriptor_info])?;
+    allocator.flush();
+
+    self.fade_overlay = Some(FadeOverlay {
+      image,
+      _image_view: image_view,
+      _sampler: sampler,
+      descriptor_sets,
+      capture_frame: self.frame_number,
+    });
+
+    Ok(())
+  }
+
+  /// Draws the captured frame over the current one, has to be the last draw of the frame since it rebinds the descriptor buffers.
+  pub(crate) fn draw_fade_overlay(&self, rendering_context: &RenderingContext, alpha: f32) {
+    let Some(fade_overlay) = &self.fade_overlay else {
+      return;
+    };
+
+    // The overlay only holds the captured frame once that frame has been recorded
+    if self.frame_number <= fade_overlay.capture_frame {
+      return;
+    }
+
+    let device = &self.device;
+    let command_buffer = rendering_context.command_buffer();
+    let (binding_info, _) = fade_overlay.descriptor_sets.get_descriptor_buffer_info();
+    let (offset, binding_slot) = fade_overlay.descriptor_sets[0].get_descriptor_set_info();
+    let constant_data = bincode::serialize(&FadePushConstant { alpha: alpha.clamp(0.0, 1.0) }).unwrap();
+
+    unsafe {
+      device.cmd_bind_pipeline(*command_buffer, vk::PipelineBindPoint::GRAPHICS, *self.fade_pipeline);
+      device.cmd_bind_descriptor_buffers(*command_buffer, &[binding_info]);
+      device.cmd_set_descriptor_buffer_offsets(*command_buffer, vk::PipelineBindPoint::GRAPHICS, **self.fade_pipeline_layout, binding_slot as u32, &[0], &[offset]);
+      device.cmd_push_constants(*command_buffer, **self.fade_pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, &constant_data);
+    }
+
+    rendering_context.draw_fullscreen_quad();
+  }
+
+  fn cmd_push_particle_constants(&self, command_buffer: &vk::CommandBuffer, emitter: &ParticleEmitter, view_projection: glm::Mat4, delta_time: f32) {
+    let push_constant = ParticlePushConstant {
+      view_projection,
+      particle_buffer: emitter.buffer.device_address(),
+      delta_time,
+      lifetime: emitter.system.lifetime,
+      emitter_position: emitter.system.emitter_position,
+      max_particles: emitter.system.max_particles,
+    };
+    let constant_data = bincode::serialize(&push_constant).unwrap();
+
     unsafe {
-      self
-        .device
-        .cmd_pipeline_barrier(*command_buffer, src_stage_mask, dst_stage_mask, vk::DependencyFlags::empty(), &[], &[], &[image_barrier]);
+      self.device.cmd_push_constants(
+        *command_buffer,
+        **self.particle_pipeline_layout,
+        vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX,
+        0,
+        &constant_data,
+      )
     }
   }
 
-  pub(crate) fn draw_frame(&self, mut rendering_context: RenderingContext) -> Result<()> {
+  fn view_projection(&self) -> glm::Mat4 {
+    let (view, projection) = camera_matrices(&self.swapchain.extent);
+    projection * view
+  }
+
+  /// Records the passes of the frame and submits it. `draw` records the draw calls of the main pass, and with a shadow map given,
+  /// `draw_shadow_casters` the ones of the shadow pass the main pass samples.
+  pub(crate) fn end_frame(&self, frame: FrameContext, shadow_map: Option<&ShadowMap>, draw_shadow_casters: impl Fn(&RenderingContext), draw: impl Fn(&RenderingContext)) -> Result<()> {
+    let FrameContext { image_index, suboptimal } = frame;
+    let command_buffer = self.command_pools[self.frame_index][0];
+    // Shared by both passes, so animated meshes line up with their shadows
+    let time = self.elapsed_time();
+
     unsafe {
       trace!("Drawing frame: {}", self.frame_index);
       let device = &self.device;
       let graphics_queue = &self.device.graphics_queue();
       let image_available = &self.image_available_semaphores[self.frame_index];
       let render_complete = &self.render_complete_semaphores[self.frame_index];
-      let fence = &self.frame_fences[self.frame_index];
-
-      let (image_index, recreate_swapchain) = device.acquire_next_image(*self.swapchain, u64::MAX, **image_available, vk::Fence::null())?;
+      let frame_semaphore = &self.frame_semaphores[self.frame_index];
 
-      let swapchain_image = &self.swapchain_images[image_index as usize];
-      let color_image = &self._color_images[self.frame_index];
-      rendering_context.complete_rendering_command();
-
-      self.transition_swapchain_image(rendering_context.command_buffer(), swapchain_image, RenderingStage::BeforeCopy);
-      self.transition_color_image(rendering_context.command_buffer(), &color_image, RenderingStage::BeforeCopy);
+      let swapchain_image = self.swapchain_images[image_index as usize];
+      let color_image = *self._color_images[self.frame_index];
+      let depth_image = *self._depth_images[self.frame_index];
 
       let swapchain_extent = self.swapchain.extent;
       let layers = vk::ImageSubresourceLayers {
@@ -317,38 +597,115 @@ impl Window {
         dst_offsets: offsets,
       };
 
-      // copy the content of color attachment to swapchain image
-      device.cmd_blit_image(
-        *rendering_context.command_buffer(),
-        **color_image,
-        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-        *swapchain_image,
-        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        &[regions],
-        vk::Filter::NEAREST,
+      let attachment_state = ImageState::new(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::NONE);
+      let depth_state = ImageState::new(
+        vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+        vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+        vk::AccessFlags2::NONE,
       );
+      // The submit waits for the swapchain image at the color attachment output stage
+      let acquired_state = ImageState::new(vk::ImageLayout::UNDEFINED, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::NONE);
+
+      let mut graph = FrameGraph::new();
+      let color = graph.add_image("Color attachment", color_image, vk::ImageAspectFlags::COLOR, attachment_state);
+      let depth = graph.add_image("Depth attachment", depth_image, vk::ImageAspectFlags::DEPTH, depth_state);
+      let swapchain = graph.add_image("Swapchain image", swapchain_image, vk::ImageAspectFlags::COLOR, acquired_state);
+
+      // The next frame starts rendering into the color attachment again
+      graph.set_final_state(
+        color,
+        ImageState::new(
+          vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+          vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+          vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        ),
+      );
+      graph.set_final_state(
+        swapchain,
+        ImageState::new(vk::ImageLayout::PRESENT_SRC_KHR, vk::PipelineStageFlags2::BOTTOM_OF_PIPE, vk::AccessFlags2::NONE),
+      );
+      // The matching acquire is recorded by `QueueOwnershipTransfer`
+      if self.queue_ownership_transfer.is_some() {
+        graph.release_to_queue_family(swapchain, self.device.graphics_queue_family_index(), self.device.present_queue_family_index());
+      }
+
+      let shadow_map = shadow_map.map(|shadow_map| shadow_map.add_pass(&mut graph, &self.transform_descriptor_sets, self.frame_index, time, &draw_shadow_casters));
+      graph.add_pass(MainPass::pass(color, depth, shadow_map, |_, command_buffer| self.record_main_pass(command_buffer, time, &draw)));
+      if let Some(memory_hud) = &self.memory_hud {
+        graph.add_pass(OverlayPass::pass(color, depth, |_, command_buffer| self.record_memory_hud_pass(command_buffer, memory_hud)));
+      }
+
+      // copy the content of color attachment to swapchain image
+      graph.add_pass(BlitPass::pass(color, swapchain, |device, command_buffer| {
+        device.cmd_blit_image(
+          command_buffer,
+          color_image,
+          vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+          swapchain_image,
+          vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+          &[regions],
+          vk::Filter::NEAREST,
+        );
+      }));
+
+      if let Some(fade_overlay) = self.fade_overlay.as_ref().filter(|overlay| overlay.capture_frame == self.frame_number) {
+        let overlay_image = *fade_overlay.image;
+        let overlay = graph.add_image("Fade overlay", overlay_image, vk::ImageAspectFlags::COLOR, ImageState::undefined());
+        graph.set_final_state(
+          overlay,
+          ImageState::new(
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+          ),
+        );
+
+        graph.add_pass(BlitPass::pass(color, overlay, move |device, command_buffer| {
+          device.cmd_blit_image(
+            command_buffer,
+            color_image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            overlay_image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[regions],
+            vk::Filter::NEAREST,
+          );
+        }));
+      }
 
-      self.transition_swapchain_image(rendering_context.command_buffer(), swapchain_image, RenderingStage::AfterCopy);
-      self.transition_color_image(rendering_context.command_buffer(), &color_image, RenderingStage::AfterCopy);
+      graph.compile()?.record(device, command_buffer);
+      device.end_command_buffer(command_buffer)?;
 
-      rendering_context.end_command_buffer()?;
+      // Values for binary semaphores are ignored, but every semaphore still needs a slot
+      let signal_semaphores = [**render_complete, **frame_semaphore];
+      let signal_values = [0, self.frame_number + 1];
+      let wait_values = [0];
+      let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+        .signal_semaphore_values(&signal_values)
+        .wait_semaphore_values(&wait_values);
 
       let submit_info = vk::SubmitInfo {
+        p_next: &mut *timeline_info as *mut vk::TimelineSemaphoreSubmitInfo as *const std::ffi::c_void,
         command_buffer_count: 1,
-        p_command_buffers: rendering_context.command_buffer(),
-        signal_semaphore_count: 1,
-        p_signal_semaphores: &**render_complete,
+        p_command_buffers: &command_buffer,
+        signal_semaphore_count: signal_semaphores.len() as u32,
+        p_signal_semaphores: signal_semaphores.as_ptr(),
         wait_semaphore_count: 1,
         p_wait_semaphores: &**image_available,
         p_wait_dst_stage_mask: &vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
         ..Default::default()
       };
 
-      device.queue_submit(*graphics_queue, &[submit_info], **fence)?;
+      device.queue_submit(*graphics_queue, &[submit_info], vk::Fence::null())?;
+
+      let mut present_wait_semaphore = **render_complete;
+      if let Some(queue_ownership_transfer) = &self.queue_ownership_transfer {
+        present_wait_semaphore = **queue_ownership_transfer.submit(image_index as usize, self.frame_index, render_complete)?;
+      }
 
       let present_info = vk::PresentInfoKHR {
         wait_semaphore_count: 1,
-        p_wait_semaphores: &**render_complete,
+        p_wait_semaphores: &present_wait_semaphore,
         swapchain_count: 1,
         p_swapchains: &*self.swapchain,
         p_image_indices: &image_index,
@@ -356,9 +713,13 @@ impl Window {
         ..Default::default()
       };
 
-      device.queue_present(*graphics_queue, &present_info)?;
+      let present_suboptimal = match device.queue_present(device.present_queue(), &present_info) {
+        Ok(suboptimal) => suboptimal,
+        Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
+        Err(e) => return Err(e.into()),
+      };
 
-      if recreate_swapchain {
+      if suboptimal || present_suboptimal {
         return Err(EngineError::OldSwapchain);
       }
 
@@ -366,13 +727,21 @@ impl Window {
     }
   }
 
+  /// Statistics of the last frame that used the current frame slot, if the GPU has finished it.
+  pub(crate) fn read_pipeline_statistics(&self) -> Result<Option<PipelineStatistics>> {
+    self.statistics_query_pools[self.frame_index].get_results()
+  }
+
   pub(crate) fn progress_frame(&mut self) {
     self.frame_index = (self.frame_index + 1) % MAX_FRAMES_IN_FLIGHT as usize;
+    self.frame_number += 1;
   }
 
   pub(crate) fn recreate_swapchain(&mut self) -> Result<()> {
     debug!("Recreating swapchain!");
     self.device.wait_idle();
+    // The captured frame no longer matches the size of the swapchain
+    self.fade_overlay = None;
 
     // create new swapchain related elements
     let window_framebuffer = FramebufferSize::from(self.glfw_window.get_framebuffer_size());
@@ -382,7 +751,8 @@ impl Window {
     // let swapchain_image_views = create_swapchain_image_views(&self.device, &swapchain_images, &swapchain.format)?;
 
     // put the new elements into the renderer
-    self.global_descriptor_sets[0].update_descriptor(create_global_descriptor_set_info(&swapchain.extent))?;
+    self.outdated_global_descriptor_sets = [true; MAX_FRAMES_IN_FLIGHT as usize];
+    self.queue_ownership_transfer = QueueOwnershipTransfer::new(&self.device, &swapchain_images)?;
     self.swapchain = swapchain;
     self.swapchain_images = swapchain_images;
     // self.swapchain_image_views = swapchain_image_views;
@@ -391,18 +761,127 @@ impl Window {
     Ok(())
   }
 
+  /// Takes effect with the next `upload_transforms` of every frame slot.
+  pub(crate) fn update_global_descriptor(&mut self, environment_intensity: f32) {
+    self.environment_intensity = environment_intensity;
+    self.outdated_global_descriptor_sets = [true; MAX_FRAMES_IN_FLIGHT as usize];
+  }
+
+  /// Lets the drawn frames sample `environment_map` for their ambient light, without one they fall back to a constant term.
+  pub(crate) fn set_environment_map(&mut self, environment_map: Option<SkyboxDescriptorSetInfo>) -> Result<()> {
+    // The skybox is shared between frames in flight
+    self.wait_for_frames_in_flight()?;
+
+    // Without an environment map the skybox keeps pointing at the old one, the shaders stop sampling it though
+    if let Some(environment_map) = &environment_map {
+      self.skybox_descriptor_sets.update_environment_map(0, environment_map)?;
+    }
+
+    self.has_environment_map = environment_map.is_some();
+    self.outdated_global_descriptor_sets = [true; MAX_FRAMES_IN_FLIGHT as usize];
+    Ok(())
+  }
+
+  /// World space ray through the current cursor position, using the same camera the frames are drawn with.
+  pub(crate) fn cursor_ray(&self) -> Ray {
+    let (view, projection) = camera_matrices(&self.swapchain.extent);
+    Ray::from_cursor(self.glfw_window.get_cursor_pos(), self.glfw_window.get_size(), &view, &projection)
+  }
+
   pub(crate) fn should_close(&self) -> bool {
     self.glfw_window.should_close()
   }
 }
 
-//-----------------------------------Helpers----------------------------------------------
+/// Acquires the swapchain images on the present queue after the graphics queue released them.
+/// The barrier of every image never changes, so the command buffers only get recorded once per swapchain.
+struct QueueOwnershipTransfer {
+  device: Arc<Device>,
+  command_pool: CommandPool,
+  transfer_complete_semaphores: Vec<Semaphore>,
+}
 
-enum RenderingStage {
-  BeforeCopy,
-  AfterCopy,
+impl QueueOwnershipTransfer {
+  fn new(device: &Arc<Device>, swapchain_images: &[vk::Image]) -> Result<Option<Self>> {
+    if device.graphics_queue_family_index() == device.present_queue_family_index() {
+      return Ok(None);
+    }
+
+    debug!("Graphics and present queue families differ, recording swapchain image ownership transfers.");
+    let command_pool = CommandPool::new(device, device.present_queue_family_index(), swapchain_images.len() as u32)?;
+    let transfer_complete_semaphores = create_semaphores(device, MAX_FRAMES_IN_FLIGHT as usize)?;
+
+    for (index, image) in swapchain_images.iter().enumerate() {
+      let mut image_barrier = color_image_barrier(
+        *image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::PRESENT_SRC_KHR,
+        vk::AccessFlags::NONE,
+        vk::AccessFlags::NONE,
+      );
+      image_barrier.src_queue_family_index = device.graphics_queue_family_index();
+      image_barrier.dst_queue_family_index = device.present_queue_family_index();
+
+      unsafe {
+        device.begin_command_buffer(command_pool[index], &vk::CommandBufferBeginInfo::default())?;
+        device.cmd_pipeline_barrier(
+          command_pool[index],
+          vk::PipelineStageFlags::TOP_OF_PIPE,
+          vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+          vk::DependencyFlags::empty(),
+          &[],
+          &[],
+          &[image_barrier],
+        );
+        device.end_command_buffer(command_pool[index])?;
+      }
+    }
+
+    Ok(Some(Self {
+      device: device.clone(),
+      command_pool,
+      transfer_complete_semaphores,
+    }))
+  }
+
+  /// Submits the acquire of a swapchain image once rendering is complete, presenting has to wait on the returned semaphore.
+  fn submit(&self, image_index: usize, frame_index: usize, render_complete: &Semaphore) -> Result<&Semaphore> {
+    let transfer_complete = &self.transfer_complete_semaphores[frame_index];
+
+    let submit_info = vk::SubmitInfo {
+      command_buffer_count: 1,
+      p_command_buffers: &self.command_pool[image_index],
+      wait_semaphore_count: 1,
+      p_wait_semaphores: &**render_complete,
+      p_wait_dst_stage_mask: &vk::PipelineStageFlags::ALL_COMMANDS,
+      signal_semaphore_count: 1,
+      p_signal_semaphores: &**transfer_complete,
+      ..Default::default()
+    };
+
+    unsafe { self.device.queue_submit(self.device.present_queue(), &[submit_info], vk::Fence::null())? };
+    Ok(transfer_complete)
+  }
+}
+
+/// Copy of a single frame, drawn over the frames following it to fade it out.
+struct FadeOverlay {
+  image: Image,
+  _image_view: ImageView,
+  _sampler: Sampler,
+  descriptor_sets: PostProcessDescriptorSets,
+  /// Frame number of the frame that gets copied into the image.
+  capture_frame: u64,
 }
 
+/// Scope of a single frame, from acquiring the swapchain image to presenting it.
+pub(crate) struct FrameContext {
+  image_index: u32,
+  suboptimal: bool,
+}
+
+//-----------------------------------Helpers----------------------------------------------
+
 pub(crate) struct FramebufferSize(pub(crate) i32, pub(crate) i32);
 impl From<(i32, i32)> for FramebufferSize {
   fn from(input: (i32, i32)) -> Self {
@@ -415,6 +894,8 @@ pub(crate) struct WindowResources {
   pub(crate) depth_images: Vec<Image>,
   pub(crate) color_images: Vec<Image>,
   pub(crate) global_descriptor_sets: GlobalDescriptorSets,
+  pub(crate) transform_descriptor_sets: TransformDescriptorSets,
+  pub(crate) skybox_descriptor_sets: SkyboxDescriptorSets,
 }
 
 // fn create_swapchain_image_views(device: &Arc<Device>, images: &Vec<vk::Image>, format: &vk::Format) -> Result<Vec<ImageView>> {
@@ -468,33 +949,92 @@ fn create_semaphores(device: &Arc<Device>, count: usize) -> Result<Vec<Semaphore
   Ok(semaphores)
 }
 
-fn create_fences(device: &Arc<Device>, count: usize) -> Result<Vec<Fence>> {
-  debug!("Creating {} fences.", count);
-  let mut fences: Vec<Fence> = Vec::with_capacity(count);
+fn color_image_barrier(
+  image: vk::Image,
+  old_layout: vk::ImageLayout,
+  new_layout: vk::ImageLayout,
+  src_access_mask: vk::AccessFlags,
+  dst_access_mask: vk::AccessFlags,
+) -> vk::ImageMemoryBarrier {
+  vk::ImageMemoryBarrier {
+    src_access_mask,
+    dst_access_mask,
+    old_layout,
+    new_layout,
+    src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+    dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+    image,
+    subresource_range: vk::ImageSubresourceRange {
+      aspect_mask: vk::ImageAspectFlags::COLOR,
+      base_mip_level: 0,
+      level_count: 1,
+      base_array_layer: 0,
+      layer_count: 1,
+    },
+    ..Default::default()
+  }
+}
+
+fn create_command_pools(device: &Arc<Device>, count: usize) -> Result<Vec<CommandPool>> {
+  debug!("Creating {} command pools.", count);
+  let mut command_pools: Vec<CommandPool> = Vec::with_capacity(count);
+
+  for _ in 0..count {
+    let command_pool = CommandPool::new_transient(device, device.graphics_queue_family_index(), 1)?;
+    command_pools.push(command_pool);
+  }
+
+  Ok(command_pools)
+}
+
+fn create_timeline_semaphores(device: &Arc<Device>, count: usize) -> Result<Vec<TimelineSemaphore>> {
+  debug!("Creating {} timeline semaphores.", count);
+  let mut semaphores: Vec<TimelineSemaphore> = Vec::with_capacity(count);
+
+  for _ in 0..count {
+    let semaphore = TimelineSemaphore::new(device, 0)?;
+    semaphores.push(semaphore);
+  }
+
+  Ok(semaphores)
+}
+
+fn create_statistics_query_pools(device: &Arc<Device>, count: usize) -> Result<Vec<StatisticsQueryPool>> {
+  debug!("Creating {} statistics query pools.", count);
+  let mut query_pools: Vec<StatisticsQueryPool> = Vec::with_capacity(count);
 
   for _ in 0..count {
-    let fence = Fence::new(device, vk::FenceCreateFlags::SIGNALED)?;
-    fences.push(fence);
+    let query_pool = StatisticsQueryPool::new(device)?;
+    query_pools.push(query_pool);
   }
 
-  Ok(fences)
+  Ok(query_pools)
 }
 
-fn create_global_descriptor_set_info(swapchain_extent: &vk::Extent2D) -> GlobalDescriptorSetInfo {
-  let camera_pos = glm::Vec3::new(1.0, 1.0, 1.5);
+fn create_global_descriptor_set_info(swapchain_extent: &vk::Extent2D, environment_intensity: f32, has_environment_map: bool) -> GlobalDescriptorSetInfo {
+  let (view, projection) = camera_matrices(swapchain_extent);
+
+  GlobalDescriptorSetInfo {
+    view,
+    projection,
+    environment_intensity,
+    has_environment_map: has_environment_map as u32,
+  }
+}
+
+fn camera_matrices(swapchain_extent: &vk::Extent2D) -> (glm::Mat4, glm::Mat4) {
+  let camera_pos = glm::Vec3::from(CAMERA_POSITION);
   let center_pos = glm::Vec3::new(-2.0, -2.0, 0.0);
   let up_direction = glm::Vec3::new(0.0, 0.0, -1.0);
   let view = glm::look_at(&camera_pos, &center_pos, &up_direction);
 
+  (view, projection_matrix(swapchain_extent))
+}
+
+pub(super) fn projection_matrix(extent: &vk::Extent2D) -> glm::Mat4 {
   let fov_y_radians = 80.0 * std::f32::consts::PI / 180.0;
-  let aspect_ratio = swapchain_extent.width as f32 / swapchain_extent.height as f32;
+  let aspect_ratio = extent.width as f32 / extent.height as f32;
   let z_near = 0.1;
   let z_far = 10.0;
-  let projection = glm::perspective(aspect_ratio, fov_y_radians, z_near, z_far);
-
-  GlobalDescriptorSetInfo {
-    view,
-    projection,
-    model: glm::rotate_z(&glm::Mat4::identity(), 2.0),
-  }
+  glm::perspective(aspect_ratio, fov_y_radians, z_near, z_far)
 }